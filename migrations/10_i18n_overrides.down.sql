@@ -0,0 +1 @@
+DROP TABLE i18n_overrides;
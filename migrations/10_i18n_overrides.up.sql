@@ -0,0 +1,7 @@
+CREATE TABLE i18n_overrides (
+    lang TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    CONSTRAINT pk_i18n_overrides
+        PRIMARY KEY (lang, key)
+);
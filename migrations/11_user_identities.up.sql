@@ -0,0 +1,13 @@
+CREATE TABLE user_identities (
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    user INTEGER NOT NULL
+        CONSTRAINT fk_user_identities_user
+        REFERENCES users (id)
+        ON UPDATE CASCADE
+        ON DELETE CASCADE,
+    CONSTRAINT pk_user_identities
+        PRIMARY KEY (issuer, subject)
+);
+
+CREATE INDEX ix_user_identities_user ON user_identities (user);
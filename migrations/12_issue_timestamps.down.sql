@@ -0,0 +1,9 @@
+DROP TRIGGER tr_issues_updated_at;
+
+DROP TRIGGER tr_issues_created_at;
+
+DROP INDEX ix_issues_created_at;
+
+ALTER TABLE issues DROP COLUMN updated_at;
+
+ALTER TABLE issues DROP COLUMN created_at;
@@ -0,0 +1,23 @@
+ALTER TABLE issues ADD COLUMN created_at INTEGER DEFAULT NULL;
+
+ALTER TABLE issues ADD COLUMN updated_at INTEGER DEFAULT NULL;
+
+CREATE INDEX ix_issues_created_at ON issues (created_at);
+
+CREATE TRIGGER tr_issues_created_at
+    AFTER INSERT ON issues
+    FOR EACH ROW
+    WHEN NEW.created_at IS NULL
+BEGIN
+    UPDATE issues
+        SET created_at = unixepoch(), updated_at = unixepoch()
+        WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER tr_issues_updated_at
+    AFTER UPDATE ON issues
+    FOR EACH ROW
+    WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE issues SET updated_at = unixepoch() WHERE id = NEW.id;
+END;
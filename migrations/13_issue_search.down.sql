@@ -0,0 +1,7 @@
+DROP TRIGGER tr_issues_fts_update;
+
+DROP TRIGGER tr_issues_fts_delete;
+
+DROP TRIGGER tr_issues_fts_insert;
+
+DROP TABLE issues_fts;
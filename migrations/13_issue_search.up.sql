@@ -0,0 +1,35 @@
+CREATE VIRTUAL TABLE issues_fts USING fts5 (
+    title,
+    description,
+    content = 'issues',
+    content_rowid = 'id',
+    tokenize = 'porter unicode61'
+);
+
+INSERT INTO issues_fts (issues_fts) VALUES ('rebuild');
+
+CREATE TRIGGER tr_issues_fts_insert
+    AFTER INSERT ON issues
+    FOR EACH ROW
+BEGIN
+    INSERT INTO issues_fts (rowid, title, description)
+        VALUES (NEW.id, NEW.title, NEW.description);
+END;
+
+CREATE TRIGGER tr_issues_fts_delete
+    AFTER DELETE ON issues
+    FOR EACH ROW
+BEGIN
+    INSERT INTO issues_fts (issues_fts, rowid, title, description)
+        VALUES ('delete', OLD.id, OLD.title, OLD.description);
+END;
+
+CREATE TRIGGER tr_issues_fts_update
+    AFTER UPDATE OF title, description ON issues
+    FOR EACH ROW
+BEGIN
+    INSERT INTO issues_fts (issues_fts, rowid, title, description)
+        VALUES ('delete', OLD.id, OLD.title, OLD.description);
+    INSERT INTO issues_fts (rowid, title, description)
+        VALUES (NEW.id, NEW.title, NEW.description);
+END;
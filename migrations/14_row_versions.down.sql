@@ -0,0 +1,3 @@
+ALTER TABLE issues DROP COLUMN version;
+
+ALTER TABLE issue_statuses DROP COLUMN version;
@@ -0,0 +1,3 @@
+ALTER TABLE issue_statuses ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+ALTER TABLE issues ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,12 @@
+CREATE TABLE maintenance (
+    id INTEGER NOT NULL
+        CONSTRAINT pk_maintenance
+        PRIMARY KEY
+        CONSTRAINT ck_maintenance_single_row
+        CHECK (id = 1),
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    retry_after INTEGER NOT NULL DEFAULT 3600,
+    message TEXT DEFAULT NULL
+);
+
+INSERT INTO maintenance (id) VALUES (1);
@@ -0,0 +1,15 @@
+CREATE TABLE canned_replies (
+    id INTEGER NOT NULL
+        CONSTRAINT pk_canned_replies
+        PRIMARY KEY AUTOINCREMENT,
+    user INTEGER NOT NULL
+        CONSTRAINT fk_canned_replies_user
+        REFERENCES users (id)
+        ON UPDATE CASCADE
+        ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    body TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    CONSTRAINT un_canned_replies_user_name
+        UNIQUE (user, name)
+);
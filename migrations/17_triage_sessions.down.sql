@@ -0,0 +1,3 @@
+DROP TABLE triage_session_items;
+
+DROP TABLE triage_sessions;
@@ -0,0 +1,31 @@
+CREATE TABLE triage_sessions (
+    id INTEGER NOT NULL
+        CONSTRAINT pk_triage_sessions
+        PRIMARY KEY AUTOINCREMENT,
+    user INTEGER NOT NULL
+        CONSTRAINT fk_triage_sessions_user
+        REFERENCES users (id)
+        ON UPDATE CASCADE
+        ON DELETE CASCADE,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    expires_at INTEGER NOT NULL,
+    submitted_at INTEGER DEFAULT NULL
+);
+
+CREATE TABLE triage_session_items (
+    session INTEGER NOT NULL
+        CONSTRAINT fk_triage_session_items_session
+        REFERENCES triage_sessions (id)
+        ON UPDATE CASCADE
+        ON DELETE CASCADE,
+    issue INTEGER NOT NULL
+        CONSTRAINT fk_triage_session_items_issue
+        REFERENCES issues (id)
+        ON UPDATE CASCADE
+        ON DELETE CASCADE,
+    decision TEXT DEFAULT NULL,
+    CONSTRAINT pk_triage_session_items
+        PRIMARY KEY (session, issue)
+);
+
+CREATE INDEX ix_triage_session_items_issue ON triage_session_items (issue);
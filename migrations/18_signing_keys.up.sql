@@ -0,0 +1,19 @@
+CREATE TABLE signing_keys (
+    id INTEGER NOT NULL
+        CONSTRAINT pk_signing_keys
+        PRIMARY KEY AUTOINCREMENT,
+    user INTEGER NOT NULL
+        CONSTRAINT fk_signing_keys_user
+        REFERENCES users (id)
+        ON UPDATE CASCADE
+        ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    key_id TEXT NOT NULL
+        CONSTRAINT un_signing_keys_key_id
+        UNIQUE,
+    secret TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    last_used_at INTEGER DEFAULT NULL
+);
+
+CREATE INDEX ix_signing_keys_user ON signing_keys (user);
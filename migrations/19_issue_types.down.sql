@@ -0,0 +1,7 @@
+DROP INDEX ix_issues_type;
+
+ALTER TABLE issues DROP COLUMN type;
+
+DROP TABLE issue_type_statuses;
+
+DROP TABLE issue_types;
@@ -0,0 +1,33 @@
+CREATE TABLE issue_types (
+    id INTEGER NOT NULL
+        CONSTRAINT pk_issue_types
+        PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL
+        CONSTRAINT un_issue_types_name
+        UNIQUE,
+    template TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE issue_type_statuses (
+    type INTEGER NOT NULL
+        CONSTRAINT fk_issue_type_statuses_type
+        REFERENCES issue_types (id)
+        ON UPDATE CASCADE
+        ON DELETE CASCADE,
+    status INTEGER NOT NULL
+        CONSTRAINT fk_issue_type_statuses_status
+        REFERENCES issue_statuses (id)
+        ON UPDATE CASCADE
+        ON DELETE CASCADE,
+    CONSTRAINT pk_issue_type_statuses
+        PRIMARY KEY (type, status)
+);
+
+ALTER TABLE issues
+    ADD COLUMN type INTEGER DEFAULT NULL
+        CONSTRAINT fk_issues_type
+        REFERENCES issue_types (id)
+        ON UPDATE CASCADE
+        ON DELETE SET NULL;
+
+CREATE INDEX ix_issues_type ON issues (type);
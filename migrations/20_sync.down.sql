@@ -0,0 +1,55 @@
+DROP TRIGGER tr_issue_type_statuses_sync_delete;
+
+DROP TRIGGER tr_issue_type_statuses_sync_insert;
+
+DROP TRIGGER tr_issue_labels_sync_delete;
+
+DROP TRIGGER tr_issue_labels_sync_insert;
+
+DROP TRIGGER tr_issues_sync_delete;
+
+DROP TRIGGER tr_issues_sync_update;
+
+DROP TRIGGER tr_issues_sync_insert;
+
+DROP TRIGGER tr_versions_sync_delete;
+
+DROP TRIGGER tr_versions_sync_update;
+
+DROP TRIGGER tr_versions_sync_insert;
+
+DROP TRIGGER tr_milestones_sync_delete;
+
+DROP TRIGGER tr_milestones_sync_update;
+
+DROP TRIGGER tr_milestones_sync_insert;
+
+DROP TRIGGER tr_labels_sync_delete;
+
+DROP TRIGGER tr_labels_sync_update;
+
+DROP TRIGGER tr_labels_sync_insert;
+
+DROP TRIGGER tr_issue_types_sync_delete;
+
+DROP TRIGGER tr_issue_types_sync_update;
+
+DROP TRIGGER tr_issue_types_sync_insert;
+
+DROP TRIGGER tr_issue_statuses_sync_delete;
+
+DROP TRIGGER tr_issue_statuses_sync_update;
+
+DROP TRIGGER tr_issue_statuses_sync_insert;
+
+DROP TABLE sync_peers;
+
+DROP TABLE sync_pending_parents;
+
+DROP TRIGGER tr_sync_changes_global_id;
+
+DROP TABLE sync_ids;
+
+DROP TABLE sync_changes;
+
+DROP TABLE sync_instance;
@@ -0,0 +1,287 @@
+CREATE TABLE sync_instance (
+    id INTEGER NOT NULL
+        CONSTRAINT pk_sync_instance
+        PRIMARY KEY
+        CONSTRAINT ck_sync_instance_single_row
+        CHECK (id = 1),
+    instance_id TEXT NOT NULL
+);
+
+INSERT INTO sync_instance (id, instance_id)
+    VALUES (1, lower(hex(randomblob(16))));
+
+CREATE TABLE sync_changes (
+    seq INTEGER NOT NULL
+        CONSTRAINT pk_sync_changes
+        PRIMARY KEY AUTOINCREMENT,
+    entity TEXT NOT NULL,
+    row_id INTEGER NOT NULL,
+    deleted BOOLEAN NOT NULL,
+    origin TEXT DEFAULT NULL,
+    changed_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    global_id TEXT DEFAULT NULL
+);
+
+CREATE INDEX ix_sync_changes_entity_row ON sync_changes (entity, row_id);
+
+CREATE INDEX ix_sync_changes_entity_global
+    ON sync_changes (entity, global_id);
+
+-- Each instance numbers its own rows, so synced rows are told apart by a
+-- random global id instead. A row may be known by more than one global id
+-- when two instances created it under the same name; the first one is the
+-- id it is sent under.
+CREATE TABLE sync_ids (
+    global_id TEXT NOT NULL
+        CONSTRAINT pk_sync_ids
+        PRIMARY KEY,
+    entity TEXT NOT NULL,
+    row_id INTEGER NOT NULL
+);
+
+CREATE INDEX ix_sync_ids_entity_row ON sync_ids (entity, row_id);
+
+CREATE TRIGGER tr_sync_changes_global_id
+    AFTER INSERT ON sync_changes
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_ids (global_id, entity, row_id)
+        SELECT lower(hex(randomblob(16))), NEW.entity, NEW.row_id
+        WHERE NOT NEW.deleted
+        AND NOT EXISTS (
+            SELECT 1 FROM sync_ids
+            WHERE entity = NEW.entity AND row_id = NEW.row_id
+        );
+    UPDATE sync_changes
+        SET global_id = (
+            SELECT global_id FROM sync_ids
+            WHERE entity = NEW.entity AND row_id = NEW.row_id
+            ORDER BY rowid LIMIT 1
+        )
+        WHERE seq = NEW.seq;
+    -- Only the latest change of a row is ever sent, so earlier ones are
+    -- dropped rather than kept for every edit.
+    DELETE FROM sync_changes
+        WHERE entity = NEW.entity
+        AND global_id = (SELECT global_id FROM sync_changes WHERE seq = NEW.seq)
+        AND seq < NEW.seq;
+    -- The deletion keeps the global id it is sent under.
+    DELETE FROM sync_ids
+        WHERE NEW.deleted AND entity = NEW.entity AND row_id = NEW.row_id;
+END;
+
+-- Parents received before the issue they point to.
+CREATE TABLE sync_pending_parents (
+    issue INTEGER NOT NULL
+        CONSTRAINT pk_sync_pending_parents
+        PRIMARY KEY
+        CONSTRAINT fk_sync_pending_parents_issue
+        REFERENCES issues (id)
+        ON DELETE CASCADE,
+    parent TEXT NOT NULL
+);
+
+CREATE TABLE sync_peers (
+    instance_id TEXT NOT NULL
+        CONSTRAINT pk_sync_peers
+        PRIMARY KEY,
+    pulled_seq INTEGER NOT NULL DEFAULT 0,
+    pushed_seq INTEGER NOT NULL DEFAULT 0,
+    synced_at INTEGER DEFAULT NULL
+);
+
+-- Rows that already exist are recorded once, so that a first sync sends
+-- them under a fresh global id.
+INSERT INTO sync_changes (entity, row_id, deleted)
+    SELECT 'issue_statuses', id, FALSE FROM issue_statuses ORDER BY id;
+INSERT INTO sync_changes (entity, row_id, deleted)
+    SELECT 'issue_types', id, FALSE FROM issue_types ORDER BY id;
+INSERT INTO sync_changes (entity, row_id, deleted)
+    SELECT 'labels', id, FALSE FROM labels ORDER BY id;
+INSERT INTO sync_changes (entity, row_id, deleted)
+    SELECT 'milestones', id, FALSE FROM milestones ORDER BY id;
+INSERT INTO sync_changes (entity, row_id, deleted)
+    SELECT 'versions', id, FALSE FROM versions ORDER BY id;
+INSERT INTO sync_changes (entity, row_id, deleted)
+    SELECT 'issues', id, FALSE FROM issues ORDER BY id;
+
+CREATE TRIGGER tr_issue_statuses_sync_insert
+    AFTER INSERT ON issue_statuses
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('issue_statuses', NEW.id, FALSE);
+END;
+
+CREATE TRIGGER tr_issue_statuses_sync_update
+    AFTER UPDATE ON issue_statuses
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('issue_statuses', NEW.id, FALSE);
+END;
+
+CREATE TRIGGER tr_issue_statuses_sync_delete
+    AFTER DELETE ON issue_statuses
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('issue_statuses', OLD.id, TRUE);
+END;
+
+CREATE TRIGGER tr_issue_types_sync_insert
+    AFTER INSERT ON issue_types
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('issue_types', NEW.id, FALSE);
+END;
+
+CREATE TRIGGER tr_issue_types_sync_update
+    AFTER UPDATE ON issue_types
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('issue_types', NEW.id, FALSE);
+END;
+
+CREATE TRIGGER tr_issue_types_sync_delete
+    AFTER DELETE ON issue_types
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('issue_types', OLD.id, TRUE);
+END;
+
+CREATE TRIGGER tr_labels_sync_insert
+    AFTER INSERT ON labels
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('labels', NEW.id, FALSE);
+END;
+
+CREATE TRIGGER tr_labels_sync_update
+    AFTER UPDATE ON labels
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('labels', NEW.id, FALSE);
+END;
+
+CREATE TRIGGER tr_labels_sync_delete
+    AFTER DELETE ON labels
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('labels', OLD.id, TRUE);
+END;
+
+CREATE TRIGGER tr_milestones_sync_insert
+    AFTER INSERT ON milestones
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('milestones', NEW.id, FALSE);
+END;
+
+CREATE TRIGGER tr_milestones_sync_update
+    AFTER UPDATE ON milestones
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('milestones', NEW.id, FALSE);
+END;
+
+CREATE TRIGGER tr_milestones_sync_delete
+    AFTER DELETE ON milestones
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('milestones', OLD.id, TRUE);
+END;
+
+CREATE TRIGGER tr_versions_sync_insert
+    AFTER INSERT ON versions
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('versions', NEW.id, FALSE);
+END;
+
+CREATE TRIGGER tr_versions_sync_update
+    AFTER UPDATE ON versions
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('versions', NEW.id, FALSE);
+END;
+
+CREATE TRIGGER tr_versions_sync_delete
+    AFTER DELETE ON versions
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('versions', OLD.id, TRUE);
+END;
+
+CREATE TRIGGER tr_issues_sync_insert
+    AFTER INSERT ON issues
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('issues', NEW.id, FALSE);
+END;
+
+CREATE TRIGGER tr_issues_sync_update
+    AFTER UPDATE ON issues
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('issues', NEW.id, FALSE);
+END;
+
+CREATE TRIGGER tr_issues_sync_delete
+    AFTER DELETE ON issues
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('issues', OLD.id, TRUE);
+END;
+
+-- Labels travel with their issue. Links removed because the issue itself
+-- was deleted are already covered by the deletion of the issue.
+CREATE TRIGGER tr_issue_labels_sync_insert
+    AFTER INSERT ON issue_labels
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('issues', NEW.issue, FALSE);
+END;
+
+CREATE TRIGGER tr_issue_labels_sync_delete
+    AFTER DELETE ON issue_labels
+    FOR EACH ROW
+    WHEN EXISTS (SELECT 1 FROM issues WHERE id = OLD.issue)
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('issues', OLD.issue, FALSE);
+END;
+
+-- Allowed statuses travel with their issue type.
+CREATE TRIGGER tr_issue_type_statuses_sync_insert
+    AFTER INSERT ON issue_type_statuses
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('issue_types', NEW.type, FALSE);
+END;
+
+CREATE TRIGGER tr_issue_type_statuses_sync_delete
+    AFTER DELETE ON issue_type_statuses
+    FOR EACH ROW
+    WHEN EXISTS (SELECT 1 FROM issue_types WHERE id = OLD.type)
+BEGIN
+    INSERT INTO sync_changes (entity, row_id, deleted)
+        VALUES ('issue_types', OLD.type, FALSE);
+END;
@@ -0,0 +1,19 @@
+CREATE TABLE issue_checks (
+    id INTEGER NOT NULL
+        CONSTRAINT pk_issue_checks
+        PRIMARY KEY AUTOINCREMENT,
+    issue INTEGER NOT NULL
+        CONSTRAINT fk_issue_checks_issue
+        REFERENCES issues (id)
+        ON UPDATE CASCADE
+        ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    state TEXT NOT NULL
+        CONSTRAINT ck_issue_checks_state
+        CHECK (state IN ('pending', 'success', 'failure', 'error')),
+    url TEXT DEFAULT NULL,
+    summary TEXT DEFAULT NULL,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE INDEX ix_issue_checks_issue_name ON issue_checks (issue, name);
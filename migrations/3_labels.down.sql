@@ -0,0 +1,3 @@
+DROP TABLE issue_labels;
+
+DROP TABLE labels;
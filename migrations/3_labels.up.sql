@@ -0,0 +1,25 @@
+CREATE TABLE labels (
+    id INTEGER NOT NULL
+        CONSTRAINT pk_labels
+        PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL
+        CONSTRAINT un_labels_name
+        UNIQUE
+);
+
+CREATE TABLE issue_labels (
+    issue INTEGER NOT NULL
+        CONSTRAINT fk_issue_labels_issue
+        REFERENCES issues (id)
+        ON UPDATE CASCADE
+        ON DELETE CASCADE,
+    label INTEGER NOT NULL
+        CONSTRAINT fk_issue_labels_label
+        REFERENCES labels (id)
+        ON UPDATE CASCADE
+        ON DELETE CASCADE,
+    CONSTRAINT pk_issue_labels
+        PRIMARY KEY (issue, label)
+);
+
+CREATE INDEX ix_issue_labels_label ON issue_labels (label);
@@ -0,0 +1,5 @@
+ALTER TABLE issues DROP COLUMN milestone;
+
+DROP TABLE milestones;
+
+ALTER TABLE issue_statuses DROP COLUMN closed;
@@ -0,0 +1,22 @@
+ALTER TABLE issue_statuses
+    ADD COLUMN closed BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE milestones (
+    id INTEGER NOT NULL
+        CONSTRAINT pk_milestones
+        PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL
+        CONSTRAINT un_milestones_name
+        UNIQUE,
+    description TEXT NOT NULL DEFAULT '',
+    due_date TEXT DEFAULT NULL
+        CONSTRAINT ck_milestones_due_date
+        CHECK (due_date IS NULL OR date(due_date) IS due_date)
+);
+
+ALTER TABLE issues
+    ADD COLUMN milestone INTEGER DEFAULT NULL
+        CONSTRAINT fk_issues_milestone
+        REFERENCES milestones (id)
+        ON UPDATE CASCADE
+        ON DELETE SET NULL;
@@ -0,0 +1,5 @@
+ALTER TABLE issues DROP COLUMN fixed_in_version;
+
+ALTER TABLE issues DROP COLUMN affects_version;
+
+DROP TABLE versions;
@@ -0,0 +1,25 @@
+CREATE TABLE versions (
+    id INTEGER NOT NULL
+        CONSTRAINT pk_versions
+        PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL
+        CONSTRAINT un_versions_name
+        UNIQUE,
+    release_date TEXT DEFAULT NULL
+        CONSTRAINT ck_versions_release_date
+        CHECK (release_date IS NULL OR date(release_date) IS release_date)
+);
+
+ALTER TABLE issues
+    ADD COLUMN affects_version INTEGER DEFAULT NULL
+        CONSTRAINT fk_issues_affects_version
+        REFERENCES versions (id)
+        ON UPDATE CASCADE
+        ON DELETE SET NULL;
+
+ALTER TABLE issues
+    ADD COLUMN fixed_in_version INTEGER DEFAULT NULL
+        CONSTRAINT fk_issues_fixed_in_version
+        REFERENCES versions (id)
+        ON UPDATE CASCADE
+        ON DELETE SET NULL;
@@ -0,0 +1,11 @@
+CREATE TABLE users (
+    id INTEGER NOT NULL
+        CONSTRAINT pk_users
+        PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL
+        CONSTRAINT un_users_name
+        UNIQUE,
+    password_hash TEXT NOT NULL,
+    is_admin BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
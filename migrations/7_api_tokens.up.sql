@@ -0,0 +1,18 @@
+CREATE TABLE api_tokens (
+    id INTEGER NOT NULL
+        CONSTRAINT pk_api_tokens
+        PRIMARY KEY AUTOINCREMENT,
+    user INTEGER NOT NULL
+        CONSTRAINT fk_api_tokens_user
+        REFERENCES users (id)
+        ON UPDATE CASCADE
+        ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL
+        CONSTRAINT un_api_tokens_token_hash
+        UNIQUE,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    last_used_at INTEGER DEFAULT NULL
+);
+
+CREATE INDEX ix_api_tokens_user ON api_tokens (user);
@@ -0,0 +1,17 @@
+CREATE TABLE sessions (
+    id INTEGER NOT NULL
+        CONSTRAINT pk_sessions
+        PRIMARY KEY AUTOINCREMENT,
+    user INTEGER NOT NULL
+        CONSTRAINT fk_sessions_user
+        REFERENCES users (id)
+        ON UPDATE CASCADE
+        ON DELETE CASCADE,
+    token_hash TEXT NOT NULL
+        CONSTRAINT un_sessions_token_hash
+        UNIQUE,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX ix_sessions_user ON sessions (user);
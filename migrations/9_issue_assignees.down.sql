@@ -0,0 +1,3 @@
+DROP INDEX ix_issues_assignee;
+
+ALTER TABLE issues DROP COLUMN assignee;
@@ -0,0 +1,8 @@
+ALTER TABLE issues
+    ADD COLUMN assignee INTEGER DEFAULT NULL
+        CONSTRAINT fk_issues_assignee
+        REFERENCES users (id)
+        ON UPDATE CASCADE
+        ON DELETE SET NULL;
+
+CREATE INDEX ix_issues_assignee ON issues (assignee);
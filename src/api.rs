@@ -1,19 +1,107 @@
-use std::sync::Arc;
+use std::{
+    collections::hash_map::RandomState,
+    error::Error,
+    hash::{BuildHasher, Hasher},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
-use axum::Router;
+use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{get, post},
+    Extension,
+    Router,
+};
 use futures::future::BoxFuture;
-use sqlx::{pool::PoolConnection, Pool, SqlitePool, Transaction};
+use sqlx::{
+    error::DatabaseError,
+    pool::PoolConnection,
+    Pool,
+    SqlitePool,
+    Transaction,
+};
+use tracing::{field, Span};
 
-use crate::RDBMS;
+use crate::{
+    maintenance::Maintenance,
+    session::SessionConfig,
+    upgrade::UpgradeState,
+    HttpConfig,
+    RDBMS,
+};
 
+mod admin;
+mod auth;
+mod batch;
+mod body_limit;
+mod build;
+mod canned;
+mod check;
+mod clock;
+mod context;
+mod cursor;
+mod edit_lock;
+mod etag;
+mod export;
+mod form;
+mod i18n;
+mod issue;
+mod issue_type;
+mod jsonapi;
+mod label;
+mod maintenance;
+mod milestone;
+mod network;
+mod precondition;
+mod presence;
+mod read_only;
 mod response;
+mod schema;
+mod search;
+mod shape;
+mod signature;
+mod sort;
 mod status;
+mod sync;
+mod throttle;
+mod token;
+mod triage;
+mod undo;
+mod version;
+
+use clock::{Clock, SystemClock};
+use context::RequestContext;
+
+pub(crate) use auth::hash_password;
+pub use auth::{OidcConfig, PasswordError};
+pub use body_limit::BodyLimits;
+pub use network::{reject_denied, AdminListener, NetworkAcl, NetworkAclError};
 
 struct Resources {
     pool: Pool<RDBMS>,
+    upgrade: UpgradeState,
+    sessions: SessionConfig,
+    oidc: Option<OidcConfig>,
+    presence: presence::Presence,
+    edit_locks: edit_lock::EditLocks,
+    read_only: read_only::ReadOnly,
+    maintenance: Arc<Maintenance>,
+    throttle: throttle::Throttle,
+    undo: undo::Undo,
+    network: NetworkAcl,
+    replays: signature::ReplayGuard,
+    backup_dir: Option<PathBuf>,
+    clock: Arc<dyn Clock>,
 }
 
 impl Resources {
+    #[tracing::instrument(
+        name = "db.connection",
+        skip_all,
+        fields(actor = field::Empty, locale = field::Empty),
+    )]
     pub async fn with_bare_conn<F, T, E>(&self, callback: F) -> Result<T, E>
     where
         F: for<'c> FnOnce(
@@ -21,12 +109,18 @@ impl Resources {
         ) -> BoxFuture<'c, Result<T, E>>,
         E: From<sqlx::Error>,
     {
+        RequestContext::record_current(&Span::current());
         let mut conn = self.pool.acquire().await?;
         let result = callback(&mut conn).await;
         conn.close().await?;
         result
     }
 
+    #[tracing::instrument(
+        name = "db.transaction",
+        skip_all,
+        fields(actor = field::Empty, locale = field::Empty),
+    )]
     pub async fn with_transaction<F, T, E>(&self, callback: F) -> Result<T, E>
     where
         F: for<'c> FnOnce(
@@ -34,6 +128,7 @@ impl Resources {
         ) -> BoxFuture<'c, Result<T, E>>,
         E: From<sqlx::Error>,
     {
+        RequestContext::record_current(&Span::current());
         let mut transaction = self.pool.begin().await?;
         let result = callback(&mut transaction).await;
         if result.is_ok() {
@@ -43,9 +138,186 @@ impl Resources {
         }
         result
     }
+
+    /// Like `with_transaction`, but runs the callback again from a fresh
+    /// transaction when SQLite reports the database as busy or locked.
+    #[tracing::instrument(
+        name = "db.retrying_transaction",
+        skip_all,
+        fields(actor = field::Empty, locale = field::Empty),
+    )]
+    pub async fn with_retrying_transaction<F, T, E>(
+        &self,
+        callback: F,
+    ) -> Result<T, E>
+    where
+        F: for<'c> Fn(
+            &'c mut Transaction<RDBMS>,
+        ) -> BoxFuture<'c, Result<T, E>>,
+        E: From<sqlx::Error> + Error + 'static,
+    {
+        RequestContext::record_current(&Span::current());
+        let mut attempt = 0;
+        loop {
+            let result = self.with_transaction(&callback).await;
+            match result {
+                Err(error) if attempt < BUSY_RETRIES && is_busy(&error) => {
+                    attempt += 1;
+                    tokio::time::sleep(busy_backoff(attempt)).await;
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Expensive requests allowed to run at once.
+const EXPENSIVE_CONCURRENCY: usize = 4;
+
+/// Expensive requests allowed to wait for a turn before 429 is answered.
+const EXPENSIVE_QUEUE: usize = 64;
+
+/// Extra attempts made by `Resources::with_retrying_transaction`.
+const BUSY_RETRIES: u32 = 4;
+
+fn is_busy(error: &(dyn Error + 'static)) -> bool {
+    const SQLITE_BUSY: i32 = 5;
+    const SQLITE_LOCKED: i32 = 6;
+    let mut next = Some(error);
+    while let Some(current) = next {
+        if let Some(sqlx::Error::Database(error)) = current.downcast_ref() {
+            let code = error.code().and_then(|code| code.parse::<i32>().ok());
+            // Extended result codes keep the primary code in the low byte.
+            if let Some(code) = code {
+                if matches!(code & 0xff, SQLITE_BUSY | SQLITE_LOCKED) {
+                    return true;
+                }
+            }
+        }
+        next = current.source();
+    }
+    false
+}
+
+/// Exponential backoff starting at 10ms, with up to 50% random jitter so
+/// that competing writers do not retry in lockstep.
+fn busy_backoff(attempt: u32) -> Duration {
+    let base = Duration::from_millis(10 << attempt.min(6));
+    let random = RandomState::new().build_hasher().finish();
+    base + base.mul_f64((random % 1000) as f64 / 2000.0)
+}
+
+fn is_foreign_key_violation(error: &dyn DatabaseError) -> bool {
+    // SQLite reports `ON DELETE RESTRICT` failures as trigger constraints.
+    error.is_foreign_key_violation()
+        || error.message() == "FOREIGN KEY constraint failed"
 }
 
-pub fn router(pool: SqlitePool) -> Router {
-    let resources = Arc::new(Resources { pool });
-    Router::new().nest("/status/", status::router(resources))
+pub fn router(
+    pool: SqlitePool,
+    upgrade: UpgradeState,
+    sessions: SessionConfig,
+    oidc: Option<OidcConfig>,
+    read_only: bool,
+    maintenance: Arc<Maintenance>,
+    http: &HttpConfig,
+) -> Router {
+    let body_limits = http.body_limits;
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let resources = Arc::new(Resources {
+        pool,
+        upgrade,
+        sessions,
+        oidc,
+        presence: presence::Presence::new(clock.clone()),
+        edit_locks: edit_lock::EditLocks::new(clock.clone()),
+        read_only: read_only::ReadOnly::new(read_only),
+        maintenance,
+        throttle: throttle::Throttle::new(
+            EXPENSIVE_CONCURRENCY,
+            EXPENSIVE_QUEUE,
+        ),
+        undo: undo::Undo::new(clock.clone(), http.undo_window),
+        replays: signature::ReplayGuard::new(clock.clone()),
+        network: http.network.clone(),
+        backup_dir: http.backup_dir.clone(),
+        clock,
+    });
+    let api = Router::new()
+        .nest("/auth/", auth::router(resources.clone()))
+        .nest("/canned/", canned::router(resources.clone()))
+        .nest("/i18n/", i18n::router(resources.clone()))
+        .nest("/issue/", issue::router(resources.clone()))
+        .nest("/issue-type/", issue_type::router(resources.clone()))
+        .nest("/label/", label::router(resources.clone()))
+        .nest("/milestone/", milestone::router(resources.clone()))
+        .nest("/presence/", presence::router(resources.clone()))
+        .nest("/schema/", schema::router())
+        .nest("/signing-keys/", signature::router(resources.clone()))
+        .nest("/status/", status::router(resources.clone()))
+        .nest("/sync/", sync::router(resources.clone()))
+        .nest("/tokens/", token::router(resources.clone()))
+        .nest("/triage/", triage::router(resources.clone()))
+        .nest("/undo/", undo::router(resources.clone()))
+        .nest("/versions/", version::router(resources.clone()))
+        .route(
+            "/export",
+            get({
+                let resources = resources.clone();
+                move |admin, query| export::get_export(admin, query, resources)
+            })
+            .layer(middleware::from_fn(throttle::limit)),
+        )
+        .route(
+            "/import",
+            post({
+                let resources = resources.clone();
+                move |admin, body| export::post_import(admin, body, resources)
+            }),
+        )
+        .route(
+            "/search",
+            get({
+                let resources = resources.clone();
+                move |query| search::get_search(query, resources)
+            })
+            .layer(middleware::from_fn(throttle::limit)),
+        )
+        .route(
+            "/triage",
+            get({
+                let resources = resources.clone();
+                move |user| triage::get_queue(user, resources)
+            }),
+        )
+        .route("/version", get(build::get_version))
+        .layer(middleware::from_fn(read_only::reject_writes))
+        .layer(middleware::from_fn(maintenance::reject_requests))
+        .nest("/admin/", admin::router(resources.clone()))
+        .layer(middleware::from_fn(context::scope_context))
+        .layer(middleware::from_fn(token::bearer_auth))
+        .layer(middleware::from_fn(signature::signature_auth))
+        .layer(middleware::from_fn(shape::shape_response))
+        .layer(middleware::from_fn(etag::conditional_get))
+        .layer(middleware::from_fn(network::restrict))
+        .layer(Extension(resources.clone()));
+    Router::new()
+        .route(
+            "/batch",
+            post({
+                let api = api.clone();
+                move |peer, admin_listener, user, body| {
+                    batch::post_batch(api, peer, admin_listener, user, body)
+                }
+            }),
+        )
+        .layer(middleware::from_fn(token::bearer_auth))
+        .layer(middleware::from_fn(signature::signature_auth))
+        .layer(middleware::from_fn(shape::shape_response))
+        .layer(Extension(resources))
+        .merge(api)
+        .layer(middleware::from_fn(move |request, next| {
+            body_limit::enforce(body_limits, request, next)
+        }))
+        .layer(DefaultBodyLimit::disable())
 }
@@ -0,0 +1,392 @@
+use std::{
+    convert::Infallible,
+    io,
+    path::{Component, PathBuf},
+    pin::Pin,
+    process,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::rejection::JsonRejection,
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderMap,
+        StatusCode,
+    },
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json,
+    Router,
+};
+use futures::Stream;
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::fs::{self, File};
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    dump::{self, DumpError, KeptTable},
+    maintenance::MaintenanceState,
+    status::ResponseStatusCode,
+    upgrade::UpgradeStatus,
+    version::BUILD_INFO,
+};
+
+use super::{auth::AdminUser, response::ApiResponse, throttle, Resources};
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct UpgradeResponse {
+    enabled: bool,
+    current_version: &'static str,
+    #[serde(flatten)]
+    latest: Option<UpgradeStatus>,
+}
+
+impl ResponseStatusCode for UpgradeResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct ReadOnlyPayload {
+    enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct ReadOnlyResponse {
+    enabled: bool,
+}
+
+impl ResponseStatusCode for ReadOnlyResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Failed to manipulate database resources")]
+struct MaintenanceError(#[from] sqlx::Error);
+
+impl ResponseStatusCode for MaintenanceError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+impl ResponseStatusCode for MaintenanceState {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct BackupPayload {
+    /// File in the configured backup directory to write the snapshot to.
+    /// The snapshot is downloaded instead when missing.
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct BackupResponse {
+    path: PathBuf,
+    size: u64,
+}
+
+impl ResponseStatusCode for BackupResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::CREATED
+    }
+}
+
+#[derive(Debug, Error)]
+enum BackupError {
+    #[error("Invalid backup request")]
+    Payload(#[source] JsonRejection),
+    #[error("Server-side backups are disabled")]
+    NoBackupDir,
+    #[error("Backup path must name a file in the backup directory")]
+    OutsideBackupDir,
+    #[error("Failed to back up database")]
+    Dump(
+        #[source]
+        #[from]
+        DumpError,
+    ),
+    #[error("Failed to read backup")]
+    Io(
+        #[source]
+        #[from]
+        io::Error,
+    ),
+}
+
+impl ResponseStatusCode for BackupError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Payload(rejection) => rejection.status(),
+            Self::NoBackupDir => StatusCode::FORBIDDEN,
+            Self::OutsideBackupDir => StatusCode::BAD_REQUEST,
+            Self::Dump(DumpError::OutputExists) => StatusCode::CONFLICT,
+            Self::Dump(DumpError::Sqlx(_)) | Self::Io(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            },
+        }
+    }
+}
+
+/// Streams a temporary snapshot and deletes it once the download finishes
+/// or is abandoned.
+struct SnapshotStream {
+    reader: Option<ReaderStream<File>>,
+    path: PathBuf,
+}
+
+impl Stream for SnapshotStream {
+    type Item = Result<Bytes, io::Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        match &mut self.reader {
+            Some(reader) => Pin::new(reader).poll_next(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+impl Drop for SnapshotStream {
+    fn drop(&mut self) {
+        drop(self.reader.take());
+        if let Err(error) = std::fs::remove_file(&self.path) {
+            tracing::warn!(
+                path = %self.path.display(),
+                %error,
+                "Failed to remove temporary backup"
+            );
+        }
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("UpgradeResponse", schema_for!(UpgradeResponse)),
+        ("ReadOnlyPayload", schema_for!(ReadOnlyPayload)),
+        ("ReadOnlyResponse", schema_for!(ReadOnlyResponse)),
+        ("MaintenanceState", schema_for!(MaintenanceState)),
+        ("BackupPayload", schema_for!(BackupPayload)),
+        ("BackupResponse", schema_for!(BackupResponse)),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/upgrade",
+            get({
+                let resources = resources.clone();
+                move |admin| get_upgrade(admin, resources)
+            }),
+        )
+        .route(
+            "/read-only",
+            get({
+                let resources = resources.clone();
+                move |admin| get_read_only(admin, resources)
+            })
+            .put({
+                let resources = resources.clone();
+                move |admin, body| put_read_only(admin, body, resources)
+            }),
+        )
+        .route(
+            "/maintenance",
+            get({
+                let resources = resources.clone();
+                move |admin| get_maintenance(admin, resources)
+            })
+            .put({
+                let resources = resources.clone();
+                move |admin, body| put_maintenance(admin, body, resources)
+            }),
+        )
+        .route(
+            "/backup",
+            post({
+                let resources = resources.clone();
+                move |admin, headers, body| {
+                    post_backup(admin, headers, body, resources)
+                }
+            })
+            .layer(middleware::from_fn(throttle::limit)),
+        )
+}
+
+async fn get_upgrade(
+    _admin: AdminUser,
+    resources: Arc<Resources>,
+) -> ApiResponse<UpgradeResponse, Infallible> {
+    ApiResponse::new(Ok(UpgradeResponse {
+        enabled: resources.upgrade.is_enabled(),
+        current_version: BUILD_INFO.version,
+        latest: resources.upgrade.latest(),
+    }))
+}
+
+async fn get_read_only(
+    _admin: AdminUser,
+    resources: Arc<Resources>,
+) -> ApiResponse<ReadOnlyResponse, Infallible> {
+    ApiResponse::new(Ok(ReadOnlyResponse {
+        enabled: resources.read_only.is_enabled(),
+    }))
+}
+
+async fn put_read_only(
+    _admin: AdminUser,
+    Json(payload): Json<ReadOnlyPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<ReadOnlyResponse, Infallible> {
+    resources.read_only.set(payload.enabled);
+    tracing::info!(enabled = payload.enabled, "Read-only mode changed");
+    ApiResponse::new(Ok(ReadOnlyResponse { enabled: payload.enabled }))
+}
+
+async fn get_maintenance(
+    _admin: AdminUser,
+    resources: Arc<Resources>,
+) -> ApiResponse<MaintenanceState, MaintenanceError> {
+    ApiResponse::new(resources.maintenance.state().await.map_err(Into::into))
+}
+
+async fn put_maintenance(
+    _admin: AdminUser,
+    Json(state): Json<MaintenanceState>,
+    resources: Arc<Resources>,
+) -> ApiResponse<MaintenanceState, MaintenanceError> {
+    if let Err(error) = resources.maintenance.set(state.clone()).await {
+        return ApiResponse::new(Err(error.into()));
+    }
+    tracing::info!(enabled = state.enabled, "Maintenance mode changed");
+    ApiResponse::new(Ok(state))
+}
+
+/// Snapshots the live database with `VACUUM INTO`, which reads a
+/// consistent view while the server keeps serving requests, as the online
+/// backup API would, and writes a compacted copy. A request without a body,
+/// and so without `Content-Type`, downloads the snapshot.
+async fn post_backup(
+    _admin: AdminUser,
+    headers: HeaderMap,
+    payload: Result<Json<BackupPayload>, JsonRejection>,
+    resources: Arc<Resources>,
+) -> Response {
+    let path = match payload {
+        Ok(Json(payload)) => payload.path,
+        Err(JsonRejection::MissingJsonContentType(_))
+            if !headers.contains_key(CONTENT_TYPE) =>
+        {
+            None
+        },
+        Err(rejection) => {
+            let error = BackupError::Payload(rejection);
+            return ApiResponse::<BackupResponse, _>::new(Err(error))
+                .into_response();
+        },
+    };
+    match path {
+        Some(path) => {
+            let result = backup_to(&resources, path).await;
+            ApiResponse::new(result).into_response()
+        },
+        None => download_snapshot(&resources, "backup.bin", None).await,
+    }
+}
+
+async fn backup_to(
+    resources: &Resources,
+    file: PathBuf,
+) -> Result<BackupResponse, BackupError> {
+    let dir = resources.backup_dir.as_ref().ok_or(BackupError::NoBackupDir)?;
+    let mut components = file.components();
+    let (Some(Component::Normal(_)), None) =
+        (components.next(), components.next())
+    else {
+        return Err(BackupError::OutsideBackupDir);
+    };
+    let path = dir.join(file);
+    dump::dump(&resources.pool, &path, false).await?;
+    let size = fs::metadata(&path).await?.len();
+    tracing::info!(path = %path.display(), size, "Database backed up");
+    Ok(BackupResponse { path, size })
+}
+
+/// Streams a fresh snapshot of the database as an attachment named
+/// `filename`, reduced to `tables` when given.
+pub(super) async fn download_snapshot(
+    resources: &Resources,
+    filename: &str,
+    tables: Option<&[KeptTable]>,
+) -> Response {
+    match stream_snapshot(resources, filename, tables).await {
+        Ok(response) => response,
+        Err(error) => {
+            ApiResponse::<BackupResponse, _>::new(Err(error)).into_response()
+        },
+    }
+}
+
+async fn stream_snapshot(
+    resources: &Resources,
+    filename: &str,
+    tables: Option<&[KeptTable]>,
+) -> Result<Response, BackupError> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    let path = std::env::temp_dir().join(format!(
+        "portable-issuer-backup-{}-{timestamp}.bin",
+        process::id()
+    ));
+    dump::dump(&resources.pool, &path, false).await?;
+    if let Some(tables) = tables {
+        if let Err(error) = dump::retain(&path, tables).await {
+            let _ = fs::remove_file(&path).await;
+            return Err(error.into());
+        }
+    }
+    let opened = async {
+        let file = File::open(&path).await?;
+        let length = file.metadata().await?.len();
+        Ok::<_, io::Error>((file, length))
+    };
+    let (file, length) = match opened.await {
+        Ok(opened) => opened,
+        Err(error) => {
+            let _ = fs::remove_file(&path).await;
+            return Err(error.into());
+        },
+    };
+    let stream = SnapshotStream { reader: Some(ReaderStream::new(file)), path };
+    tracing::info!(size = length, filename, "Database snapshot downloaded");
+    Ok((
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, "application/vnd.sqlite3".to_owned()),
+            (CONTENT_LENGTH, length.to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response())
+}
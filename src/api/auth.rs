@@ -0,0 +1,537 @@
+use std::{sync::Arc, time::Duration};
+
+use argon2::{
+    password_hash::{
+        self,
+        rand_core::OsRng,
+        PasswordHash,
+        PasswordHasher,
+        PasswordVerifier,
+        SaltString,
+    },
+    Argon2,
+};
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json,
+    Router,
+};
+use axum_extra::{
+    extract::cookie::{Cookie, SameSite, SignedCookieJar},
+    headers::{authorization::Basic, Authorization},
+    TypedHeader,
+};
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, sqlite::SqliteRow, Row, SqliteConnection};
+use thiserror::Error;
+use tokio::task::{self, JoinError};
+
+use crate::{session::SessionConfig, status::ResponseStatusCode};
+
+use super::{
+    context::RequestContext,
+    response::{ApiResponse, NoData},
+    token::{generate_token, hash_token},
+    Resources,
+};
+
+mod oidc;
+
+pub use oidc::OidcConfig;
+
+const SESSION_COOKIE: &str = "session";
+
+/// Hash of a random password nobody knows, checked when the user has no
+/// password to check against so that the response takes as long as for a
+/// wrong password and does not reveal which user names exist.
+const DUMMY_PASSWORD_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$\
+                                   GTf11r0NQDXtq939+/KMnQ$\
+                                   T/EeNBIauhsRtfh2gg0JYA8x0OzO/NaWXshab4bRIe4";
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct CredentialsPayload {
+    name: String,
+    password: String,
+}
+
+#[derive(Debug, Error)]
+pub enum PasswordError {
+    #[error("Failed to hash or verify password")]
+    Hash(#[source] password_hash::Error),
+    #[error("Password hashing task failed")]
+    Join(#[source] JoinError),
+}
+
+#[derive(Debug, Error)]
+enum RegisterError {
+    #[error("User name and password must not be empty")]
+    EmptyCredentials,
+    #[error("User with the given name already exists")]
+    AlreadyExists,
+    #[error("Failed to process password")]
+    Password(
+        #[source]
+        #[from]
+        PasswordError,
+    ),
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for RegisterError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if error.is_unique_violation() {
+                return Self::AlreadyExists;
+            }
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for RegisterError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::EmptyCredentials => StatusCode::BAD_REQUEST,
+            Self::AlreadyExists => StatusCode::FORBIDDEN,
+            Self::Password(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("Authentication credentials are missing")]
+    MissingCredentials,
+    #[error("Invalid user name or password")]
+    InvalidCredentials,
+    #[error("Invalid or revoked API token")]
+    InvalidToken,
+    #[error("Session is invalid or expired")]
+    InvalidSession,
+    #[error("Administrator privileges are required")]
+    NotAdmin,
+    #[error("Failed to process password")]
+    Password(
+        #[source]
+        #[from]
+        PasswordError,
+    ),
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for AuthError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::InvalidCredentials;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::MissingCredentials => StatusCode::UNAUTHORIZED,
+            Self::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            Self::InvalidToken => StatusCode::UNAUTHORIZED,
+            Self::InvalidSession => StatusCode::UNAUTHORIZED,
+            Self::NotAdmin => StatusCode::FORBIDDEN,
+            Self::Password(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        ApiResponse::<NoData, _>::new(Err(self)).into_response()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct UserResponse {
+    pub id: i64,
+    pub name: String,
+    pub is_admin: bool,
+    pub created_at: i64,
+}
+
+impl UserResponse {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            is_admin: row.try_get("is_admin")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+impl ResponseStatusCode for UserResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct LogoutResponse {
+    logged_out: bool,
+}
+
+impl ResponseStatusCode for LogoutResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CurrentUser(pub UserResponse);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CurrentUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(user) = parts.extensions.get::<Self>() {
+            return Ok(user.clone());
+        }
+        let context = RequestContext::current();
+        if let Some(user) = context.as_ref().and_then(RequestContext::user) {
+            return Ok(Self(user.clone()));
+        }
+        let user = Self::authenticate(parts, state).await?;
+        if let Some(context) = &context {
+            context.authenticated(&user.0);
+        }
+        Ok(user)
+    }
+}
+
+impl CurrentUser {
+    async fn authenticate<S>(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, AuthError>
+    where
+        S: Send + Sync,
+    {
+        let resources = parts
+            .extensions
+            .get::<Arc<Resources>>()
+            .cloned()
+            .expect("API resources must be installed as an extension");
+        let jar = SignedCookieJar::from_headers(
+            &parts.headers,
+            resources.sessions.key().clone(),
+        );
+        let session = jar.get(SESSION_COOKIE);
+        if let Some(cookie) = &session {
+            let token = cookie.value().to_owned();
+            let user = resources
+                .with_bare_conn(move |connection| {
+                    Box::pin(async move {
+                        user_for_session(connection, &token).await
+                    })
+                })
+                .await?;
+            if let Some(user) = user {
+                return Ok(Self(user));
+            }
+        }
+        let TypedHeader(Authorization(credentials)) =
+            TypedHeader::<Authorization<Basic>>::from_request_parts(
+                parts, state,
+            )
+            .await
+            .map_err(|_| match session {
+                Some(_) => AuthError::InvalidSession,
+                None => AuthError::MissingCredentials,
+            })?;
+        let name = credentials.username().to_owned();
+        let password = credentials.password().to_owned();
+        let user = resources
+            .with_bare_conn(move |connection| {
+                Box::pin(async move {
+                    authenticate(connection, &name, password).await
+                })
+            })
+            .await?;
+        Ok(Self(user))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AdminUser;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let CurrentUser(user) =
+            CurrentUser::from_request_parts(parts, state).await?;
+        if !user.is_admin {
+            return Err(AuthError::NotAdmin);
+        }
+        Ok(Self)
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("CredentialsPayload", schema_for!(CredentialsPayload)),
+        ("UserResponse", schema_for!(UserResponse)),
+        ("LogoutResponse", schema_for!(LogoutResponse)),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/register",
+            post({
+                let resources = resources.clone();
+                move |body| post_register(body, resources)
+            }),
+        )
+        .route(
+            "/login",
+            post({
+                let resources = resources.clone();
+                move |body| post_login(body, resources)
+            }),
+        )
+        .route(
+            "/logout",
+            post({
+                let resources = resources.clone();
+                move |headers| post_logout(headers, resources)
+            }),
+        )
+        .route("/me", get(get_me))
+        .nest("/oidc/", oidc::router(resources))
+}
+
+pub async fn user_by_id(
+    connection: &mut SqliteConnection,
+    id: i64,
+) -> Result<UserResponse, sqlx::Error> {
+    let sql = "SELECT id, name, is_admin, created_at FROM users WHERE id = ?";
+    let row = query(sql).bind(id).fetch_one(connection).await?;
+    UserResponse::from_row(&row)
+}
+
+pub(crate) async fn hash_password(
+    password: String,
+) -> Result<String, PasswordError> {
+    task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(PasswordError::Hash)
+    })
+    .await
+    .map_err(PasswordError::Join)?
+}
+
+async fn verify_password(
+    password: String,
+    hash: String,
+) -> Result<bool, PasswordError> {
+    task::spawn_blocking(move || {
+        let hash = PasswordHash::new(&hash).map_err(PasswordError::Hash)?;
+        match Argon2::default().verify_password(password.as_bytes(), &hash) {
+            Ok(()) => Ok(true),
+            Err(password_hash::Error::Password) => Ok(false),
+            Err(error) => Err(PasswordError::Hash(error)),
+        }
+    })
+    .await
+    .map_err(PasswordError::Join)?
+}
+
+async fn authenticate(
+    connection: &mut SqliteConnection,
+    name: &str,
+    password: String,
+) -> Result<UserResponse, AuthError> {
+    let sql = "SELECT id, name, password_hash, is_admin, created_at \
+               FROM users WHERE name = ?";
+    let row = query(sql).bind(name).fetch_optional(connection).await?;
+    let hash = match &row {
+        Some(row) => row.try_get("password_hash")?,
+        None => String::new(),
+    };
+    // Accounts provisioned through single sign-on have no password.
+    let verified = if hash.is_empty() {
+        verify_password(password, DUMMY_PASSWORD_HASH.to_owned()).await?;
+        false
+    } else {
+        verify_password(password, hash).await?
+    };
+    match row {
+        Some(row) if verified => Ok(UserResponse::from_row(&row)?),
+        _ => Err(AuthError::InvalidCredentials),
+    }
+}
+
+async fn create_session(
+    connection: &mut SqliteConnection,
+    user_id: i64,
+    ttl: Duration,
+) -> Result<String, sqlx::Error> {
+    query("DELETE FROM sessions WHERE expires_at <= unixepoch()")
+        .execute(&mut *connection)
+        .await?;
+    let token = generate_token();
+    query(
+        "INSERT INTO sessions (user, token_hash, expires_at) \
+         VALUES (?, ?, unixepoch() + ?)",
+    )
+    .bind(user_id)
+    .bind(hash_token(&token))
+    .bind(ttl.as_secs() as i64)
+    .execute(connection)
+    .await?;
+    Ok(token)
+}
+
+async fn user_for_session(
+    connection: &mut SqliteConnection,
+    token: &str,
+) -> Result<Option<UserResponse>, sqlx::Error> {
+    let sql = "SELECT user FROM sessions \
+               WHERE token_hash = ? AND expires_at > unixepoch()";
+    let row = query(sql)
+        .bind(hash_token(token))
+        .fetch_optional(&mut *connection)
+        .await?;
+    match row {
+        Some(row) => {
+            let user_id = row.try_get("user")?;
+            Ok(Some(user_by_id(connection, user_id).await?))
+        },
+        None => Ok(None),
+    }
+}
+
+fn session_cookie(token: String, sessions: &SessionConfig) -> Cookie<'static> {
+    Cookie::build((SESSION_COOKIE, token))
+        .path("/")
+        .http_only(true)
+        .secure(sessions.secure_cookies())
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(sessions.ttl().as_secs() as i64))
+        .build()
+}
+
+async fn post_register(
+    Json(credentials): Json<CredentialsPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<UserResponse, RegisterError> {
+    if credentials.name.is_empty() || credentials.password.is_empty() {
+        return ApiResponse::new(Err(RegisterError::EmptyCredentials));
+    }
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let hash = hash_password(credentials.password).await?;
+                let sql = "INSERT INTO users (name, password_hash) \
+                           VALUES (?, ?) \
+                           RETURNING id, name, is_admin, created_at";
+                let row = query(sql)
+                    .bind(&credentials.name)
+                    .bind(&hash)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(UserResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn post_login(
+    Json(credentials): Json<CredentialsPayload>,
+    resources: Arc<Resources>,
+) -> (SignedCookieJar, ApiResponse<UserResponse, AuthError>) {
+    let jar = SignedCookieJar::new(resources.sessions.key().clone());
+    let ttl = resources.sessions.ttl();
+    let result = resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let user = authenticate(
+                    connection,
+                    &credentials.name,
+                    credentials.password,
+                )
+                .await?;
+                let token = create_session(connection, user.id, ttl).await?;
+                Ok((user, token))
+            })
+        })
+        .await;
+    match result {
+        Ok((user, token)) => {
+            let cookie = session_cookie(token, &resources.sessions);
+            (jar.add(cookie), ApiResponse::new(Ok(user)))
+        },
+        Err(error) => (jar, ApiResponse::new(Err(error))),
+    }
+}
+
+async fn post_logout(
+    headers: HeaderMap,
+    resources: Arc<Resources>,
+) -> (SignedCookieJar, ApiResponse<LogoutResponse, AuthError>) {
+    let jar = SignedCookieJar::from_headers(
+        &headers,
+        resources.sessions.key().clone(),
+    );
+    let Some(cookie) = jar.get(SESSION_COOKIE) else {
+        return (jar, ApiResponse::new(Err(AuthError::MissingCredentials)));
+    };
+    let token = cookie.value().to_owned();
+    let result = resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                query("DELETE FROM sessions WHERE token_hash = ?")
+                    .bind(hash_token(&token))
+                    .execute(&mut **connection)
+                    .await?;
+                Ok(LogoutResponse { logged_out: true })
+            })
+        })
+        .await;
+    (
+        jar.remove(Cookie::build(SESSION_COOKIE).path("/")),
+        ApiResponse::new(result),
+    )
+}
+
+async fn get_me(
+    CurrentUser(user): CurrentUser,
+) -> ApiResponse<UserResponse, AuthError> {
+    ApiResponse::new(Ok(user))
+}
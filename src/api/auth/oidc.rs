@@ -0,0 +1,288 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::Query,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
+};
+use axum_extra::extract::cookie::{Cookie, SameSite, SignedCookieJar};
+use serde::Deserialize;
+use sqlx::{query, Row, SqliteConnection};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{
+    create_session,
+    session_cookie,
+    user_by_id,
+    ApiResponse,
+    NoData,
+    Resources,
+    UserResponse,
+};
+
+const STATE_COOKIE: &str = "oidc_state";
+
+const STATE_TTL: Duration = Duration::from_secs(600);
+
+const SCOPES: &str = "openid profile email";
+
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_url: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Discovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TokenGrant {
+    access_token: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct UserInfo {
+    sub: String,
+    #[serde(default)]
+    preferred_username: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct CallbackQuery {
+    code: String,
+    state: String,
+}
+
+#[derive(Debug, Error)]
+pub enum OidcError {
+    #[error("Single sign-on is not configured")]
+    NotConfigured,
+    #[error("Login state is missing or does not match")]
+    InvalidState,
+    #[error("Failed to discover identity provider configuration")]
+    Discovery(#[source] reqwest::Error),
+    #[error("Failed to exchange authorization code")]
+    TokenExchange(#[source] reqwest::Error),
+    #[error("Failed to fetch user information")]
+    UserInfo(#[source] reqwest::Error),
+    #[error("User with the given name already exists")]
+    NameTaken,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for OidcError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if error.is_unique_violation() {
+                return Self::NameTaken;
+            }
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for OidcError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotConfigured => StatusCode::NOT_FOUND,
+            Self::InvalidState => StatusCode::BAD_REQUEST,
+            Self::Discovery(_) => StatusCode::BAD_GATEWAY,
+            Self::TokenExchange(_) => StatusCode::BAD_GATEWAY,
+            Self::UserInfo(_) => StatusCode::BAD_GATEWAY,
+            Self::NameTaken => StatusCode::FORBIDDEN,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl IntoResponse for OidcError {
+    fn into_response(self) -> Response {
+        ApiResponse::<NoData, _>::new(Err(self)).into_response()
+    }
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/login",
+            get({
+                let resources = resources.clone();
+                move || get_login(resources)
+            }),
+        )
+        .route(
+            "/callback",
+            get({
+                let resources = resources.clone();
+                move |headers, query| get_callback(headers, query, resources)
+            }),
+        )
+}
+
+async fn discover(
+    client: &reqwest::Client,
+    config: &OidcConfig,
+) -> Result<Discovery, OidcError> {
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        config.issuer_url.trim_end_matches('/')
+    );
+    client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(OidcError::Discovery)?
+        .json()
+        .await
+        .map_err(OidcError::Discovery)
+}
+
+async fn fetch_user_info(
+    client: &reqwest::Client,
+    config: &OidcConfig,
+    discovery: &Discovery,
+    code: &str,
+) -> Result<UserInfo, OidcError> {
+    let grant: TokenGrant = client
+        .post(&discovery.token_endpoint)
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &config.redirect_url),
+            ("client_id", &config.client_id),
+            ("client_secret", &config.client_secret),
+        ])
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(OidcError::TokenExchange)?
+        .json()
+        .await
+        .map_err(OidcError::TokenExchange)?;
+    client
+        .get(&discovery.userinfo_endpoint)
+        .bearer_auth(grant.access_token)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(OidcError::UserInfo)?
+        .json()
+        .await
+        .map_err(OidcError::UserInfo)
+}
+
+async fn provision(
+    connection: &mut SqliteConnection,
+    issuer: &str,
+    info: &UserInfo,
+) -> Result<UserResponse, OidcError> {
+    let row = query(
+        "SELECT user FROM user_identities WHERE issuer = ? AND subject = ?",
+    )
+    .bind(issuer)
+    .bind(&info.sub)
+    .fetch_optional(&mut *connection)
+    .await?;
+    if let Some(row) = row {
+        return Ok(user_by_id(connection, row.try_get("user")?).await?);
+    }
+    let name = info
+        .preferred_username
+        .as_ref()
+        .or(info.email.as_ref())
+        .unwrap_or(&info.sub);
+    let sql = "INSERT INTO users (name, password_hash) \
+               VALUES (?, '') RETURNING id";
+    let row = query(sql).bind(name).fetch_one(&mut *connection).await?;
+    let user_id: i64 = row.try_get("id")?;
+    query(
+        "INSERT INTO user_identities (issuer, subject, user) \
+         VALUES (?, ?, ?)",
+    )
+    .bind(issuer)
+    .bind(&info.sub)
+    .bind(user_id)
+    .execute(&mut *connection)
+    .await?;
+    Ok(user_by_id(connection, user_id).await?)
+}
+
+async fn get_login(
+    resources: Arc<Resources>,
+) -> Result<(SignedCookieJar, Redirect), OidcError> {
+    let config = resources.oidc.as_ref().ok_or(OidcError::NotConfigured)?;
+    let client = reqwest::Client::new();
+    let discovery = discover(&client, config).await?;
+    let state = super::generate_token();
+    let request = client
+        .get(&discovery.authorization_endpoint)
+        .query(&[
+            ("response_type", "code"),
+            ("client_id", &config.client_id),
+            ("redirect_uri", &config.redirect_url),
+            ("scope", SCOPES),
+            ("state", &state),
+        ])
+        .build()
+        .map_err(OidcError::Discovery)?;
+    let cookie = Cookie::build((STATE_COOKIE, state))
+        .path("/")
+        .http_only(true)
+        .secure(resources.sessions.secure_cookies())
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(STATE_TTL.as_secs() as i64))
+        .build();
+    let jar = SignedCookieJar::new(resources.sessions.key().clone());
+    Ok((jar.add(cookie), Redirect::to(request.url().as_str())))
+}
+
+async fn get_callback(
+    headers: HeaderMap,
+    Query(callback): Query<CallbackQuery>,
+    resources: Arc<Resources>,
+) -> Result<(SignedCookieJar, Redirect), OidcError> {
+    let config = resources.oidc.clone().ok_or(OidcError::NotConfigured)?;
+    let jar = SignedCookieJar::from_headers(
+        &headers,
+        resources.sessions.key().clone(),
+    );
+    let expected_state =
+        jar.get(STATE_COOKIE).ok_or(OidcError::InvalidState)?;
+    if expected_state.value() != callback.state {
+        return Err(OidcError::InvalidState);
+    }
+    let client = reqwest::Client::new();
+    let discovery = discover(&client, &config).await?;
+    let info =
+        fetch_user_info(&client, &config, &discovery, &callback.code).await?;
+    let ttl = resources.sessions.ttl();
+    let token = resources
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                let user =
+                    provision(transaction, &config.issuer_url, &info).await?;
+                Ok::<_, OidcError>(
+                    create_session(transaction, user.id, ttl).await?,
+                )
+            })
+        })
+        .await?;
+    let jar = jar
+        .remove(Cookie::build(STATE_COOKIE).path("/"))
+        .add(session_cookie(token, &resources.sessions));
+    Ok((jar, Redirect::to("/")))
+}
@@ -0,0 +1,233 @@
+use std::{collections::BTreeMap, net::SocketAddr};
+
+use axum::{
+    body::{self, Body},
+    extract::{ConnectInfo, Request},
+    http::{
+        header::CONTENT_TYPE,
+        HeaderMap,
+        HeaderName,
+        HeaderValue,
+        Method,
+        StatusCode,
+    },
+    Extension,
+    Json,
+    Router,
+};
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tower::ServiceExt;
+
+use crate::status::ResponseStatusCode;
+
+use super::{
+    auth::{AuthError, CurrentUser},
+    network::AdminListener,
+    response::ApiResponse,
+};
+
+const MAX_SUB_REQUESTS: usize = 50;
+
+/// Headers a sub-request may set for itself. Credentials are left out so
+/// that a batch cannot switch users halfway through.
+const SUB_REQUEST_HEADERS: &[&str] = &[
+    "accept",
+    "accept-language",
+    "if-match",
+    "if-none-match",
+    "x-request-id",
+    "x-response-casing",
+    "x-response-envelope",
+];
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct SubRequest {
+    method: String,
+    path: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    body: Option<Value>,
+}
+
+#[derive(Debug, Error)]
+pub enum BatchError {
+    #[error("Batch must hold between 1 and {MAX_SUB_REQUESTS} sub-requests")]
+    InvalidSize,
+    #[error("Sub-request {0} has an invalid method")]
+    InvalidMethod(usize),
+    #[error("Sub-request {0} must target an API path other than /batch")]
+    InvalidPath(usize),
+    #[error("Sub-request {0} sets header {1:?} to an unsupported value")]
+    InvalidHeader(usize, String),
+    #[error("Failed to dispatch sub-request {0}")]
+    Dispatch(usize),
+    #[error(transparent)]
+    Auth(#[from] AuthError),
+}
+
+impl ResponseStatusCode for BatchError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Dispatch(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Auth(error) => error.status_code(),
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct SubResponse {
+    status: u16,
+    body: Value,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct BatchResponse {
+    list: Vec<SubResponse>,
+}
+
+impl ResponseStatusCode for BatchResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("BatchPayload", schema_for!(Vec<SubRequest>)),
+        ("BatchResponse", schema_for!(BatchResponse)),
+    ]
+}
+
+/// Runs sub-requests one after another through `api`, which is the API
+/// router without the batch route itself. The batch request is
+/// authenticated once and every sub-request runs as that user, or
+/// anonymously when the batch carries no credentials.
+pub async fn post_batch(
+    api: Router,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    admin_listener: Option<Extension<AdminListener>>,
+    user: Result<CurrentUser, AuthError>,
+    Json(sub_requests): Json<Vec<SubRequest>>,
+) -> ApiResponse<BatchResponse, BatchError> {
+    let user = match user {
+        Ok(user) => Some(user),
+        Err(AuthError::MissingCredentials) => None,
+        Err(error) => return ApiResponse::new(Err(error.into())),
+    };
+    if sub_requests.is_empty() || sub_requests.len() > MAX_SUB_REQUESTS {
+        return ApiResponse::new(Err(BatchError::InvalidSize));
+    }
+    // Rejected before anything runs, so that a bad header does not leave
+    // the batch half applied.
+    let own_headers: Result<Vec<_>, _> = sub_requests
+        .iter()
+        .enumerate()
+        .map(|(index, sub_request)| own_headers(index, sub_request))
+        .collect();
+    let own_headers = match own_headers {
+        Ok(own_headers) => own_headers,
+        Err(error) => return ApiResponse::new(Err(error)),
+    };
+    let mut responses = Vec::with_capacity(sub_requests.len());
+    let items = sub_requests.into_iter().zip(own_headers).enumerate();
+    for (index, (sub_request, own_headers)) in items {
+        let dispatched = dispatch(
+            &api,
+            peer,
+            admin_listener,
+            user.as_ref(),
+            index,
+            sub_request,
+            own_headers,
+        );
+        match dispatched.await {
+            Ok(response) => responses.push(response),
+            Err(error) => return ApiResponse::new(Err(error)),
+        }
+    }
+    ApiResponse::new(Ok(BatchResponse { list: responses }))
+}
+
+fn own_headers(
+    index: usize,
+    sub_request: &SubRequest,
+) -> Result<HeaderMap, BatchError> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &sub_request.headers {
+        let name = name.to_ascii_lowercase();
+        let allowed = SUB_REQUEST_HEADERS.contains(&name.as_str());
+        let value = HeaderValue::from_str(value).ok();
+        match (HeaderName::from_bytes(name.as_bytes()), value) {
+            (Ok(name), Some(value)) if allowed => {
+                headers.append(name, value);
+            },
+            _ => return Err(BatchError::InvalidHeader(index, name)),
+        }
+    }
+    Ok(headers)
+}
+
+async fn dispatch(
+    api: &Router,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    admin_listener: Option<Extension<AdminListener>>,
+    user: Option<&CurrentUser>,
+    index: usize,
+    sub_request: SubRequest,
+    own_headers: HeaderMap,
+) -> Result<SubResponse, BatchError> {
+    let method = Method::from_bytes(sub_request.method.as_bytes())
+        .map_err(|_| BatchError::InvalidMethod(index))?;
+    if !sub_request.path.starts_with('/')
+        || sub_request.path.trim_end_matches('/') == "/batch"
+    {
+        return Err(BatchError::InvalidPath(index));
+    }
+    let mut builder = Request::builder().method(method).uri(&sub_request.path);
+    // Network restrictions apply to sub-requests as well.
+    if let Some(peer) = peer {
+        builder = builder.extension(peer);
+    }
+    if let Some(Extension(admin_listener)) = admin_listener {
+        builder = builder.extension(admin_listener);
+    }
+    // Credentials are not sent again: signatures cover the batch request
+    // alone and could not be used twice anyway.
+    if let Some(user) = user {
+        builder = builder.extension(user.clone());
+    }
+    for (name, value) in &own_headers {
+        builder = builder.header(name, value);
+    }
+    let body = match &sub_request.body {
+        Some(value) => {
+            builder = builder.header(CONTENT_TYPE, "application/json");
+            Body::from(value.to_string())
+        },
+        None => Body::empty(),
+    };
+    let request =
+        builder.body(body).map_err(|_| BatchError::InvalidPath(index))?;
+    let response = api
+        .clone()
+        .oneshot(request)
+        .await
+        .map_err(|_| BatchError::Dispatch(index))?;
+    let status = response.status().as_u16();
+    let bytes = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .map_err(|_| BatchError::Dispatch(index))?;
+    let body = if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&bytes).unwrap_or_else(|_| {
+            Value::String(String::from_utf8_lossy(&bytes).into_owned())
+        })
+    };
+    Ok(SubResponse { status, body })
+}
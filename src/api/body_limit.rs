@@ -0,0 +1,115 @@
+use axum::{
+    body::{self, Body},
+    extract::Request,
+    http::{header::CONTENT_TYPE, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::response::{ApiResponse, NoData};
+
+/// Limits on request bodies, checked before any handler parses them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyLimits {
+    pub max_bytes: usize,
+    /// Deepest nesting of JSON arrays and objects accepted.
+    pub max_json_depth: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self { max_bytes: 2 * 1024 * 1024, max_json_depth: 64 }
+    }
+}
+
+#[derive(Debug, Error)]
+enum BodyLimitError {
+    #[error("Request body exceeds {0} bytes")]
+    TooLarge(usize),
+    #[error("JSON nesting exceeds {0} levels")]
+    TooDeep(usize),
+}
+
+impl ResponseStatusCode for BodyLimitError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::TooDeep(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// Buffers the body up to the size limit, so that a body cut short by a
+/// disconnect is reported as too large, which nobody will read anyway.
+pub async fn enforce(
+    limits: BodyLimits,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = body::to_bytes(body, limits.max_bytes).await else {
+        let error = BodyLimitError::TooLarge(limits.max_bytes);
+        return ApiResponse::<NoData, _>::new(Err(error)).into_response();
+    };
+    let is_json = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if is_json && exceeds_depth(&bytes, limits.max_json_depth) {
+        let error = BodyLimitError::TooDeep(limits.max_json_depth);
+        return ApiResponse::<NoData, _>::new(Err(error)).into_response();
+    }
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
+
+/// Scans brackets outside of strings without parsing, so that malformed
+/// JSON is still left for the handler to reject.
+fn exceeds_depth(json: &[u8], max_depth: usize) -> bool {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for &byte in json {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max_depth {
+                    return true;
+                }
+            },
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::exceeds_depth;
+
+    #[test]
+    fn nesting_is_counted() {
+        assert!(!exceeds_depth(br#"{"a": [1, {"b": []}]}"#, 4));
+        assert!(exceeds_depth(br#"{"a": [1, {"b": [[]]}]}"#, 4));
+    }
+
+    #[test]
+    fn brackets_inside_strings_are_ignored() {
+        assert!(!exceeds_depth(br#"{"a": "[[[{{{\"[["}"#, 1));
+    }
+}
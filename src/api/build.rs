@@ -0,0 +1,25 @@
+use std::convert::Infallible;
+
+use axum::http::StatusCode;
+use schemars::{schema_for, Schema};
+
+use crate::{
+    status::ResponseStatusCode,
+    version::{BuildInfo, BUILD_INFO},
+};
+
+use super::response::ApiResponse;
+
+impl ResponseStatusCode for BuildInfo {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![("BuildInfo", schema_for!(BuildInfo))]
+}
+
+pub async fn get_version() -> ApiResponse<BuildInfo, Infallible> {
+    ApiResponse::new(Ok(BUILD_INFO))
+}
@@ -0,0 +1,395 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    routing::{get, post},
+    Json,
+    Router,
+};
+use futures::TryStreamExt;
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, sqlite::SqliteRow, Row};
+use thiserror::Error;
+
+use crate::{
+    domain::{DomainError, Name},
+    status::ResponseStatusCode,
+};
+
+use super::{auth::CurrentUser, response::ApiResponse, Resources};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct NewCannedReplyPayload {
+    name: String,
+    body: String,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct PatchCannedReplyPayload {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+#[derive(Debug, Error)]
+enum CannedReplyError {
+    #[error(transparent)]
+    Invalid(#[from] DomainError),
+    #[error("At least one field must be patched, none were")]
+    NoFieldsPatched,
+    #[error("Saved reply with the given name already exists")]
+    AlreadyExists,
+    #[error("Saved reply not found")]
+    NotFound,
+    #[error("Issue not found")]
+    IssueNotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for CannedReplyError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if error.is_unique_violation() {
+                return Self::AlreadyExists;
+            }
+        }
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for CannedReplyError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
+            Self::NoFieldsPatched => StatusCode::BAD_REQUEST,
+            Self::AlreadyExists => StatusCode::FORBIDDEN,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::IssueNotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct CannedReplyResponse {
+    id: i64,
+    name: String,
+    body: String,
+    created_at: i64,
+}
+
+impl CannedReplyResponse {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            body: row.try_get("body")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+impl ResponseStatusCode for CannedReplyResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct CannedReplyListResponse {
+    list: Vec<CannedReplyResponse>,
+}
+
+impl ResponseStatusCode for CannedReplyListResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct RenderedReplyResponse {
+    body: String,
+}
+
+impl ResponseStatusCode for RenderedReplyResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("NewCannedReplyPayload", schema_for!(NewCannedReplyPayload)),
+        ("PatchCannedReplyPayload", schema_for!(PatchCannedReplyPayload)),
+        ("CannedReplyResponse", schema_for!(CannedReplyResponse)),
+        ("CannedReplyListResponse", schema_for!(CannedReplyListResponse)),
+        ("RenderedReplyResponse", schema_for!(RenderedReplyResponse)),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/new",
+            post({
+                let resources = resources.clone();
+                move |user, body| post_new(user, body, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            get({
+                let resources = resources.clone();
+                move |user, id| get_by_id(user, id, resources)
+            })
+            .patch({
+                let resources = resources.clone();
+                move |user, id, body| patch_by_id(user, id, body, resources)
+            })
+            .delete({
+                let resources = resources.clone();
+                move |user, id| delete_by_id(user, id, resources)
+            }),
+        )
+        .route(
+            "/id/:id/render/:issue_id",
+            get({
+                let resources = resources.clone();
+                move |user, ids| get_rendered(user, ids, resources)
+            }),
+        )
+        .route(
+            "/list/",
+            get({
+                let resources = resources.clone();
+                move |user| get_list(user, resources)
+            }),
+        )
+}
+
+/// Replaces `{{name}}` placeholders with the matching variable, leaving
+/// unknown placeholders as they are.
+fn expand(template: &str, variables: &[(&str, &str)]) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = after[..end].trim();
+        match variables.iter().find(|(variable, _)| *variable == name) {
+            Some((_, value)) => output.push_str(value),
+            None => output.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+    output.push_str(rest);
+    output
+}
+
+async fn post_new(
+    CurrentUser(user): CurrentUser,
+    Json(payload): Json<NewCannedReplyPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<CannedReplyResponse, CannedReplyError> {
+    let name = match Name::parse(&payload.name) {
+        Ok(name) => name,
+        Err(error) => return ApiResponse::new(Err(error.into())),
+    };
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let sql = "INSERT INTO canned_replies (user, name, body) \
+                           VALUES (?, ?, ?) \
+                           RETURNING id, name, body, created_at";
+                let row = query(sql)
+                    .bind(user.id)
+                    .bind(name.as_str())
+                    .bind(&payload.body)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(CannedReplyResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_by_id(
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<CannedReplyResponse, CannedReplyError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let sql = "SELECT id, name, body, created_at \
+                           FROM canned_replies WHERE id = ? AND user = ?";
+                let row = query(sql)
+                    .bind(id)
+                    .bind(user.id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(CannedReplyResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn patch_by_id(
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<PatchCannedReplyPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<CannedReplyResponse, CannedReplyError> {
+    if payload.name.is_none() && payload.body.is_none() {
+        return ApiResponse::new(Err(CannedReplyError::NoFieldsPatched));
+    }
+    let name = match payload.name.as_deref().map(Name::parse).transpose() {
+        Ok(name) => name,
+        Err(error) => return ApiResponse::new(Err(error.into())),
+    };
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let sql = "UPDATE canned_replies \
+                           SET name = COALESCE(?, name), \
+                           body = COALESCE(?, body) \
+                           WHERE id = ? AND user = ? \
+                           RETURNING id, name, body, created_at";
+                let row = query(sql)
+                    .bind(name.as_ref().map(Name::as_str))
+                    .bind(&payload.body)
+                    .bind(id)
+                    .bind(user.id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(CannedReplyResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn delete_by_id(
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<CannedReplyResponse, CannedReplyError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let sql = "DELETE FROM canned_replies \
+                           WHERE id = ? AND user = ? \
+                           RETURNING id, name, body, created_at";
+                let row = query(sql)
+                    .bind(id)
+                    .bind(user.id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(CannedReplyResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+/// Expands `{{issue}}`, `{{title}}`, `{{assignee}}` and `{{user}}` against
+/// the given issue and the current user.
+async fn get_rendered(
+    CurrentUser(user): CurrentUser,
+    Path((id, issue_id)): Path<(i64, i64)>,
+    resources: Arc<Resources>,
+) -> ApiResponse<RenderedReplyResponse, CannedReplyError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let template: String = query(
+                    "SELECT body FROM canned_replies WHERE id = ? AND user = ?",
+                )
+                .bind(id)
+                .bind(user.id)
+                .fetch_one(&mut **connection)
+                .await?
+                .try_get("body")?;
+                let issue = query(
+                    "SELECT issues.title, users.name AS assignee \
+                     FROM issues \
+                     LEFT JOIN users ON users.id = issues.assignee \
+                     WHERE issues.id = ?",
+                )
+                .bind(issue_id)
+                .fetch_optional(&mut **connection)
+                .await?
+                .ok_or(CannedReplyError::IssueNotFound)?;
+                let title: String = issue.try_get("title")?;
+                let assignee: Option<String> = issue.try_get("assignee")?;
+                let issue_id = issue_id.to_string();
+                let body = expand(
+                    &template,
+                    &[
+                        ("issue", &issue_id),
+                        ("title", &title),
+                        ("assignee", assignee.as_deref().unwrap_or("")),
+                        ("user", &user.name),
+                    ],
+                );
+                Ok(RenderedReplyResponse { body })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_list(
+    CurrentUser(user): CurrentUser,
+    resources: Arc<Resources>,
+) -> ApiResponse<CannedReplyListResponse, CannedReplyError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let mut list = Vec::new();
+                let sql = "SELECT id, name, body, created_at \
+                           FROM canned_replies WHERE user = ? ORDER BY name";
+                let mut stream =
+                    query(sql).bind(user.id).fetch(&mut **connection);
+                while let Some(row) = stream.try_next().await? {
+                    list.push(CannedReplyResponse::from_row(&row)?);
+                }
+                Ok(CannedReplyListResponse { list })
+            })
+        })
+        .await
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand;
+
+    #[test]
+    fn known_variables_are_expanded() {
+        let body = expand(
+            "Thanks {{ user }}, see #{{issue}}.",
+            &[("issue", "7"), ("user", "ann")],
+        );
+        assert_eq!(body, "Thanks ann, see #7.");
+    }
+
+    #[test]
+    fn unknown_and_unclosed_placeholders_are_kept() {
+        let body = expand("{{reporter}} and {{issue", &[("issue", "7")]);
+        assert_eq!(body, "{{reporter}} and {{issue");
+    }
+}
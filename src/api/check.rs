@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    routing::{get, post},
+    Json,
+    Router,
+};
+use futures::TryStreamExt;
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, sqlite::SqliteRow, Row, SqliteConnection};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{is_foreign_key_violation, response::ApiResponse, Resources};
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum CheckState {
+    Pending,
+    Success,
+    Failure,
+    Error,
+}
+
+impl CheckState {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Success => "success",
+            Self::Failure => "failure",
+            Self::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct NewCheckPayload {
+    name: String,
+    state: CheckState,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    summary: Option<String>,
+}
+
+#[derive(Debug, Error)]
+enum NewCheckError {
+    #[error("Issue not found")]
+    IssueNotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for NewCheckError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if is_foreign_key_violation(&**error) {
+                return Self::IssueNotFound;
+            }
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for NewCheckError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::IssueNotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum GetChecksError {
+    #[error("Issue not found")]
+    IssueNotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for GetChecksError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::IssueNotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for GetChecksError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::IssueNotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct CheckResponse {
+    id: i64,
+    name: String,
+    state: String,
+    url: Option<String>,
+    summary: Option<String>,
+    created_at: i64,
+}
+
+impl CheckResponse {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            state: row.try_get("state")?,
+            url: row.try_get("url")?,
+            summary: row.try_get("summary")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+impl ResponseStatusCode for CheckResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct CheckListResponse {
+    list: Vec<CheckResponse>,
+}
+
+impl ResponseStatusCode for CheckListResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("NewCheckPayload", schema_for!(NewCheckPayload)),
+        ("CheckResponse", schema_for!(CheckResponse)),
+        ("CheckListResponse", schema_for!(CheckListResponse)),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/id/:id/checks",
+            post({
+                let resources = resources.clone();
+                move |id, body| post_new(id, body, resources)
+            }),
+        )
+        .route(
+            "/id/:id/checks",
+            get({
+                let resources = resources.clone();
+                move |id| get_latest(id, resources)
+            }),
+        )
+}
+
+pub async fn latest_for_issue(
+    connection: &mut SqliteConnection,
+    issue_id: i64,
+) -> Result<Vec<CheckResponse>, sqlx::Error> {
+    let mut checks = Vec::new();
+    let sql = "SELECT id, name, state, url, summary, created_at \
+               FROM issue_checks \
+               WHERE id IN ( \
+                   SELECT MAX(id) FROM issue_checks \
+                   WHERE issue = ? GROUP BY name \
+               ) \
+               ORDER BY name";
+    let mut stream = query(sql).bind(issue_id).fetch(connection);
+    while let Some(row) = stream.try_next().await? {
+        checks.push(CheckResponse::from_row(&row)?);
+    }
+    Ok(checks)
+}
+
+async fn post_new(
+    Path(issue_id): Path<i64>,
+    Json(new_check): Json<NewCheckPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<CheckResponse, NewCheckError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let sql = "INSERT INTO issue_checks \
+                           (issue, name, state, url, summary) \
+                           VALUES (?, ?, ?, ?, ?) \
+                           RETURNING id, name, state, url, summary, \
+                           created_at";
+                let row = query(sql)
+                    .bind(issue_id)
+                    .bind(&new_check.name)
+                    .bind(new_check.state.as_str())
+                    .bind(&new_check.url)
+                    .bind(&new_check.summary)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(CheckResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_latest(
+    Path(issue_id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<CheckListResponse, GetChecksError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                query("SELECT id FROM issues WHERE id = ?")
+                    .bind(issue_id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                let list = latest_for_issue(connection, issue_id).await?;
+                Ok(CheckListResponse { list })
+            })
+        })
+        .await
+        .into()
+}
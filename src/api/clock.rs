@@ -0,0 +1,60 @@
+use std::{
+    fmt::Debug,
+    time::{Instant, SystemTime},
+};
+
+#[cfg(test)]
+use std::{sync::Mutex, time::Duration};
+
+/// Source of the current time for in-memory expiry, so that it can be
+/// driven by hand in tests.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time, for comparing with dates sent by clients.
+    fn system_time(&self) -> SystemTime;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock that only moves when told to.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<(Instant, SystemTime)>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new() -> Self {
+        Self { now: Mutex::new((Instant::now(), SystemTime::now())) }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        now.0 += duration;
+        now.1 += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.now.lock().unwrap().0
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.now.lock().unwrap().1
+    }
+}
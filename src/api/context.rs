@@ -0,0 +1,119 @@
+use std::sync::{Arc, OnceLock};
+
+use axum::{
+    extract::Request,
+    http::{header::ACCEPT_LANGUAGE, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{field, Instrument, Span};
+
+use super::{
+    auth::{CurrentUser, UserResponse},
+    i18n,
+};
+
+tokio::task_local! {
+    static CURRENT: RequestContext;
+}
+
+/// Who is making the current request and in which language.
+///
+/// Set up once per request and reachable through `RequestContext::current`
+/// from handlers, extractors and the callbacks run by the `Resources`
+/// database helpers, none of which need to pass it along.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    user: Arc<OnceLock<UserResponse>>,
+    locale: Option<String>,
+    span: Span,
+}
+
+impl RequestContext {
+    /// Context of the request being handled by the current task, if any.
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Self::clone).ok()
+    }
+
+    /// User authenticated so far while handling the request.
+    pub fn user(&self) -> Option<&UserResponse> {
+        self.user.get()
+    }
+
+    pub fn actor(&self) -> Option<i64> {
+        self.user().map(|user| user.id)
+    }
+
+    /// Copies the actor and locale of the current request, if any, onto
+    /// `span`, which must declare both fields.
+    pub fn record_current(span: &Span) {
+        let Some(context) = Self::current() else {
+            return;
+        };
+        if let Some(actor) = context.actor() {
+            span.record("actor", actor);
+        }
+        if let Some(locale) = &context.locale {
+            span.record("locale", locale.as_str());
+        }
+    }
+
+    /// Records the user an extractor authenticated, so that later
+    /// extractions reuse it and later log lines carry it.
+    pub fn authenticated(&self, user: &UserResponse) {
+        if self.user.set(user.clone()).is_ok() {
+            self.span.record("actor", user.id);
+        }
+    }
+}
+
+/// Runs the rest of the request with its context installed, inside a span
+/// that records the actor once known. Nothing is authenticated here; users
+/// resolved by the token and signature middlewares are picked up, others
+/// when `CurrentUser` first succeeds.
+pub async fn scope_context(request: Request, next: Next) -> Response {
+    let locale = preferred_locale(request.headers());
+    let span = tracing::info_span!(
+        "context",
+        actor = field::Empty,
+        locale = locale.as_deref(),
+    );
+    let context =
+        RequestContext { user: Arc::default(), locale, span: span.clone() };
+    if let Some(CurrentUser(user)) = request.extensions().get() {
+        context.authenticated(user);
+    }
+    CURRENT.scope(context, next.run(request)).instrument(span).await
+}
+
+/// First language listed in `Accept-Language`, ignoring wildcards and
+/// malformed tags.
+fn preferred_locale(headers: &HeaderMap) -> Option<String> {
+    let header = headers.get(ACCEPT_LANGUAGE)?.to_str().ok()?;
+    let first = header.split(',').next()?;
+    let tag = first.split(';').next()?.trim();
+    i18n::is_lang_tag(tag).then(|| tag.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header::ACCEPT_LANGUAGE, HeaderMap, HeaderValue};
+
+    use super::preferred_locale;
+
+    fn locale(accept_language: &'static str) -> Option<String> {
+        let mut headers = HeaderMap::new();
+        headers
+            .insert(ACCEPT_LANGUAGE, HeaderValue::from_static(accept_language));
+        preferred_locale(&headers)
+    }
+
+    #[test]
+    fn first_listed_language_wins() {
+        assert_eq!(locale("pt-BR, en;q=0.8").as_deref(), Some("pt-BR"));
+        assert_eq!(locale("de;q=0.9,en").as_deref(), Some("de"));
+        assert_eq!(locale("*"), None);
+        assert_eq!(locale(""), None);
+        assert_eq!(preferred_locale(&HeaderMap::new()), None);
+    }
+}
@@ -0,0 +1,47 @@
+use std::fmt::Write;
+
+use thiserror::Error;
+
+pub const DEFAULT_LIMIT: i64 = 50;
+
+pub const MAX_LIMIT: i64 = 500;
+
+#[derive(Debug, Error)]
+#[error("Pagination cursor is malformed")]
+pub struct InvalidCursor;
+
+/// Position of a row in `(created_at, id)` order, handed to clients as an
+/// opaque hex string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: i64,
+    pub id: i64,
+}
+
+impl Cursor {
+    pub fn encode(self) -> String {
+        let plain = format!("{}:{}", self.created_at, self.id);
+        let mut encoded = String::with_capacity(plain.len() * 2);
+        for byte in plain.bytes() {
+            let _ = write!(encoded, "{byte:02x}");
+        }
+        encoded
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self, InvalidCursor> {
+        if !encoded.len().is_multiple_of(2) || !encoded.is_ascii() {
+            return Err(InvalidCursor);
+        }
+        let bytes = (0..encoded.len())
+            .step_by(2)
+            .map(|start| u8::from_str_radix(&encoded[start..start + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| InvalidCursor)?;
+        let plain = String::from_utf8(bytes).map_err(|_| InvalidCursor)?;
+        let (created_at, id) = plain.split_once(':').ok_or(InvalidCursor)?;
+        Ok(Self {
+            created_at: created_at.parse().map_err(|_| InvalidCursor)?,
+            id: id.parse().map_err(|_| InvalidCursor)?,
+        })
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    routing::{delete, post},
+    Router,
+};
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::Serialize;
+use sqlx::query;
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{
+    auth::CurrentUser,
+    clock::Clock,
+    response::ApiResponse,
+    Resources,
+};
+
+/// How long an acquired or renewed lock lasts without another renewal.
+const LOCK_TTL: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone)]
+struct EditLock {
+    user_id: i64,
+    name: String,
+    expires_at: Instant,
+}
+
+impl EditLock {
+    fn to_response(&self, now: Instant) -> EditLockResponse {
+        EditLockResponse {
+            user_id: self.user_id,
+            name: self.name.clone(),
+            expires_in: self
+                .expires_at
+                .saturating_duration_since(now)
+                .as_secs(),
+        }
+    }
+}
+
+/// Advisory locks on issue descriptions. They only warn other editors;
+/// writes are still guarded by row versions.
+#[derive(Debug)]
+pub struct EditLocks {
+    clock: Arc<dyn Clock>,
+    locks: Mutex<HashMap<i64, EditLock>>,
+}
+
+impl EditLocks {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self { clock, locks: Mutex::default() }
+    }
+
+    pub fn holder(&self, issue_id: i64) -> Option<EditLockResponse> {
+        let locks =
+            self.locks.lock().unwrap_or_else(|error| error.into_inner());
+        let now = self.clock.now();
+        locks
+            .get(&issue_id)
+            .filter(|lock| lock.expires_at > now)
+            .map(|lock| lock.to_response(now))
+    }
+
+    fn acquire(
+        &self,
+        issue_id: i64,
+        user_id: i64,
+        name: String,
+    ) -> Result<EditLockResponse, EditLockError> {
+        let mut locks =
+            self.locks.lock().unwrap_or_else(|error| error.into_inner());
+        let now = self.clock.now();
+        locks.retain(|_, lock| lock.expires_at > now);
+        if let Some(lock) = locks.get(&issue_id) {
+            if lock.user_id != user_id {
+                return Err(EditLockError::Held(lock.name.clone()));
+            }
+        }
+        let lock = EditLock { user_id, name, expires_at: now + LOCK_TTL };
+        let response = lock.to_response(now);
+        locks.insert(issue_id, lock);
+        Ok(response)
+    }
+
+    fn release(
+        &self,
+        issue_id: i64,
+        user_id: i64,
+    ) -> Result<(), EditLockError> {
+        let mut locks =
+            self.locks.lock().unwrap_or_else(|error| error.into_inner());
+        let now = self.clock.now();
+        locks.retain(|_, lock| lock.expires_at > now);
+        match locks.get(&issue_id) {
+            None => Err(EditLockError::NotLocked),
+            Some(lock) if lock.user_id != user_id => {
+                Err(EditLockError::Held(lock.name.clone()))
+            },
+            Some(_) => {
+                locks.remove(&issue_id);
+                Ok(())
+            },
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum EditLockError {
+    #[error("Issue not found")]
+    NotFound,
+    #[error("Issue description is being edited by {0}")]
+    Held(String),
+    #[error("Issue description is not locked")]
+    NotLocked,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for EditLockError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for EditLockError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Held(_) => StatusCode::CONFLICT,
+            Self::NotLocked => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct EditLockResponse {
+    user_id: i64,
+    name: String,
+    expires_in: u64,
+}
+
+impl ResponseStatusCode for EditLockResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct ReleaseResponse {
+    issue_id: i64,
+}
+
+impl ResponseStatusCode for ReleaseResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("EditLockResponse", schema_for!(EditLockResponse)),
+        ("ReleaseResponse", schema_for!(ReleaseResponse)),
+    ]
+}
+
+pub fn issue_router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/id/:id/lock",
+            post({
+                let resources = resources.clone();
+                move |id, user| post_lock(id, user, resources)
+            }),
+        )
+        .route(
+            "/id/:id/lock",
+            delete({
+                let resources = resources.clone();
+                move |id, user| delete_lock(id, user, resources)
+            }),
+        )
+}
+
+async fn post_lock(
+    Path(issue_id): Path<i64>,
+    CurrentUser(user): CurrentUser,
+    resources: Arc<Resources>,
+) -> ApiResponse<EditLockResponse, EditLockError> {
+    let exists = resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                query("SELECT 1 FROM issues WHERE id = ?")
+                    .bind(issue_id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(())
+            })
+        })
+        .await;
+    let result = exists.and_then(|()| {
+        resources.edit_locks.acquire(issue_id, user.id, user.name)
+    });
+    ApiResponse::new(result)
+}
+
+async fn delete_lock(
+    Path(issue_id): Path<i64>,
+    CurrentUser(user): CurrentUser,
+    resources: Arc<Resources>,
+) -> ApiResponse<ReleaseResponse, EditLockError> {
+    let result = resources.edit_locks.release(issue_id, user.id);
+    ApiResponse::new(result.map(|()| ReleaseResponse { issue_id }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use super::{EditLockError, EditLocks, LOCK_TTL};
+    use crate::api::clock::MockClock;
+
+    fn setup() -> (Arc<MockClock>, EditLocks) {
+        let clock = Arc::new(MockClock::new());
+        (clock.clone(), EditLocks::new(clock))
+    }
+
+    #[test]
+    fn lock_expires_after_ttl() {
+        let (clock, locks) = setup();
+        locks.acquire(1, 10, String::from("ann")).unwrap();
+        clock.advance(LOCK_TTL - Duration::from_secs(1));
+        assert_eq!(locks.holder(1).unwrap().expires_in, 1);
+        clock.advance(Duration::from_secs(1));
+        assert!(locks.holder(1).is_none());
+    }
+
+    #[test]
+    fn other_user_waits_for_expiry() {
+        let (clock, locks) = setup();
+        locks.acquire(1, 10, String::from("ann")).unwrap();
+        let result = locks.acquire(1, 20, String::from("bob"));
+        assert!(
+            matches!(result, Err(EditLockError::Held(name)) if name == "ann")
+        );
+        clock.advance(LOCK_TTL);
+        let lock = locks.acquire(1, 20, String::from("bob")).unwrap();
+        assert_eq!(lock.user_id, 20);
+    }
+
+    #[test]
+    fn renewal_extends_lock() {
+        let (clock, locks) = setup();
+        locks.acquire(1, 10, String::from("ann")).unwrap();
+        clock.advance(LOCK_TTL / 2);
+        locks.acquire(1, 10, String::from("ann")).unwrap();
+        clock.advance(LOCK_TTL / 2);
+        assert_eq!(locks.holder(1).unwrap().expires_in, LOCK_TTL.as_secs() / 2);
+    }
+
+    #[test]
+    fn expired_lock_cannot_be_released() {
+        let (clock, locks) = setup();
+        locks.acquire(1, 10, String::from("ann")).unwrap();
+        clock.advance(LOCK_TTL);
+        let result = locks.release(1, 10);
+        assert!(matches!(result, Err(EditLockError::NotLocked)));
+    }
+}
@@ -0,0 +1,87 @@
+use axum::{
+    body::{self, Body, HttpBody},
+    extract::Request,
+    http::{
+        header::{CONTENT_DISPOSITION, ETAG, IF_NONE_MATCH, VARY},
+        HeaderMap,
+        HeaderValue,
+        Method,
+        StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+/// Largest body hashed for a tag. Bigger or streamed bodies are sent
+/// untagged rather than held in memory.
+const MAX_TAGGED_BODY: u64 = 1 << 20;
+
+/// Request headers the encoded body depends on.
+const VARY_HEADERS: &str = "Accept, X-Response-Casing, X-Response-Envelope";
+
+/// Tags successful GET responses with a weak ETag derived from the encoded
+/// body, unless the handler set one, and answers 304 when the client already
+/// holds that representation.
+pub async fn conditional_get(request: Request, next: Next) -> Response {
+    if request.method() != Method::GET {
+        return next.run(request).await;
+    }
+    let if_none_match = request.headers().get(IF_NONE_MATCH).cloned();
+    let mut response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    response.headers_mut().append(VARY, HeaderValue::from_static(VARY_HEADERS));
+    // Versioned rows already carry the tag that `If-Match` expects.
+    if let Some(tag) = response.headers().get(ETAG) {
+        let tag = tag.to_str().unwrap_or_default().to_owned();
+        if if_none_match.is_some_and(|value| matches(&value, &tag)) {
+            return not_modified(response.headers()[ETAG].clone());
+        }
+        return response;
+    }
+    let size = response.body().size_hint().exact();
+    let download = response.headers().contains_key(CONTENT_DISPOSITION);
+    if download || size.is_none_or(|size| size > MAX_TAGGED_BODY) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = body::to_bytes(body, MAX_TAGGED_BODY as usize).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let tag = weak_tag(&bytes);
+    let Ok(header) = HeaderValue::from_str(&tag) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    parts.headers.insert(ETAG, header.clone());
+    if if_none_match.is_some_and(|value| matches(&value, &tag)) {
+        return not_modified(header);
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+fn not_modified(tag: HeaderValue) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(ETAG, tag);
+    headers.insert(VARY, HeaderValue::from_static(VARY_HEADERS));
+    (StatusCode::NOT_MODIFIED, headers).into_response()
+}
+
+fn weak_tag(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    let hex: String =
+        digest[..16].iter().map(|byte| format!("{byte:02x}")).collect();
+    format!("W/\"{hex}\"")
+}
+
+/// Uses weak comparison, as required for `If-None-Match`.
+fn matches(if_none_match: &HeaderValue, tag: &str) -> bool {
+    let Ok(if_none_match) = if_none_match.to_str() else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_owned();
+    if_none_match.split(',').any(|candidate| {
+        candidate.trim() == "*" || opaque(candidate) == opaque(tag)
+    })
+}
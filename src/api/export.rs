@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::Query,
+    http::{header::CONTENT_DISPOSITION, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::TryStreamExt;
+use schemars::{schema_for, Schema};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{query, query_scalar, SqliteConnection};
+use thiserror::Error;
+
+use crate::{
+    archive::{self, Archive, ArchiveError, ArchiveReport},
+    dump::KeptTable,
+    status::ResponseStatusCode,
+    version::BUILD_INFO,
+};
+
+use super::{
+    admin,
+    auth::AdminUser,
+    response::{ApiResponse, NoData},
+    Resources,
+};
+
+/// Everything needed to rebuild the tracker elsewhere. Credentials,
+/// sessions and per-user settings stay behind; users are only exported so
+/// that assignees can be matched by name.
+const EXPORTED_TABLES: &[KeptTable] = &[
+    KeptTable { table: "issue_statuses", columns: "*" },
+    KeptTable { table: "issue_types", columns: "*" },
+    KeptTable { table: "issue_type_statuses", columns: "*" },
+    KeptTable { table: "labels", columns: "*" },
+    KeptTable { table: "milestones", columns: "*" },
+    KeptTable { table: "versions", columns: "*" },
+    KeptTable { table: "users", columns: "id, name" },
+    KeptTable { table: "issues", columns: "*" },
+    KeptTable { table: "issue_labels", columns: "*" },
+    KeptTable { table: "issue_blockings", columns: "*" },
+    KeptTable { table: "issue_checks", columns: "*" },
+];
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExportFormat {
+    #[default]
+    Json,
+    Sqlite,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ExportDocument {
+    format: &'static str,
+    format_version: u32,
+    app_version: &'static str,
+    schema_version: Option<i64>,
+    exported_at: i64,
+    tables: Map<String, Value>,
+}
+
+#[derive(Debug, Error)]
+enum ExportError {
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+impl ResponseStatusCode for ExportError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl ResponseStatusCode for ArchiveError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnsupportedFormat(_)
+            | Self::UnsupportedVersion(_)
+            | Self::Decode(_)
+            | Self::MissingReference { .. } => StatusCode::BAD_REQUEST,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl ResponseStatusCode for ArchiveReport {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("Archive", schema_for!(Archive)),
+        ("ArchiveReport", schema_for!(ArchiveReport)),
+    ]
+}
+
+/// Downloads every issue with the statuses, labels, milestones, versions and
+/// types it refers to, either as a JSON document or as a SQLite file holding
+/// the same tables.
+pub async fn get_export(
+    _admin: AdminUser,
+    Query(export): Query<ExportQuery>,
+    resources: Arc<Resources>,
+) -> Response {
+    match export.format {
+        ExportFormat::Sqlite => {
+            admin::download_snapshot(
+                &resources,
+                "export.bin",
+                Some(EXPORTED_TABLES),
+            )
+            .await
+        },
+        ExportFormat::Json => {
+            let result = resources
+                .with_transaction(|transaction| {
+                    Box::pin(async move { export_json(transaction).await })
+                })
+                .await;
+            match result {
+                Ok(document) => (
+                    [(
+                        CONTENT_DISPOSITION,
+                        "attachment; filename=\"export.json\"",
+                    )],
+                    Json(document),
+                )
+                    .into_response(),
+                Err(error) => {
+                    ApiResponse::<NoData, ExportError>::new(Err(error))
+                        .into_response()
+                },
+            }
+        },
+    }
+}
+
+/// Merges an archive produced by `get_export` into this database.
+pub async fn post_import(
+    _admin: AdminUser,
+    Json(archive): Json<Archive>,
+    resources: Arc<Resources>,
+) -> ApiResponse<ArchiveReport, ArchiveError> {
+    let result = resources
+        .with_transaction(|transaction| {
+            Box::pin(
+                async move { archive::import(transaction, &archive).await },
+            )
+        })
+        .await;
+    if let Ok(report) = &result {
+        tracing::info!(issues = report.issues, "Archive imported");
+    }
+    ApiResponse::new(result)
+}
+
+async fn export_json(
+    conn: &mut SqliteConnection,
+) -> Result<ExportDocument, ExportError> {
+    let schema_version =
+        query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+            .fetch_one(&mut *conn)
+            .await?;
+    let exported_at =
+        query_scalar("SELECT unixepoch()").fetch_one(&mut *conn).await?;
+    let mut tables = Map::new();
+    for exported in EXPORTED_TABLES {
+        let rows = export_table(conn, exported).await?;
+        tables.insert(exported.table.to_owned(), Value::Array(rows));
+    }
+    Ok(ExportDocument {
+        format: archive::FORMAT,
+        format_version: archive::FORMAT_VERSION,
+        app_version: BUILD_INFO.version,
+        schema_version,
+        exported_at,
+        tables,
+    })
+}
+
+async fn export_table(
+    conn: &mut SqliteConnection,
+    exported: &KeptTable,
+) -> Result<Vec<Value>, sqlx::Error> {
+    let select = format!(
+        "SELECT {columns} FROM {table} ORDER BY rowid",
+        columns = exported.columns,
+        table = exported.table,
+    );
+    let mut rows = Vec::new();
+    let mut stream = query(&select).fetch(conn);
+    while let Some(row) = stream.try_next().await? {
+        rows.push(Value::Object(archive::row_object(&row)?));
+    }
+    Ok(rows)
+}
@@ -0,0 +1,207 @@
+use std::sync::Arc;
+
+use axum::{http::StatusCode, routing::get, Router};
+use futures::TryStreamExt;
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::{query, Row, SqliteConnection};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{auth::CurrentUser, response::ApiResponse, Resources};
+
+const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+#[derive(Debug, Error)]
+enum IssueFormError {
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+impl ResponseStatusCode for IssueFormError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct FormOption {
+    id: i64,
+    name: String,
+}
+
+/// An issue type with the statuses its issues may be in and the template
+/// that fills in an empty description.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct TypeOption {
+    id: i64,
+    name: String,
+    statuses: Vec<FormOption>,
+    template: String,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct IssueFormResponse {
+    /// JSON Schema of the `POST /issue/new` payload.
+    schema: Value,
+    /// Labels are attached after creation, so they are listed apart from
+    /// the payload schema.
+    labels: Vec<FormOption>,
+    /// Choosing a type narrows `status_id` down to the statuses of the type.
+    types: Vec<TypeOption>,
+}
+
+impl ResponseStatusCode for IssueFormResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![("IssueFormResponse", schema_for!(IssueFormResponse))]
+}
+
+pub fn issue_router(resources: Arc<Resources>) -> Router {
+    Router::new().route(
+        "/form",
+        get({
+            let resources = resources.clone();
+            move |user| get_form(user, resources)
+        }),
+    )
+}
+
+async fn options(
+    connection: &mut SqliteConnection,
+    sql: &str,
+) -> Result<Vec<FormOption>, sqlx::Error> {
+    let mut options = Vec::new();
+    let mut stream = query(sql).fetch(connection);
+    while let Some(row) = stream.try_next().await? {
+        let id = row.try_get("id")?;
+        let name = row.try_get("name")?;
+        options.push(FormOption { id, name });
+    }
+    Ok(options)
+}
+
+async fn type_options(
+    connection: &mut SqliteConnection,
+) -> Result<Vec<TypeOption>, sqlx::Error> {
+    let rows = query("SELECT id, name, template FROM issue_types ORDER BY id")
+        .fetch_all(&mut *connection)
+        .await?;
+    let mut types = Vec::with_capacity(rows.len());
+    for row in rows {
+        let id = row.try_get("id")?;
+        // Types without statuses of their own allow every status.
+        let sql = "SELECT id, name FROM issue_statuses \
+                   WHERE NOT EXISTS ( \
+                       SELECT 1 FROM issue_type_statuses WHERE type = ?1 \
+                   ) OR id IN ( \
+                       SELECT status FROM issue_type_statuses WHERE type = ?1 \
+                   ) \
+                   ORDER BY id";
+        let mut statuses = Vec::new();
+        let mut stream = query(sql).bind(id).fetch(&mut *connection);
+        while let Some(row) = stream.try_next().await? {
+            let id = row.try_get("id")?;
+            let name = row.try_get("name")?;
+            statuses.push(FormOption { id, name });
+        }
+        types.push(TypeOption {
+            id,
+            name: row.try_get("name")?,
+            statuses,
+            template: row.try_get("template")?,
+        });
+    }
+    Ok(types)
+}
+
+fn reference(options: &[FormOption], required: bool) -> Value {
+    let mut choices: Vec<_> = options
+        .iter()
+        .map(|option| json!({ "const": option.id, "title": option.name }))
+        .collect();
+    if required {
+        json!({ "type": "integer", "oneOf": choices })
+    } else {
+        choices.push(json!({ "const": null, "title": "none" }));
+        json!({ "type": ["integer", "null"], "oneOf": choices })
+    }
+}
+
+/// Assignees are only offered to signed-in users, so that the form does not
+/// tell anyone which accounts exist.
+async fn get_form(
+    user: Option<CurrentUser>,
+    resources: Arc<Resources>,
+) -> ApiResponse<IssueFormResponse, IssueFormError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let statuses = options(
+                    connection,
+                    "SELECT id, name FROM issue_statuses ORDER BY id",
+                )
+                .await?;
+                let versions = options(
+                    connection,
+                    "SELECT id, name FROM versions ORDER BY id",
+                )
+                .await?;
+                let assignee = match user {
+                    Some(_) => {
+                        let users = options(
+                            connection,
+                            "SELECT id, name FROM users ORDER BY id",
+                        )
+                        .await?;
+                        reference(&users, false)
+                    },
+                    None => json!({ "type": ["integer", "null"] }),
+                };
+                let labels = options(
+                    connection,
+                    "SELECT id, name FROM labels ORDER BY name",
+                )
+                .await?;
+                let types = type_options(connection).await?;
+                let type_choices: Vec<_> = types
+                    .iter()
+                    .map(|option| FormOption {
+                        id: option.id,
+                        name: option.name.clone(),
+                    })
+                    .collect();
+                let schema = json!({
+                    "$schema": SCHEMA_DIALECT,
+                    "title": "New issue",
+                    "type": "object",
+                    "required": ["title", "status_id"],
+                    "additionalProperties": false,
+                    "properties": {
+                        "title": { "type": "string" },
+                        "description": { "type": "string", "default": "" },
+                        "status_id": reference(&statuses, true),
+                        "affects_version_id": reference(&versions, false),
+                        "fixed_in_version_id": reference(&versions, false),
+                        "assignee_id": assignee,
+                        "type_id": reference(&type_choices, false),
+                    },
+                });
+                Ok(IssueFormResponse { schema, labels, types })
+            })
+        })
+        .await
+        .into()
+}
@@ -0,0 +1,197 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    routing::{delete, get, put},
+    Json,
+    Router,
+};
+use futures::TryStreamExt;
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::Serialize;
+use sqlx::{query, Row, SqliteConnection};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{auth::AdminUser, response::ApiResponse, Resources};
+
+const DEFAULT_CATALOG: &str = include_str!("../../i18n/en.json");
+
+const MAX_LANG_LEN: usize = 35;
+
+#[derive(Debug, Error)]
+enum CatalogError {
+    #[error("Catalog path must be a language tag followed by .json")]
+    InvalidLang,
+    #[error("Failed to decode default catalog")]
+    DefaultCatalog(#[source] serde_json::Error),
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+impl ResponseStatusCode for CatalogError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidLang => StatusCode::NOT_FOUND,
+            Self::DefaultCatalog(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct CatalogResponse {
+    lang: String,
+    messages: BTreeMap<String, String>,
+}
+
+impl ResponseStatusCode for CatalogResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![("CatalogResponse", schema_for!(CatalogResponse))]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/:file",
+            get({
+                let resources = resources.clone();
+                move |file| get_catalog(file, resources)
+            }),
+        )
+        .route(
+            "/:file",
+            put({
+                let resources = resources.clone();
+                move |admin, file, body| {
+                    put_overrides(admin, file, body, resources)
+                }
+            }),
+        )
+        .route(
+            "/:file",
+            delete({
+                let resources = resources.clone();
+                move |admin, file| delete_overrides(admin, file, resources)
+            }),
+        )
+}
+
+fn parse_lang(file: &str) -> Result<String, CatalogError> {
+    let lang = file.strip_suffix(".json").ok_or(CatalogError::InvalidLang)?;
+    if !is_lang_tag(lang) {
+        return Err(CatalogError::InvalidLang);
+    }
+    Ok(lang.to_owned())
+}
+
+pub fn is_lang_tag(lang: &str) -> bool {
+    !lang.is_empty()
+        && lang.len() <= MAX_LANG_LEN
+        && lang.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-')
+}
+
+async fn catalog(
+    connection: &mut SqliteConnection,
+    lang: String,
+) -> Result<CatalogResponse, CatalogError> {
+    let mut messages: BTreeMap<String, String> =
+        serde_json::from_str(DEFAULT_CATALOG)
+            .map_err(CatalogError::DefaultCatalog)?;
+    let mut stream =
+        query("SELECT key, value FROM i18n_overrides WHERE lang = ?")
+            .bind(&lang)
+            .fetch(connection);
+    while let Some(row) = stream.try_next().await? {
+        messages.insert(row.try_get("key")?, row.try_get("value")?);
+    }
+    drop(stream);
+    Ok(CatalogResponse { lang, messages })
+}
+
+async fn get_catalog(
+    Path(file): Path<String>,
+    resources: Arc<Resources>,
+) -> ApiResponse<CatalogResponse, CatalogError> {
+    let lang = match parse_lang(&file) {
+        Ok(lang) => lang,
+        Err(error) => return ApiResponse::new(Err(error)),
+    };
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move { catalog(connection, lang).await })
+        })
+        .await
+        .into()
+}
+
+async fn put_overrides(
+    _admin: AdminUser,
+    Path(file): Path<String>,
+    Json(overrides): Json<BTreeMap<String, String>>,
+    resources: Arc<Resources>,
+) -> ApiResponse<CatalogResponse, CatalogError> {
+    let lang = match parse_lang(&file) {
+        Ok(lang) => lang,
+        Err(error) => return ApiResponse::new(Err(error)),
+    };
+    resources
+        .with_retrying_transaction(|transaction| {
+            let lang = lang.clone();
+            let overrides = overrides.clone();
+            Box::pin(async move {
+                query("DELETE FROM i18n_overrides WHERE lang = ?")
+                    .bind(&lang)
+                    .execute(&mut **transaction)
+                    .await?;
+                for (key, value) in &overrides {
+                    query(
+                        "INSERT INTO i18n_overrides (lang, key, value) \
+                         VALUES (?, ?, ?)",
+                    )
+                    .bind(&lang)
+                    .bind(key)
+                    .bind(value)
+                    .execute(&mut **transaction)
+                    .await?;
+                }
+                catalog(transaction, lang).await
+            })
+        })
+        .await
+        .into()
+}
+
+async fn delete_overrides(
+    _admin: AdminUser,
+    Path(file): Path<String>,
+    resources: Arc<Resources>,
+) -> ApiResponse<CatalogResponse, CatalogError> {
+    let lang = match parse_lang(&file) {
+        Ok(lang) => lang,
+        Err(error) => return ApiResponse::new(Err(error)),
+    };
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                query("DELETE FROM i18n_overrides WHERE lang = ?")
+                    .bind(&lang)
+                    .execute(&mut **connection)
+                    .await?;
+                catalog(connection, lang).await
+            })
+        })
+        .await
+        .into()
+}
@@ -0,0 +1,984 @@
+use std::{fmt, sync::Arc};
+
+use axum::{
+    extract::{Path, Query},
+    http::{header::ETAG, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get, patch, post},
+    Json,
+    Router,
+};
+use futures::TryStreamExt;
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use sqlx::{query, sqlite::SqliteRow, Row, SqliteConnection};
+use thiserror::Error;
+
+use crate::{
+    domain::{
+        check_status_change,
+        DomainError,
+        IssuePatch,
+        NewIssue,
+        StatusChange,
+        StatusChangeError,
+        Title,
+    },
+    status::ResponseStatusCode,
+};
+
+use super::{
+    auth::AdminUser,
+    check::{self, CheckResponse},
+    cursor::{self, Cursor, InvalidCursor},
+    edit_lock::{self, EditLockResponse},
+    form,
+    is_foreign_key_violation,
+    issue_type,
+    jsonapi::{self, Document, Include, Included, JsonApi, Resource},
+    label::{self, LabelResponse},
+    precondition::{self, IfMatch},
+    response::ApiResponse,
+    sort::{SortParams, Sortable},
+    triage,
+    Resources,
+};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct NewIssuePayload {
+    title: String,
+    #[serde(default)]
+    description: String,
+    status_id: i64,
+    #[serde(default)]
+    affects_version_id: Option<i64>,
+    #[serde(default)]
+    fixed_in_version_id: Option<i64>,
+    #[serde(default)]
+    assignee_id: Option<i64>,
+    /// Fills in an empty description with the template of the type.
+    #[serde(default)]
+    type_id: Option<i64>,
+}
+
+impl TryFrom<NewIssuePayload> for NewIssue {
+    type Error = DomainError;
+
+    fn try_from(payload: NewIssuePayload) -> Result<Self, Self::Error> {
+        Ok(Self {
+            title: Title::parse(&payload.title)?,
+            description: payload.description,
+            status_id: payload.status_id,
+            affects_version_id: payload.affects_version_id,
+            fixed_in_version_id: payload.fixed_in_version_id,
+            assignee_id: payload.assignee_id,
+            type_id: payload.type_id,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct PatchIssuePayload {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    status_id: Option<i64>,
+    /// `null` clears the version.
+    #[serde(default, deserialize_with = "nullable")]
+    affects_version_id: Option<Option<i64>>,
+    /// `null` clears the version.
+    #[serde(default, deserialize_with = "nullable")]
+    fixed_in_version_id: Option<Option<i64>>,
+    /// `null` unassigns the issue.
+    #[serde(default, deserialize_with = "nullable")]
+    assignee_id: Option<Option<i64>>,
+    /// `null` clears the type.
+    #[serde(default, deserialize_with = "nullable")]
+    type_id: Option<Option<i64>>,
+}
+
+/// Tells a field set to `null` apart from a missing one, which serde would
+/// otherwise both read as `None`.
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+impl TryFrom<PatchIssuePayload> for IssuePatch {
+    type Error = DomainError;
+
+    fn try_from(payload: PatchIssuePayload) -> Result<Self, Self::Error> {
+        Ok(Self {
+            title: payload.title.as_deref().map(Title::parse).transpose()?,
+            description: payload.description,
+            status_id: payload.status_id,
+            affects_version_id: payload.affects_version_id,
+            fixed_in_version_id: payload.fixed_in_version_id,
+            assignee_id: payload.assignee_id,
+            type_id: payload.type_id,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PatchIssueQuery {
+    /// Lets administrators close an issue that open issues still block.
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct IssueListQuery {
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    title_contains: Option<String>,
+    #[serde(default)]
+    status_id: Option<i64>,
+    #[serde(default)]
+    assignee_id: Option<i64>,
+    #[serde(default)]
+    milestone_id: Option<i64>,
+    #[serde(default)]
+    created_after: Option<i64>,
+    #[serde(default)]
+    created_before: Option<i64>,
+    #[serde(default)]
+    after: Option<String>,
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+struct IssueSort;
+
+impl Sortable for IssueSort {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("id", "id"),
+        ("title", "title"),
+        ("status_id", "status"),
+        ("milestone_id", "milestone"),
+        ("assignee_id", "assignee"),
+        ("created_at", "created_at"),
+        ("updated_at", "updated_at"),
+    ];
+}
+
+#[derive(Debug, Error)]
+enum NewIssueError {
+    #[error(transparent)]
+    Invalid(#[from] DomainError),
+    // Worded before issue types existed, and kept for clients of v1.
+    #[error("Referenced status, version or user not found")]
+    ReferenceNotFound,
+    #[error("Status is not allowed for issues of the given type")]
+    StatusNotAllowed,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for NewIssueError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if is_foreign_key_violation(&**error) {
+                return Self::ReferenceNotFound;
+            }
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for NewIssueError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
+            Self::ReferenceNotFound => StatusCode::BAD_REQUEST,
+            Self::StatusNotAllowed => StatusCode::BAD_REQUEST,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum GetIssueError {
+    #[error("Issue not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for GetIssueError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for GetIssueError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum DeleteIssueError {
+    #[error("Issue not found")]
+    NotFound,
+    #[error("Issue was modified since the given version")]
+    Stale,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for DeleteIssueError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for DeleteIssueError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Stale => StatusCode::PRECONDITION_FAILED,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum ListIssuesError {
+    #[error("Invalid pagination cursor")]
+    InvalidCursor(#[source] InvalidCursor),
+    #[error("Cursor pagination cannot be combined with custom sorting")]
+    SortedPagination,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+impl ResponseStatusCode for ListIssuesError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidCursor(_) => StatusCode::BAD_REQUEST,
+            Self::SortedPagination => StatusCode::BAD_REQUEST,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum PatchIssueError {
+    #[error(transparent)]
+    Invalid(#[from] DomainError),
+    #[error("At least one field must be patched, none were")]
+    NoFieldsPatched,
+    #[error("Issue not found")]
+    NotFound,
+    // Worded before issue types existed, and kept for clients of v1.
+    #[error("Referenced status, version or user not found")]
+    ReferenceNotFound,
+    #[error("Status is not allowed for issues of the given type")]
+    StatusNotAllowed,
+    #[error("Issue was modified since the given version")]
+    Stale,
+    #[error("Issue is blocked by open issues {0}, closing it must be forced")]
+    Blocked(OpenBlockers),
+    #[error("Only administrators can force closing a blocked issue")]
+    ForceNotAllowed,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for PatchIssueError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if is_foreign_key_violation(&**error) {
+                return Self::ReferenceNotFound;
+            }
+        }
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl From<StatusChangeError> for PatchIssueError {
+    fn from(error: StatusChangeError) -> Self {
+        match error {
+            StatusChangeError::NotAllowed => Self::StatusNotAllowed,
+            StatusChangeError::Blocked(ids) => Self::Blocked(ids.into()),
+        }
+    }
+}
+
+impl ResponseStatusCode for PatchIssueError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
+            Self::NoFieldsPatched => StatusCode::BAD_REQUEST,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::ReferenceNotFound => StatusCode::BAD_REQUEST,
+            Self::StatusNotAllowed => StatusCode::BAD_REQUEST,
+            Self::Stale => StatusCode::PRECONDITION_FAILED,
+            Self::Blocked(_) => StatusCode::CONFLICT,
+            Self::ForceNotAllowed => StatusCode::FORBIDDEN,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl PatchIssueError {
+    fn detail(&self) -> Option<(&'static str, Value)> {
+        match self {
+            Self::Blocked(blockers) => blockers.detail(),
+            _ => None,
+        }
+    }
+}
+
+/// Ids of open issues that block another one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OpenBlockers(Vec<i64>);
+
+impl OpenBlockers {
+    /// Error detail listing the blockers.
+    pub fn detail(&self) -> Option<(&'static str, Value)> {
+        Some(("blockers", json!(self)))
+    }
+}
+
+impl From<Vec<i64>> for OpenBlockers {
+    fn from(ids: Vec<i64>) -> Self {
+        Self(ids)
+    }
+}
+
+impl fmt::Display for OpenBlockers {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for (i, id) in self.0.iter().enumerate() {
+            if i > 0 {
+                formatter.write_str(", ")?;
+            }
+            write!(formatter, "#{id}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct IssueResponse {
+    id: i64,
+    title: String,
+    description: String,
+    status_id: i64,
+    milestone_id: Option<i64>,
+    affects_version_id: Option<i64>,
+    fixed_in_version_id: Option<i64>,
+    assignee_id: Option<i64>,
+    type_id: Option<i64>,
+    created_at: Option<i64>,
+    updated_at: Option<i64>,
+    version: i64,
+}
+
+impl IssueResponse {
+    pub fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            title: row.try_get("title")?,
+            description: row.try_get("description")?,
+            status_id: row.try_get("status")?,
+            milestone_id: row.try_get("milestone")?,
+            affects_version_id: row.try_get("affects_version")?,
+            fixed_in_version_id: row.try_get("fixed_in_version")?,
+            assignee_id: row.try_get("assignee")?,
+            type_id: row.try_get("type")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+            version: row.try_get("version")?,
+        })
+    }
+}
+
+impl ResponseStatusCode for IssueResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct IssueDetailResponse {
+    #[serde(flatten)]
+    issue: IssueResponse,
+    labels: Vec<LabelResponse>,
+    checks: Vec<CheckResponse>,
+    edit_lock: Option<EditLockResponse>,
+}
+
+impl ResponseStatusCode for IssueDetailResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct IssueListResponse {
+    list: Vec<IssueResponse>,
+    next_cursor: Option<String>,
+}
+
+impl ResponseStatusCode for IssueListResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("NewIssuePayload", schema_for!(NewIssuePayload)),
+        ("PatchIssuePayload", schema_for!(PatchIssuePayload)),
+        ("IssueResponse", schema_for!(IssueResponse)),
+        ("IssueDetailResponse", schema_for!(IssueDetailResponse)),
+        ("IssueListResponse", schema_for!(IssueListResponse)),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/new",
+            post({
+                let resources = resources.clone();
+                move |body| post_new(body, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            get({
+                let resources = resources.clone();
+                move |id, jsonapi| get_by_id(id, jsonapi, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            delete({
+                let resources = resources.clone();
+                move |id, if_match| delete_by_id(id, if_match, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            patch({
+                let resources = resources.clone();
+                move |id, if_match, query, admin, payload| {
+                    patch_by_id(id, if_match, query, admin, payload, resources)
+                }
+            }),
+        )
+        .route(
+            "/list/",
+            get({
+                let resources = resources.clone();
+                move |query, sort, jsonapi| {
+                    get_list(query, sort, jsonapi, resources)
+                }
+            }),
+        )
+        .merge(check::router(resources.clone()))
+        .merge(label::issue_router(resources.clone()))
+        .merge(triage::issue_router(resources.clone()))
+        .merge(edit_lock::issue_router(resources.clone()))
+        .merge(form::issue_router(resources))
+}
+
+async fn post_new(
+    Json(new_issue): Json<NewIssuePayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<IssueResponse, NewIssueError> {
+    let mut new_issue = match NewIssue::try_from(new_issue) {
+        Ok(new_issue) => new_issue,
+        Err(error) => return ApiResponse::new(Err(error.into())),
+    };
+    let result = resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                if let Some(type_id) = new_issue.type_id {
+                    let template = issue_type::template(connection, type_id)
+                        .await?
+                        .ok_or(NewIssueError::ReferenceNotFound)?;
+                    if new_issue.description.is_empty() {
+                        new_issue.description = template;
+                    }
+                }
+                let allowed = issue_type::allows_status(
+                    connection,
+                    new_issue.type_id,
+                    new_issue.status_id,
+                )
+                .await?;
+                if !allowed {
+                    return Err(NewIssueError::StatusNotAllowed);
+                }
+                let sql = "INSERT INTO issues \
+                           (title, description, status, \
+                           affects_version, fixed_in_version, assignee, type, \
+                           created_at, updated_at) \
+                           VALUES \
+                           (?, ?, ?, ?, ?, ?, ?, unixepoch(), unixepoch()) \
+                           RETURNING id, created_at, updated_at, version";
+                let row = query(sql)
+                    .bind(new_issue.title.as_str())
+                    .bind(&new_issue.description)
+                    .bind(new_issue.status_id)
+                    .bind(new_issue.affects_version_id)
+                    .bind(new_issue.fixed_in_version_id)
+                    .bind(new_issue.assignee_id)
+                    .bind(new_issue.type_id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(IssueResponse {
+                    id: row.try_get("id")?,
+                    title: new_issue.title.as_str().to_owned(),
+                    description: new_issue.description,
+                    status_id: new_issue.status_id,
+                    milestone_id: None,
+                    affects_version_id: new_issue.affects_version_id,
+                    fixed_in_version_id: new_issue.fixed_in_version_id,
+                    assignee_id: new_issue.assignee_id,
+                    type_id: new_issue.type_id,
+                    created_at: row.try_get("created_at")?,
+                    updated_at: row.try_get("updated_at")?,
+                    version: row.try_get("version")?,
+                })
+            })
+        })
+        .await;
+    ApiResponse::new(result).with_row_version(|issue| issue.version)
+}
+
+/// Loads what `domain::check_status_change` decides on for issue `id`,
+/// given its new type and status, each `None` when left alone.
+pub async fn status_change(
+    connection: &mut SqliteConnection,
+    id: i64,
+    type_id: Option<Option<i64>>,
+    status_id: Option<i64>,
+) -> Result<StatusChange, sqlx::Error> {
+    let row = query("SELECT status, type FROM issues WHERE id = ?")
+        .bind(id)
+        .fetch_one(&mut *connection)
+        .await?;
+    let type_id = match type_id {
+        Some(type_id) => type_id,
+        None => row.try_get("type")?,
+    };
+    let mut allowed_status_ids = Vec::new();
+    if let Some(type_id) = type_id {
+        let mut stream =
+            query("SELECT status FROM issue_type_statuses WHERE type = ?")
+                .bind(type_id)
+                .fetch(&mut *connection);
+        while let Some(row) = stream.try_next().await? {
+            allowed_status_ids.push(row.try_get("status")?);
+        }
+    }
+    let closes = match status_id {
+        Some(status_id) => {
+            query("SELECT closed FROM issue_statuses WHERE id = ?")
+                .bind(status_id)
+                .fetch_optional(&mut *connection)
+                .await?
+                .map(|row| row.try_get("closed"))
+                .transpose()?
+                .unwrap_or(false)
+        },
+        None => false,
+    };
+    let mut open_blocker_ids = Vec::new();
+    if closes {
+        let sql = "SELECT DISTINCT issue_blockings.blocker AS id \
+                   FROM issue_blockings \
+                   JOIN issues ON issues.id = issue_blockings.blocker \
+                   JOIN issue_statuses ON issue_statuses.id = issues.status \
+                   WHERE issue_blockings.blocked = ? \
+                   AND NOT issue_statuses.closed \
+                   ORDER BY issue_blockings.blocker";
+        let mut stream = query(sql).bind(id).fetch(&mut *connection);
+        while let Some(row) = stream.try_next().await? {
+            open_blocker_ids.push(row.try_get("id")?);
+        }
+    }
+    Ok(StatusChange {
+        status_id: status_id.unwrap_or(row.try_get("status")?),
+        allowed_status_ids,
+        closes,
+        open_blocker_ids,
+    })
+}
+
+pub async fn detail_for_issue(
+    connection: &mut SqliteConnection,
+    id: i64,
+    edit_lock: Option<EditLockResponse>,
+) -> Result<IssueDetailResponse, sqlx::Error> {
+    let sql = "SELECT id, title, description, status, milestone, \
+               affects_version, fixed_in_version, assignee, type, \
+               created_at, updated_at, version \
+               FROM issues WHERE id = ?";
+    let row = query(sql).bind(id).fetch_one(&mut *connection).await?;
+    let issue = IssueResponse::from_row(&row)?;
+    let labels = label::labels_for_issue(connection, id).await?;
+    let checks = check::latest_for_issue(connection, id).await?;
+    Ok(IssueDetailResponse { issue, labels, checks, edit_lock })
+}
+
+/// Builds the JSON:API resource object of an issue, pushing the requested
+/// related resources into `included`.
+async fn issue_resource(
+    connection: &mut SqliteConnection,
+    issue: &IssueResponse,
+    labels: &[LabelResponse],
+    jsonapi: &JsonApi,
+    included: &mut Included,
+) -> Result<Resource, sqlx::Error> {
+    if jsonapi.includes(Include::Status) {
+        included.push_status(connection, issue.status_id).await?;
+    }
+    if let Some(assignee_id) = issue.assignee_id {
+        if jsonapi.includes(Include::Assignee) {
+            included.push_user(connection, assignee_id).await?;
+        }
+    }
+    if jsonapi.includes(Include::Labels) {
+        for label in labels {
+            included.push(
+                Resource::new("labels", label.id)
+                    .attribute("name", label.name.clone()),
+            );
+        }
+    }
+    Ok(Resource::new("issues", issue.id)
+        .attribute("title", issue.title.clone())
+        .attribute("description", issue.description.clone())
+        .attribute("created_at", issue.created_at)
+        .attribute("updated_at", issue.updated_at)
+        .attribute("version", issue.version)
+        .has_one("status", "statuses", Some(issue.status_id))
+        .has_one("milestone", "milestones", issue.milestone_id)
+        .has_one("affects_version", "versions", issue.affects_version_id)
+        .has_one("fixed_in_version", "versions", issue.fixed_in_version_id)
+        .has_one("assignee", "users", issue.assignee_id)
+        .has_one("issue_type", "issue_types", issue.type_id)
+        .has_many("labels", "labels", labels.iter().map(|label| label.id)))
+}
+
+async fn get_by_id(
+    Path(id): Path<i64>,
+    jsonapi: JsonApi,
+    resources: Arc<Resources>,
+) -> Response {
+    let edit_lock = resources.edit_locks.holder(id);
+    if !jsonapi.enabled {
+        let result = resources
+            .with_bare_conn(|connection| {
+                Box::pin(async move {
+                    Ok::<_, GetIssueError>(
+                        detail_for_issue(connection, id, edit_lock).await?,
+                    )
+                })
+            })
+            .await;
+        return ApiResponse::new(result)
+            .with_row_version(|detail| detail.issue.version)
+            .into_response();
+    }
+    let result = resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let detail =
+                    detail_for_issue(connection, id, edit_lock).await?;
+                let mut included = Included::default();
+                let resource = issue_resource(
+                    connection,
+                    &detail.issue,
+                    &detail.labels,
+                    &jsonapi,
+                    &mut included,
+                )
+                .await?
+                .attribute("checks", json!(detail.checks))
+                .attribute("edit_lock", json!(detail.edit_lock));
+                let document = Document::new(resource, included);
+                Ok::<_, GetIssueError>((document, detail.issue.version))
+            })
+        })
+        .await;
+    let version = result.as_ref().ok().map(|(_, version)| *version);
+    let mut response = jsonapi::respond(result.map(|(document, _)| document));
+    if let Some(version) = version {
+        response.headers_mut().insert(ETAG, precondition::version_tag(version));
+    }
+    response
+}
+
+async fn delete_by_id(
+    Path(id): Path<i64>,
+    IfMatch(version): IfMatch,
+    resources: Arc<Resources>,
+) -> ApiResponse<IssueResponse, DeleteIssueError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let sql = "DELETE FROM issues WHERE id = ? AND version = ? \
+                           RETURNING \
+                           id, title, description, status, milestone, \
+                           affects_version, fixed_in_version, assignee, type, \
+                           created_at, updated_at, version";
+                let row = query(sql)
+                    .bind(id)
+                    .bind(version)
+                    .fetch_optional(&mut **connection)
+                    .await?;
+                let Some(row) = row else {
+                    query("SELECT 1 FROM issues WHERE id = ?")
+                        .bind(id)
+                        .fetch_one(&mut **connection)
+                        .await?;
+                    return Err(DeleteIssueError::Stale);
+                };
+                Ok(IssueResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn patch_by_id(
+    Path(id): Path<i64>,
+    IfMatch(version): IfMatch,
+    Query(patch_query): Query<PatchIssueQuery>,
+    admin: Option<AdminUser>,
+    Json(payload): Json<PatchIssuePayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<IssueResponse, PatchIssueError> {
+    let patch = match IssuePatch::try_from(payload) {
+        Ok(patch) if patch.is_empty() => {
+            return ApiResponse::new(Err(PatchIssueError::NoFieldsPatched));
+        },
+        Ok(patch) => patch,
+        Err(error) => return ApiResponse::new(Err(error.into())),
+    };
+    if patch_query.force && admin.is_none() {
+        return ApiResponse::new(Err(PatchIssueError::ForceNotAllowed));
+    }
+    let result = resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                if patch.status_id.is_some() || patch.type_id.is_some() {
+                    let change = status_change(
+                        connection,
+                        id,
+                        patch.type_id,
+                        patch.status_id,
+                    )
+                    .await?;
+                    check_status_change(&change, patch_query.force)?;
+                }
+                let sql = "UPDATE issues SET \
+                           title = COALESCE(?, title), \
+                           description = COALESCE(?, description), \
+                           status = COALESCE(?, status), \
+                           affects_version = \
+                           CASE WHEN ? THEN ? ELSE affects_version END, \
+                           fixed_in_version = \
+                           CASE WHEN ? THEN ? ELSE fixed_in_version END, \
+                           assignee = CASE WHEN ? THEN ? ELSE assignee END, \
+                           type = CASE WHEN ? THEN ? ELSE type END, \
+                           updated_at = unixepoch(), \
+                           version = version + 1 \
+                           WHERE id = ? AND version = ? \
+                           RETURNING \
+                           id, title, description, status, milestone, \
+                           affects_version, fixed_in_version, assignee, type, \
+                           created_at, updated_at, version";
+                let row = query(sql)
+                    .bind(patch.title.as_ref().map(Title::as_str))
+                    .bind(&patch.description)
+                    .bind(patch.status_id)
+                    .bind(patch.affects_version_id.is_some())
+                    .bind(patch.affects_version_id.flatten())
+                    .bind(patch.fixed_in_version_id.is_some())
+                    .bind(patch.fixed_in_version_id.flatten())
+                    .bind(patch.assignee_id.is_some())
+                    .bind(patch.assignee_id.flatten())
+                    .bind(patch.type_id.is_some())
+                    .bind(patch.type_id.flatten())
+                    .bind(id)
+                    .bind(version)
+                    .fetch_optional(&mut **connection)
+                    .await?;
+                let Some(row) = row else {
+                    query("SELECT 1 FROM issues WHERE id = ?")
+                        .bind(id)
+                        .fetch_one(&mut **connection)
+                        .await?;
+                    return Err(PatchIssueError::Stale);
+                };
+                Ok(IssueResponse::from_row(&row)?)
+            })
+        })
+        .await;
+    ApiResponse::new(result)
+        .with_row_version(|issue| issue.version)
+        .with_error_detail(PatchIssueError::detail)
+}
+
+async fn get_list(
+    Query(list_query): Query<IssueListQuery>,
+    sort: SortParams<IssueSort>,
+    jsonapi: JsonApi,
+    resources: Arc<Resources>,
+) -> Response {
+    let result = list_issues(list_query, sort, &resources).await;
+    if !jsonapi.enabled {
+        return ApiResponse::new(result).into_response();
+    }
+    let result = match result {
+        Ok(list) => {
+            resources
+                .with_bare_conn(|connection| {
+                    Box::pin(async move {
+                        let mut included = Included::default();
+                        let mut data = Vec::new();
+                        for issue in &list.list {
+                            let labels =
+                                label::labels_for_issue(connection, issue.id)
+                                    .await?;
+                            let resource = issue_resource(
+                                connection,
+                                issue,
+                                &labels,
+                                &jsonapi,
+                                &mut included,
+                            )
+                            .await?;
+                            data.push(resource);
+                        }
+                        let mut document = Document::new(data, included);
+                        if let Some(next_cursor) = list.next_cursor {
+                            document =
+                                document.meta("next_cursor", next_cursor);
+                        }
+                        Ok(document)
+                    })
+                })
+                .await
+        },
+        Err(error) => Err(error),
+    };
+    jsonapi::respond(result)
+}
+
+async fn list_issues(
+    list_query: IssueListQuery,
+    sort: SortParams<IssueSort>,
+    resources: &Resources,
+) -> Result<IssueListResponse, ListIssuesError> {
+    let paginated = list_query.after.is_some() || list_query.limit.is_some();
+    if paginated && !sort.is_default() {
+        return Err(ListIssuesError::SortedPagination);
+    }
+    let after = list_query
+        .after
+        .as_deref()
+        .map(Cursor::decode)
+        .transpose()
+        .map_err(ListIssuesError::InvalidCursor)?;
+    let limit = paginated.then(|| {
+        list_query
+            .limit
+            .unwrap_or(cursor::DEFAULT_LIMIT)
+            .clamp(1, cursor::MAX_LIMIT)
+    });
+    let order_by = if paginated {
+        String::from("COALESCE(created_at, 0), id")
+    } else {
+        sort.order_by()
+    };
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let mut issues = Vec::new();
+                let sql = format!(
+                    "SELECT id, title, description, status, milestone, \
+                     affects_version, fixed_in_version, assignee, type, \
+                     created_at, updated_at, version \
+                     FROM issues \
+                     WHERE (?1 IS NULL OR id IN ( \
+                         SELECT issue_labels.issue FROM issue_labels \
+                         JOIN labels ON labels.id = issue_labels.label \
+                         WHERE labels.name = ?1 \
+                     )) \
+                     AND (?2 IS NULL \
+                         OR instr(lower(title), lower(?2)) > 0) \
+                     AND (?3 IS NULL OR status = ?3) \
+                     AND (?4 IS NULL OR assignee = ?4) \
+                     AND (?5 IS NULL OR milestone = ?5) \
+                     AND (?6 IS NULL OR created_at > ?6) \
+                     AND (?7 IS NULL OR created_at < ?7) \
+                     AND (?8 IS NULL \
+                         OR (COALESCE(created_at, 0), id) > (?8, ?9)) \
+                     ORDER BY {order_by} LIMIT ?10",
+                );
+                let mut stream = query(&sql)
+                    .bind(&list_query.label)
+                    .bind(&list_query.title_contains)
+                    .bind(list_query.status_id)
+                    .bind(list_query.assignee_id)
+                    .bind(list_query.milestone_id)
+                    .bind(list_query.created_after)
+                    .bind(list_query.created_before)
+                    .bind(after.map(|cursor| cursor.created_at))
+                    .bind(after.map(|cursor| cursor.id))
+                    // One extra row tells whether another page follows.
+                    .bind(limit.map_or(-1, |limit| limit + 1))
+                    .fetch(&mut **connection);
+                while let Some(row) = stream.try_next().await? {
+                    issues.push(IssueResponse::from_row(&row)?);
+                }
+                let mut next_cursor = None;
+                if let Some(limit) = limit {
+                    if issues.len() as i64 > limit {
+                        issues.truncate(limit as usize);
+                        next_cursor = issues.last().map(|issue| {
+                            Cursor {
+                                created_at: issue.created_at.unwrap_or(0),
+                                id: issue.id,
+                            }
+                            .encode()
+                        });
+                    }
+                }
+                Ok(IssueListResponse { list: issues, next_cursor })
+            })
+        })
+        .await
+}
@@ -0,0 +1,454 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    routing::{delete, get, patch, post},
+    Json,
+    Router,
+};
+use futures::TryStreamExt;
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, sqlite::SqliteRow, Row, SqliteConnection};
+use thiserror::Error;
+
+use crate::{
+    domain::{DomainError, Name},
+    status::ResponseStatusCode,
+};
+
+use super::{is_foreign_key_violation, response::ApiResponse, Resources};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct NewIssueTypePayload {
+    name: String,
+    /// Description given to new issues of this type that come without one.
+    #[serde(default)]
+    template: String,
+    /// Statuses issues of this type may be in; any status when empty.
+    #[serde(default)]
+    status_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct PatchIssueTypePayload {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    template: Option<String>,
+    /// Replaces the allowed statuses.
+    #[serde(default)]
+    status_ids: Option<Vec<i64>>,
+}
+
+#[derive(Debug, Error)]
+enum NewIssueTypeError {
+    #[error(transparent)]
+    Invalid(#[from] DomainError),
+    #[error("Issue type with the given name already exists")]
+    AlreadyExists,
+    #[error("Referenced status not found")]
+    StatusNotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for NewIssueTypeError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if error.is_unique_violation() {
+                return Self::AlreadyExists;
+            }
+            if is_foreign_key_violation(&**error) {
+                return Self::StatusNotFound;
+            }
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for NewIssueTypeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
+            Self::AlreadyExists => StatusCode::FORBIDDEN,
+            Self::StatusNotFound => StatusCode::BAD_REQUEST,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum GetIssueTypeError {
+    #[error("Issue type not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for GetIssueTypeError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for GetIssueTypeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum PatchIssueTypeError {
+    #[error(transparent)]
+    Invalid(#[from] DomainError),
+    #[error("At least one field must be patched, none were")]
+    NoFieldsPatched,
+    #[error("Issue type with the given name already exists")]
+    AlreadyExists,
+    #[error("Referenced status not found")]
+    StatusNotFound,
+    #[error("Issue type not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for PatchIssueTypeError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if error.is_unique_violation() {
+                return Self::AlreadyExists;
+            }
+            if is_foreign_key_violation(&**error) {
+                return Self::StatusNotFound;
+            }
+        }
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for PatchIssueTypeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
+            Self::NoFieldsPatched => StatusCode::BAD_REQUEST,
+            Self::AlreadyExists => StatusCode::FORBIDDEN,
+            Self::StatusNotFound => StatusCode::BAD_REQUEST,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct IssueTypeResponse {
+    id: i64,
+    name: String,
+    template: String,
+    status_ids: Vec<i64>,
+}
+
+impl IssueTypeResponse {
+    async fn from_row(
+        connection: &mut SqliteConnection,
+        row: &SqliteRow,
+    ) -> Result<Self, sqlx::Error> {
+        let id = row.try_get("id")?;
+        Ok(Self {
+            id,
+            name: row.try_get("name")?,
+            template: row.try_get("template")?,
+            status_ids: status_ids(connection, id).await?,
+        })
+    }
+}
+
+impl ResponseStatusCode for IssueTypeResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct IssueTypeListResponse {
+    list: Vec<IssueTypeResponse>,
+}
+
+impl ResponseStatusCode for IssueTypeListResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("NewIssueTypePayload", schema_for!(NewIssueTypePayload)),
+        ("PatchIssueTypePayload", schema_for!(PatchIssueTypePayload)),
+        ("IssueTypeResponse", schema_for!(IssueTypeResponse)),
+        ("IssueTypeListResponse", schema_for!(IssueTypeListResponse)),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/new",
+            post({
+                let resources = resources.clone();
+                move |body| post_new(body, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            get({
+                let resources = resources.clone();
+                move |id| get_by_id(id, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            delete({
+                let resources = resources.clone();
+                move |id| delete_by_id(id, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            patch({
+                let resources = resources.clone();
+                move |id, payload| patch_by_id(id, payload, resources)
+            }),
+        )
+        .route(
+            "/list/",
+            get({
+                let resources = resources.clone();
+                move || get_list(resources)
+            }),
+        )
+}
+
+/// Whether issues of the given type may be in `status_id`. Issues without
+/// a type, and types without statuses of their own, allow every status.
+pub async fn allows_status(
+    connection: &mut SqliteConnection,
+    type_id: Option<i64>,
+    status_id: i64,
+) -> Result<bool, sqlx::Error> {
+    let Some(type_id) = type_id else {
+        return Ok(true);
+    };
+    let sql = "SELECT \
+               NOT EXISTS ( \
+                   SELECT 1 FROM issue_type_statuses WHERE type = ?1 \
+               ) OR EXISTS ( \
+                   SELECT 1 FROM issue_type_statuses \
+                   WHERE type = ?1 AND status = ?2 \
+               ) AS allowed";
+    query(sql)
+        .bind(type_id)
+        .bind(status_id)
+        .fetch_one(connection)
+        .await?
+        .try_get("allowed")
+}
+
+/// Description template of a type, `None` when the type does not exist.
+pub async fn template(
+    connection: &mut SqliteConnection,
+    type_id: i64,
+) -> Result<Option<String>, sqlx::Error> {
+    query("SELECT template FROM issue_types WHERE id = ?")
+        .bind(type_id)
+        .fetch_optional(connection)
+        .await?
+        .map(|row| row.try_get("template"))
+        .transpose()
+}
+
+async fn status_ids(
+    connection: &mut SqliteConnection,
+    type_id: i64,
+) -> Result<Vec<i64>, sqlx::Error> {
+    let mut ids = Vec::new();
+    let mut stream = query(
+        "SELECT status FROM issue_type_statuses WHERE type = ? \
+         ORDER BY status",
+    )
+    .bind(type_id)
+    .fetch(connection);
+    while let Some(row) = stream.try_next().await? {
+        ids.push(row.try_get("status")?);
+    }
+    Ok(ids)
+}
+
+async fn set_status_ids(
+    connection: &mut SqliteConnection,
+    type_id: i64,
+    status_ids: &[i64],
+) -> Result<(), sqlx::Error> {
+    query("DELETE FROM issue_type_statuses WHERE type = ?")
+        .bind(type_id)
+        .execute(&mut *connection)
+        .await?;
+    for status_id in status_ids {
+        query(
+            "INSERT OR IGNORE INTO issue_type_statuses (type, status) \
+             VALUES (?, ?)",
+        )
+        .bind(type_id)
+        .bind(status_id)
+        .execute(&mut *connection)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn post_new(
+    Json(payload): Json<NewIssueTypePayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<IssueTypeResponse, NewIssueTypeError> {
+    let name = match Name::parse(&payload.name) {
+        Ok(name) => name,
+        Err(error) => return ApiResponse::new(Err(error.into())),
+    };
+    resources
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                let sql = "INSERT INTO issue_types (name, template) \
+                           VALUES (?, ?) RETURNING id, name, template";
+                let row = query(sql)
+                    .bind(name.as_str())
+                    .bind(&payload.template)
+                    .fetch_one(&mut **transaction)
+                    .await?;
+                let id = row.try_get("id")?;
+                set_status_ids(transaction, id, &payload.status_ids).await?;
+                Ok(IssueTypeResponse::from_row(transaction, &row).await?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_by_id(
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<IssueTypeResponse, GetIssueTypeError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let sql =
+                    "SELECT id, name, template FROM issue_types WHERE id = ?";
+                let row =
+                    query(sql).bind(id).fetch_one(&mut **connection).await?;
+                Ok(IssueTypeResponse::from_row(connection, &row).await?)
+            })
+        })
+        .await
+        .into()
+}
+
+/// Issues of a deleted type are left without a type.
+async fn delete_by_id(
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<IssueTypeResponse, GetIssueTypeError> {
+    resources
+        .with_transaction(|transaction| {
+            Box::pin(async move {
+                let status_ids = status_ids(transaction, id).await?;
+                let sql = "DELETE FROM issue_types WHERE id = ? \
+                           RETURNING id, name, template";
+                let row =
+                    query(sql).bind(id).fetch_one(&mut **transaction).await?;
+                Ok(IssueTypeResponse {
+                    id,
+                    name: row.try_get("name")?,
+                    template: row.try_get("template")?,
+                    status_ids,
+                })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn patch_by_id(
+    Path(id): Path<i64>,
+    Json(payload): Json<PatchIssueTypePayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<IssueTypeResponse, PatchIssueTypeError> {
+    if payload.name.is_none()
+        && payload.template.is_none()
+        && payload.status_ids.is_none()
+    {
+        return ApiResponse::new(Err(PatchIssueTypeError::NoFieldsPatched));
+    }
+    let name = match payload.name.as_deref().map(Name::parse).transpose() {
+        Ok(name) => name,
+        Err(error) => return ApiResponse::new(Err(error.into())),
+    };
+    resources
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                let sql = "UPDATE issue_types SET \
+                           name = COALESCE(?, name), \
+                           template = COALESCE(?, template) \
+                           WHERE id = ? \
+                           RETURNING id, name, template";
+                let row = query(sql)
+                    .bind(name.as_ref().map(Name::as_str))
+                    .bind(&payload.template)
+                    .bind(id)
+                    .fetch_one(&mut **transaction)
+                    .await?;
+                if let Some(status_ids) = &payload.status_ids {
+                    set_status_ids(transaction, id, status_ids).await?;
+                }
+                Ok(IssueTypeResponse::from_row(transaction, &row).await?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_list(
+    resources: Arc<Resources>,
+) -> ApiResponse<IssueTypeListResponse, GetIssueTypeError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let mut rows = Vec::new();
+                let mut stream = query(
+                    "SELECT id, name, template FROM issue_types ORDER BY id",
+                )
+                .fetch(&mut **connection);
+                while let Some(row) = stream.try_next().await? {
+                    rows.push(row);
+                }
+                drop(stream);
+                let mut issue_types = Vec::new();
+                for row in &rows {
+                    issue_types.push(
+                        IssueTypeResponse::from_row(connection, row).await?,
+                    );
+                }
+                Ok(IssueTypeListResponse { list: issue_types })
+            })
+        })
+        .await
+        .into()
+}
@@ -0,0 +1,276 @@
+use std::error::Error;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{
+        header::{ACCEPT, CONTENT_TYPE},
+        request::Parts,
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sqlx::{query, Row, SqliteConnection};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+pub const MEDIA_TYPE: &str = "application/vnd.api+json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Include {
+    Status,
+    Labels,
+    Assignee,
+}
+
+impl Include {
+    fn parse(name: &str) -> Result<Self, JsonApiError> {
+        match name {
+            "status" => Ok(Self::Status),
+            "labels" => Ok(Self::Labels),
+            "assignee" => Ok(Self::Assignee),
+            _ => Err(JsonApiError::UnknownInclude(name.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct IncludeQuery {
+    #[serde(default)]
+    include: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum JsonApiError {
+    #[error("Cannot include relationship {0:?}")]
+    UnknownInclude(String),
+    #[error("Failed to parse include parameter")]
+    Query(#[source] axum::extract::rejection::QueryRejection),
+}
+
+impl ResponseStatusCode for JsonApiError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+impl IntoResponse for JsonApiError {
+    fn into_response(self) -> Response {
+        respond(Err(self))
+    }
+}
+
+/// Negotiated through `Accept: application/vnd.api+json`; `include` is only
+/// honored when the JSON:API representation was requested.
+#[derive(Debug, Clone, Default)]
+pub struct JsonApi {
+    pub enabled: bool,
+    pub include: Vec<Include>,
+}
+
+impl JsonApi {
+    pub fn includes(&self, include: Include) -> bool {
+        self.include.contains(&include)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for JsonApi
+where
+    S: Send + Sync,
+{
+    type Rejection = JsonApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let enabled = parts
+            .headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|media_type| media_type.trim() == MEDIA_TYPE);
+        if !enabled {
+            return Ok(Self::default());
+        }
+        let Query(query) =
+            Query::<IncludeQuery>::from_request_parts(parts, state)
+                .await
+                .map_err(JsonApiError::Query)?;
+        let include = query
+            .include
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(Include::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { enabled, include })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Resource {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub id: String,
+    pub attributes: Map<String, Value>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub relationships: Map<String, Value>,
+}
+
+impl Resource {
+    pub fn new(kind: &'static str, id: i64) -> Self {
+        Self {
+            kind,
+            id: id.to_string(),
+            attributes: Map::new(),
+            relationships: Map::new(),
+        }
+    }
+
+    pub fn attribute(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.attributes.insert(name.to_owned(), value.into());
+        self
+    }
+
+    pub fn has_one(
+        mut self,
+        name: &str,
+        kind: &'static str,
+        id: Option<i64>,
+    ) -> Self {
+        let data = match id {
+            Some(id) => json!({ "type": kind, "id": id.to_string() }),
+            None => Value::Null,
+        };
+        self.relationships.insert(name.to_owned(), json!({ "data": data }));
+        self
+    }
+
+    pub fn has_many(
+        mut self,
+        name: &str,
+        kind: &'static str,
+        ids: impl IntoIterator<Item = i64>,
+    ) -> Self {
+        let data: Vec<_> = ids
+            .into_iter()
+            .map(|id| json!({ "type": kind, "id": id.to_string() }))
+            .collect();
+        self.relationships.insert(name.to_owned(), json!({ "data": data }));
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Included {
+    resources: Vec<Resource>,
+}
+
+impl Included {
+    pub fn push(&mut self, resource: Resource) {
+        let exists = self.resources.iter().any(|included| {
+            included.kind == resource.kind && included.id == resource.id
+        });
+        if !exists {
+            self.resources.push(resource);
+        }
+    }
+
+    pub async fn push_status(
+        &mut self,
+        connection: &mut SqliteConnection,
+        id: i64,
+    ) -> Result<(), sqlx::Error> {
+        let row = query("SELECT name, closed FROM issue_statuses WHERE id = ?")
+            .bind(id)
+            .fetch_one(connection)
+            .await?;
+        let name: String = row.try_get("name")?;
+        let closed: bool = row.try_get("closed")?;
+        self.push(
+            Resource::new("statuses", id)
+                .attribute("name", name)
+                .attribute("closed", closed),
+        );
+        Ok(())
+    }
+
+    pub async fn push_user(
+        &mut self,
+        connection: &mut SqliteConnection,
+        id: i64,
+    ) -> Result<(), sqlx::Error> {
+        let row = query("SELECT name FROM users WHERE id = ?")
+            .bind(id)
+            .fetch_one(connection)
+            .await?;
+        let name: String = row.try_get("name")?;
+        self.push(Resource::new("users", id).attribute("name", name));
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Document {
+    data: Value,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    included: Vec<Resource>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    meta: Map<String, Value>,
+}
+
+impl Document {
+    pub fn new(data: impl Serialize, included: Included) -> Self {
+        Self {
+            data: serde_json::to_value(data).unwrap_or(Value::Null),
+            included: included.resources,
+            meta: Map::new(),
+        }
+    }
+
+    pub fn meta(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.meta.insert(name.to_owned(), value.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ErrorObject {
+    status: String,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+pub fn respond<E>(result: Result<Document, E>) -> Response
+where
+    E: Error + ResponseStatusCode,
+{
+    let (status, body) = match result {
+        Ok(document) => (StatusCode::OK, json!(document)),
+        Err(error) => {
+            let status = error.status_code();
+            let mut causes = Vec::new();
+            let mut source = error.source();
+            while let Some(cause) = source {
+                causes.push(cause.to_string());
+                source = cause.source();
+            }
+            let object = ErrorObject {
+                status: status.as_u16().to_string(),
+                title: error.to_string(),
+                detail: (!causes.is_empty()).then(|| causes.join(": ")),
+            };
+            (status, json!({ "errors": [object] }))
+        },
+    };
+    (status, [(CONTENT_TYPE, MEDIA_TYPE)], Json(body)).into_response()
+}
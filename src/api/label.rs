@@ -0,0 +1,674 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    routing::{delete, get, patch, post, put},
+    Json,
+    Router,
+};
+use futures::TryStreamExt;
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, Row, SqliteConnection};
+use thiserror::Error;
+
+use crate::{
+    domain::{DomainError, Name, NewLabel},
+    status::ResponseStatusCode,
+};
+
+use super::{
+    is_foreign_key_violation,
+    response::ApiResponse,
+    undo::UndoAction,
+    Resources,
+};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct NewLabelPayload {
+    name: String,
+}
+
+impl TryFrom<&NewLabelPayload> for NewLabel {
+    type Error = DomainError;
+
+    fn try_from(payload: &NewLabelPayload) -> Result<Self, Self::Error> {
+        Ok(Self { name: Name::parse(&payload.name)? })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct PatchLabelPayload {
+    #[serde(default)]
+    name: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MergeLabelQuery {
+    /// Only counts the affected issues, changing nothing.
+    #[serde(default)]
+    preview: bool,
+}
+
+#[derive(Debug, Error)]
+enum NewLabelError {
+    #[error(transparent)]
+    Invalid(#[from] DomainError),
+    #[error("Label with the given name already exists")]
+    AlreadyExists,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for NewLabelError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if error.is_unique_violation() {
+                return Self::AlreadyExists;
+            }
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for NewLabelError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
+            Self::AlreadyExists => StatusCode::FORBIDDEN,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum GetLabelError {
+    #[error("Label not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for GetLabelError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for GetLabelError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum AttachLabelError {
+    #[error("Issue or label not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for AttachLabelError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if is_foreign_key_violation(&**error) {
+                return Self::NotFound;
+            }
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for AttachLabelError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum DetachLabelError {
+    #[error("Label is not attached to the issue")]
+    NotAttached,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for DetachLabelError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotAttached;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for DetachLabelError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotAttached => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum PatchLabelError {
+    #[error(transparent)]
+    Invalid(#[from] DomainError),
+    #[error("At least one field must be patched, none were")]
+    NoFieldsPatched,
+    #[error("Label with the given name already exists")]
+    AlreadyExists,
+    #[error("Label not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for PatchLabelError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if error.is_unique_violation() {
+                return Self::AlreadyExists;
+            }
+        }
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for PatchLabelError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
+            Self::NoFieldsPatched => StatusCode::BAD_REQUEST,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::AlreadyExists => StatusCode::FORBIDDEN,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum MergeLabelError {
+    #[error("Label cannot be merged into itself")]
+    SameLabel,
+    #[error("Source label not found")]
+    SourceNotFound,
+    #[error("Target label not found")]
+    TargetNotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+impl ResponseStatusCode for MergeLabelError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::SameLabel => StatusCode::BAD_REQUEST,
+            Self::SourceNotFound | Self::TargetNotFound => {
+                StatusCode::NOT_FOUND
+            },
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct LabelResponse {
+    pub id: i64,
+    pub name: String,
+}
+
+impl ResponseStatusCode for LabelResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct LabelListResponse {
+    list: Vec<LabelResponse>,
+}
+
+impl ResponseStatusCode for LabelListResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct MergeLabelResponse {
+    source: LabelResponse,
+    target: LabelResponse,
+    /// Issues carrying the source label, which now carry the target
+    /// instead.
+    affected_issues: i64,
+    preview: bool,
+}
+
+impl ResponseStatusCode for MergeLabelResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("NewLabelPayload", schema_for!(NewLabelPayload)),
+        ("PatchLabelPayload", schema_for!(PatchLabelPayload)),
+        ("LabelResponse", schema_for!(LabelResponse)),
+        ("LabelListResponse", schema_for!(LabelListResponse)),
+        ("MergeLabelResponse", schema_for!(MergeLabelResponse)),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/new",
+            post({
+                let resources = resources.clone();
+                move |body| post_new(body, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            get({
+                let resources = resources.clone();
+                move |id| get_by_id(id, resources)
+            }),
+        )
+        .route(
+            "/name/:name",
+            get({
+                let resources = resources.clone();
+                move |name| get_by_name(name, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            delete({
+                let resources = resources.clone();
+                move |id| delete_by_id(id, resources)
+            }),
+        )
+        .route(
+            "/name/:name",
+            delete({
+                let resources = resources.clone();
+                move |name| delete_by_name(name, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            patch({
+                let resources = resources.clone();
+                move |id, payload| patch_by_id(id, payload, resources)
+            }),
+        )
+        .route(
+            "/name/:name",
+            patch({
+                let resources = resources.clone();
+                move |name, payload| patch_by_name(name, payload, resources)
+            }),
+        )
+        .route(
+            "/id/:id/merge-into/:target",
+            post({
+                let resources = resources.clone();
+                move |ids, query| merge_into(ids, query, resources)
+            }),
+        )
+        .route(
+            "/list/",
+            get({
+                let resources = resources.clone();
+                move || get_list(resources)
+            }),
+        )
+}
+
+pub fn issue_router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/id/:id/labels/:label_id",
+            put({
+                let resources = resources.clone();
+                move |ids| attach(ids, resources)
+            }),
+        )
+        .route(
+            "/id/:id/labels/:label_id",
+            delete({
+                let resources = resources.clone();
+                move |ids| detach(ids, resources)
+            }),
+        )
+}
+
+pub async fn labels_for_issue(
+    connection: &mut SqliteConnection,
+    issue_id: i64,
+) -> Result<Vec<LabelResponse>, sqlx::Error> {
+    let mut labels = Vec::new();
+    let sql = "SELECT labels.id, labels.name FROM labels \
+               JOIN issue_labels ON issue_labels.label = labels.id \
+               WHERE issue_labels.issue = ? \
+               ORDER BY labels.name";
+    let mut stream = query(sql).bind(issue_id).fetch(connection);
+    while let Some(row) = stream.try_next().await? {
+        let id = row.try_get("id")?;
+        let name = row.try_get("name")?;
+        labels.push(LabelResponse { id, name });
+    }
+    Ok(labels)
+}
+
+async fn attach(
+    Path((issue_id, label_id)): Path<(i64, i64)>,
+    resources: Arc<Resources>,
+) -> ApiResponse<LabelListResponse, AttachLabelError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                query(
+                    "INSERT OR IGNORE INTO issue_labels (issue, label) \
+                     VALUES (?, ?)",
+                )
+                .bind(issue_id)
+                .bind(label_id)
+                .execute(&mut **connection)
+                .await?;
+                let list = labels_for_issue(connection, issue_id).await?;
+                Ok(LabelListResponse { list })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn detach(
+    Path((issue_id, label_id)): Path<(i64, i64)>,
+    resources: Arc<Resources>,
+) -> ApiResponse<LabelListResponse, DetachLabelError> {
+    let result = resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                query(
+                    "DELETE FROM issue_labels WHERE issue = ? AND label = ? \
+                     RETURNING label",
+                )
+                .bind(issue_id)
+                .bind(label_id)
+                .fetch_one(&mut **connection)
+                .await?;
+                let list = labels_for_issue(connection, issue_id).await?;
+                Ok(LabelListResponse { list })
+            })
+        })
+        .await;
+    let undo = result.is_ok().then(|| {
+        resources.undo.register(UndoAction::AttachLabel { issue_id, label_id })
+    });
+    ApiResponse::new(result).with_undo(undo.flatten())
+}
+
+async fn post_new(
+    Json(new_label): Json<NewLabelPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<LabelResponse, NewLabelError> {
+    let new_label = match NewLabel::try_from(&new_label) {
+        Ok(new_label) => new_label,
+        Err(error) => return ApiResponse::new(Err(error.into())),
+    };
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let row =
+                    query("INSERT INTO labels (name) VALUES (?) RETURNING id")
+                        .bind(new_label.name.as_str())
+                        .fetch_one(&mut **connection)
+                        .await?;
+                let id = row.try_get("id")?;
+                let name = new_label.name.as_str().to_owned();
+                Ok(LabelResponse { id, name })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_by_id(
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<LabelResponse, GetLabelError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let row = query("SELECT name FROM labels WHERE id = ?")
+                    .bind(id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                let name = row.try_get("name")?;
+                Ok(LabelResponse { id, name })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_by_name(
+    Path(name): Path<String>,
+    resources: Arc<Resources>,
+) -> ApiResponse<LabelResponse, GetLabelError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let row = query("SELECT id FROM labels WHERE name = ?")
+                    .bind(&name)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                let id = row.try_get("id")?;
+                Ok(LabelResponse { id, name })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn delete_by_id(
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<LabelResponse, GetLabelError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let row =
+                    query("DELETE FROM labels WHERE id = ? RETURNING name")
+                        .bind(id)
+                        .fetch_one(&mut **connection)
+                        .await?;
+                let name = row.try_get("name")?;
+                Ok(LabelResponse { id, name })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn delete_by_name(
+    Path(name): Path<String>,
+    resources: Arc<Resources>,
+) -> ApiResponse<LabelResponse, GetLabelError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let row =
+                    query("DELETE FROM labels WHERE name = ? RETURNING id")
+                        .bind(&name)
+                        .fetch_one(&mut **connection)
+                        .await?;
+                let id = row.try_get("id")?;
+                Ok(LabelResponse { id, name })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn patch_by_id(
+    Path(id): Path<i64>,
+    Json(payload): Json<PatchLabelPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<LabelResponse, PatchLabelError> {
+    let new_name = match payload.name.as_deref().map(Name::parse) {
+        Some(Ok(name)) => name.as_str().to_owned(),
+        Some(Err(error)) => return ApiResponse::new(Err(error.into())),
+        None => return ApiResponse::new(Err(PatchLabelError::NoFieldsPatched)),
+    };
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                query("UPDATE labels SET name = ? WHERE id = ? RETURNING id")
+                    .bind(&new_name)
+                    .bind(id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(LabelResponse { id, name: new_name })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn patch_by_name(
+    Path(name): Path<String>,
+    Json(payload): Json<PatchLabelPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<LabelResponse, PatchLabelError> {
+    let new_name = match payload.name.as_deref().map(Name::parse) {
+        Some(Ok(name)) => name.as_str().to_owned(),
+        Some(Err(error)) => return ApiResponse::new(Err(error.into())),
+        None => return ApiResponse::new(Err(PatchLabelError::NoFieldsPatched)),
+    };
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let sql =
+                    "UPDATE labels SET name = ? WHERE name = ? RETURNING id";
+                let row = query(sql)
+                    .bind(&new_name)
+                    .bind(&name)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                let id = row.try_get("id")?;
+                Ok(LabelResponse { id, name: new_name })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn label_name(
+    connection: &mut SqliteConnection,
+    id: i64,
+) -> Result<Option<String>, sqlx::Error> {
+    let row = query("SELECT name FROM labels WHERE id = ?")
+        .bind(id)
+        .fetch_optional(connection)
+        .await?;
+    row.map(|row| row.try_get("name")).transpose()
+}
+
+/// Moves every issue from the source label to the target one and deletes the
+/// source, all in one transaction.
+async fn merge_into(
+    Path((id, target_id)): Path<(i64, i64)>,
+    Query(merge): Query<MergeLabelQuery>,
+    resources: Arc<Resources>,
+) -> ApiResponse<MergeLabelResponse, MergeLabelError> {
+    if id == target_id {
+        return ApiResponse::new(Err(MergeLabelError::SameLabel));
+    }
+    resources
+        .with_transaction(|transaction| {
+            Box::pin(async move {
+                let name = label_name(transaction, id)
+                    .await?
+                    .ok_or(MergeLabelError::SourceNotFound)?;
+                let target_name = label_name(transaction, target_id)
+                    .await?
+                    .ok_or(MergeLabelError::TargetNotFound)?;
+                let row = query(
+                    "SELECT COUNT(*) AS count FROM issue_labels \
+                     WHERE label = ?",
+                )
+                .bind(id)
+                .fetch_one(&mut **transaction)
+                .await?;
+                let affected_issues = row.try_get("count")?;
+                if !merge.preview {
+                    query(
+                        "INSERT OR IGNORE INTO issue_labels (issue, label) \
+                         SELECT issue, ? FROM issue_labels WHERE label = ?",
+                    )
+                    .bind(target_id)
+                    .bind(id)
+                    .execute(&mut **transaction)
+                    .await?;
+                    query("DELETE FROM labels WHERE id = ?")
+                        .bind(id)
+                        .execute(&mut **transaction)
+                        .await?;
+                }
+                Ok(MergeLabelResponse {
+                    source: LabelResponse { id, name },
+                    target: LabelResponse { id: target_id, name: target_name },
+                    affected_issues,
+                    preview: merge.preview,
+                })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_list(
+    resources: Arc<Resources>,
+) -> ApiResponse<LabelListResponse, GetLabelError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let mut labels = Vec::new();
+                let mut stream =
+                    query("SELECT id, name FROM labels ORDER BY id")
+                        .fetch(&mut **connection);
+                while let Some(row) = stream.try_next().await? {
+                    let id = row.try_get("id")?;
+                    let name = row.try_get("name")?;
+                    labels.push(LabelResponse { id, name });
+                }
+                Ok(LabelListResponse { list: labels })
+            })
+        })
+        .await
+        .into()
+}
@@ -0,0 +1,57 @@
+use std::{fmt, sync::Arc};
+
+use axum::{
+    extract::Request,
+    http::{header::RETRY_AFTER, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{
+    response::{ApiResponse, NoData},
+    Resources,
+};
+
+#[derive(Debug, Error)]
+#[error("Server is under maintenance")]
+struct UnderMaintenance {
+    #[source]
+    message: Option<Message>,
+}
+
+/// Operator supplied explanation, shown after the generic error.
+#[derive(Debug)]
+struct Message(String);
+
+impl fmt::Display for Message {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Message {}
+
+impl ResponseStatusCode for UnderMaintenance {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+pub async fn reject_requests(request: Request, next: Next) -> Response {
+    let resources = request
+        .extensions()
+        .get::<Arc<Resources>>()
+        .cloned()
+        .expect("API resources must be installed as an extension");
+    let Some(state) = resources.maintenance.active().await else {
+        return next.run(request).await;
+    };
+    let error = UnderMaintenance { message: state.message.map(Message) };
+    let mut response =
+        ApiResponse::<NoData, _>::new(Err(error)).into_response();
+    response.headers_mut().insert(RETRY_AFTER, state.retry_after.into());
+    response
+}
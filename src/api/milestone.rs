@@ -0,0 +1,508 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    routing::{delete, get, patch, post, put},
+    Json,
+    Router,
+};
+use futures::TryStreamExt;
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, sqlite::SqliteRow, Row};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{is_foreign_key_violation, response::ApiResponse, Resources};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct NewMilestonePayload {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    due_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct PatchMilestonePayload {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    due_date: Option<String>,
+}
+
+#[derive(Debug, Error)]
+enum NewMilestoneError {
+    #[error("Milestone with the given name already exists")]
+    AlreadyExists,
+    #[error("Due date must be formatted as YYYY-MM-DD")]
+    InvalidDueDate,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for NewMilestoneError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if error.is_unique_violation() {
+                return Self::AlreadyExists;
+            }
+            if error.is_check_violation() {
+                return Self::InvalidDueDate;
+            }
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for NewMilestoneError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::AlreadyExists => StatusCode::FORBIDDEN,
+            Self::InvalidDueDate => StatusCode::BAD_REQUEST,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum GetMilestoneError {
+    #[error("Milestone not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for GetMilestoneError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for GetMilestoneError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum PatchMilestoneError {
+    #[error("At least one field must be patched, none were")]
+    NoFieldsPatched,
+    #[error("Milestone with the given name already exists")]
+    AlreadyExists,
+    #[error("Due date must be formatted as YYYY-MM-DD")]
+    InvalidDueDate,
+    #[error("Milestone not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for PatchMilestoneError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if error.is_unique_violation() {
+                return Self::AlreadyExists;
+            }
+            if error.is_check_violation() {
+                return Self::InvalidDueDate;
+            }
+        }
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for PatchMilestoneError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NoFieldsPatched => StatusCode::BAD_REQUEST,
+            Self::AlreadyExists => StatusCode::FORBIDDEN,
+            Self::InvalidDueDate => StatusCode::BAD_REQUEST,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum AssignIssueError {
+    #[error("Issue or milestone not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for AssignIssueError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if is_foreign_key_violation(&**error) {
+                return Self::NotFound;
+            }
+        }
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for AssignIssueError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct MilestoneResponse {
+    id: i64,
+    name: String,
+    description: String,
+    due_date: Option<String>,
+}
+
+impl MilestoneResponse {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            description: row.try_get("description")?,
+            due_date: row.try_get("due_date")?,
+        })
+    }
+}
+
+impl ResponseStatusCode for MilestoneResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct MilestoneListResponse {
+    list: Vec<MilestoneResponse>,
+}
+
+impl ResponseStatusCode for MilestoneListResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct MilestoneProgressResponse {
+    milestone_id: i64,
+    open: i64,
+    closed: i64,
+    total: i64,
+}
+
+impl ResponseStatusCode for MilestoneProgressResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct MilestoneAssignmentResponse {
+    milestone_id: Option<i64>,
+    issue_id: i64,
+}
+
+impl ResponseStatusCode for MilestoneAssignmentResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("NewMilestonePayload", schema_for!(NewMilestonePayload)),
+        ("PatchMilestonePayload", schema_for!(PatchMilestonePayload)),
+        ("MilestoneResponse", schema_for!(MilestoneResponse)),
+        ("MilestoneListResponse", schema_for!(MilestoneListResponse)),
+        ("MilestoneProgressResponse", schema_for!(MilestoneProgressResponse)),
+        (
+            "MilestoneAssignmentResponse",
+            schema_for!(MilestoneAssignmentResponse),
+        ),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/new",
+            post({
+                let resources = resources.clone();
+                move |body| post_new(body, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            get({
+                let resources = resources.clone();
+                move |id| get_by_id(id, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            delete({
+                let resources = resources.clone();
+                move |id| delete_by_id(id, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            patch({
+                let resources = resources.clone();
+                move |id, payload| patch_by_id(id, payload, resources)
+            }),
+        )
+        .route(
+            "/id/:id/progress",
+            get({
+                let resources = resources.clone();
+                move |id| get_progress(id, resources)
+            }),
+        )
+        .route(
+            "/id/:id/issues/:issue_id",
+            put({
+                let resources = resources.clone();
+                move |ids| assign_issue(ids, resources)
+            }),
+        )
+        .route(
+            "/id/:id/issues/:issue_id",
+            delete({
+                let resources = resources.clone();
+                move |ids| unassign_issue(ids, resources)
+            }),
+        )
+        .route(
+            "/list/",
+            get({
+                let resources = resources.clone();
+                move || get_list(resources)
+            }),
+        )
+}
+
+async fn post_new(
+    Json(new_milestone): Json<NewMilestonePayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<MilestoneResponse, NewMilestoneError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let sql = "INSERT INTO milestones \
+                           (name, description, due_date) \
+                           VALUES (?, ?, ?) RETURNING id";
+                let row = query(sql)
+                    .bind(&new_milestone.name)
+                    .bind(&new_milestone.description)
+                    .bind(&new_milestone.due_date)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                let id = row.try_get("id")?;
+                Ok(MilestoneResponse {
+                    id,
+                    name: new_milestone.name,
+                    description: new_milestone.description,
+                    due_date: new_milestone.due_date,
+                })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_by_id(
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<MilestoneResponse, GetMilestoneError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let sql = "SELECT id, name, description, due_date \
+                           FROM milestones WHERE id = ?";
+                let row =
+                    query(sql).bind(id).fetch_one(&mut **connection).await?;
+                Ok(MilestoneResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn delete_by_id(
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<MilestoneResponse, GetMilestoneError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let sql = "DELETE FROM milestones WHERE id = ? \
+                           RETURNING id, name, description, due_date";
+                let row =
+                    query(sql).bind(id).fetch_one(&mut **connection).await?;
+                Ok(MilestoneResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn patch_by_id(
+    Path(id): Path<i64>,
+    Json(payload): Json<PatchMilestonePayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<MilestoneResponse, PatchMilestoneError> {
+    if payload.name.is_none()
+        && payload.description.is_none()
+        && payload.due_date.is_none()
+    {
+        return ApiResponse::new(Err(PatchMilestoneError::NoFieldsPatched));
+    }
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let sql = "UPDATE milestones SET \
+                           name = COALESCE(?, name), \
+                           description = COALESCE(?, description), \
+                           due_date = COALESCE(?, due_date) \
+                           WHERE id = ? \
+                           RETURNING id, name, description, due_date";
+                let row = query(sql)
+                    .bind(&payload.name)
+                    .bind(&payload.description)
+                    .bind(&payload.due_date)
+                    .bind(id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(MilestoneResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_progress(
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<MilestoneProgressResponse, GetMilestoneError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let sql = "SELECT \
+                           COUNT(issues.id) AS total, \
+                           COALESCE(SUM(issue_statuses.closed), 0) AS closed \
+                           FROM milestones \
+                           LEFT JOIN issues \
+                           ON issues.milestone = milestones.id \
+                           LEFT JOIN issue_statuses \
+                           ON issue_statuses.id = issues.status \
+                           WHERE milestones.id = ? \
+                           GROUP BY milestones.id";
+                let row =
+                    query(sql).bind(id).fetch_one(&mut **connection).await?;
+                let total: i64 = row.try_get("total")?;
+                let closed: i64 = row.try_get("closed")?;
+                Ok(MilestoneProgressResponse {
+                    milestone_id: id,
+                    open: total - closed,
+                    closed,
+                    total,
+                })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn assign_issue(
+    Path((id, issue_id)): Path<(i64, i64)>,
+    resources: Arc<Resources>,
+) -> ApiResponse<MilestoneAssignmentResponse, AssignIssueError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                query(
+                    "UPDATE issues SET milestone = ?, version = version + 1 \
+                     WHERE id = ? RETURNING id",
+                )
+                .bind(id)
+                .bind(issue_id)
+                .fetch_one(&mut **connection)
+                .await?;
+                Ok(MilestoneAssignmentResponse {
+                    milestone_id: Some(id),
+                    issue_id,
+                })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn unassign_issue(
+    Path((id, issue_id)): Path<(i64, i64)>,
+    resources: Arc<Resources>,
+) -> ApiResponse<MilestoneAssignmentResponse, AssignIssueError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let sql = "UPDATE issues \
+                           SET milestone = NULL, version = version + 1 \
+                           WHERE id = ? AND milestone = ? RETURNING id";
+                query(sql)
+                    .bind(issue_id)
+                    .bind(id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(MilestoneAssignmentResponse { milestone_id: None, issue_id })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_list(
+    resources: Arc<Resources>,
+) -> ApiResponse<MilestoneListResponse, GetMilestoneError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let mut milestones = Vec::new();
+                let mut stream = query(
+                    "SELECT id, name, description, due_date FROM milestones \
+                     ORDER BY due_date IS NULL, due_date, id",
+                )
+                .fetch(&mut **connection);
+                while let Some(row) = stream.try_next().await? {
+                    milestones.push(MilestoneResponse::from_row(&row)?);
+                }
+                Ok(MilestoneListResponse { list: milestones })
+            })
+        })
+        .await
+        .into()
+}
@@ -0,0 +1,225 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ipnet::IpNet;
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{
+    response::{ApiResponse, NoData},
+    Resources,
+};
+
+#[derive(Debug, Error)]
+pub enum NetworkAclError {
+    #[error("Invalid network {0:?}, expected an address or CIDR block")]
+    InvalidNetwork(String),
+}
+
+/// Marks requests accepted by the listener reserved for admin routes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdminListener;
+
+/// Networks allowed or denied access, by the address of the peer. Empty
+/// allow lists put no restriction.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkAcl {
+    deny: Vec<IpNet>,
+    admin: Vec<IpNet>,
+    auth: Vec<IpNet>,
+    admin_listener_only: bool,
+}
+
+impl NetworkAcl {
+    /// With `admin_listener_only`, admin routes are only served to requests
+    /// marked with `AdminListener`.
+    pub fn new(
+        deny: &[String],
+        admin: &[String],
+        auth: &[String],
+        admin_listener_only: bool,
+    ) -> Result<Self, NetworkAclError> {
+        Ok(Self {
+            deny: parse_networks(deny)?,
+            admin: parse_networks(admin)?,
+            auth: parse_networks(auth)?,
+            admin_listener_only,
+        })
+    }
+
+    fn check_denied(&self, peer: Option<IpAddr>) -> Result<(), NetworkError> {
+        match peer {
+            Some(peer) if contains(&self.deny, peer) => {
+                Err(NetworkError::Denied)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Peers of unknown address are only let through unrestricted paths.
+    fn check_path(
+        &self,
+        path: &str,
+        peer: Option<IpAddr>,
+        admin_listener: bool,
+    ) -> Result<(), NetworkError> {
+        let allowed = if path.starts_with("/admin/") {
+            if self.admin_listener_only && !admin_listener {
+                return Err(NetworkError::NotAdminListener);
+            }
+            &self.admin
+        } else if path.starts_with("/auth/") {
+            &self.auth
+        } else {
+            return Ok(());
+        };
+        if allowed.is_empty()
+            || peer.is_some_and(|peer| contains(allowed, peer))
+        {
+            Ok(())
+        } else {
+            Err(NetworkError::Denied)
+        }
+    }
+}
+
+/// Accepts a bare address as a network of that single address.
+fn parse_networks(networks: &[String]) -> Result<Vec<IpNet>, NetworkAclError> {
+    networks
+        .iter()
+        .map(|network| {
+            network
+                .parse::<IpNet>()
+                .or_else(|_| network.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| NetworkAclError::InvalidNetwork(network.clone()))
+        })
+        .collect()
+}
+
+fn contains(networks: &[IpNet], peer: IpAddr) -> bool {
+    networks.iter().any(|network| network.contains(&peer))
+}
+
+/// IPv4 clients of a dual-stack listener show up as IPv4-mapped IPv6.
+fn peer(request: &Request) -> Option<IpAddr> {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_canonical())
+}
+
+#[derive(Debug, Error)]
+enum NetworkError {
+    #[error("Access from this network is not allowed")]
+    Denied,
+    #[error("Admin routes are only served on the admin listener")]
+    NotAdminListener,
+}
+
+impl ResponseStatusCode for NetworkError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Denied => StatusCode::FORBIDDEN,
+            Self::NotAdminListener => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+/// Turns away denied networks from every route, static files included.
+pub async fn reject_denied(
+    acl: Arc<NetworkAcl>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Err(error) = acl.check_denied(peer(&request)) {
+        return ApiResponse::<NoData, _>::new(Err(error)).into_response();
+    }
+    next.run(request).await
+}
+
+/// Keeps admin and auth routes to their allowed networks, batch
+/// sub-requests included.
+pub async fn restrict(request: Request, next: Next) -> Response {
+    let resources = request
+        .extensions()
+        .get::<Arc<Resources>>()
+        .expect("API resources must be installed as an extension");
+    let admin_listener = request.extensions().get::<AdminListener>().is_some();
+    let path = request.uri().path();
+    let checked =
+        resources.network.check_path(path, peer(&request), admin_listener);
+    if let Err(error) = checked {
+        return ApiResponse::<NoData, _>::new(Err(error)).into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::NetworkAcl;
+
+    fn ip(address: &str) -> Option<IpAddr> {
+        Some(address.parse().unwrap())
+    }
+
+    #[test]
+    fn restricted_paths_need_an_allowed_peer() {
+        let acl = NetworkAcl::new(
+            &[],
+            &[String::from("10.0.0.0/8"), String::from("::1")],
+            &[],
+            false,
+        )
+        .unwrap();
+        assert!(acl.check_path("/admin/users", ip("10.1.2.3"), false).is_ok());
+        assert!(acl.check_path("/admin/users", ip("::1"), false).is_ok());
+        assert!(acl
+            .check_path("/admin/users", ip("192.168.0.1"), false)
+            .is_err());
+        assert!(acl.check_path("/admin/users", None, false).is_err());
+        assert!(acl
+            .check_path("/auth/login", ip("192.168.0.1"), false)
+            .is_ok());
+        assert!(acl.check_path("/issue/list/", None, false).is_ok());
+    }
+
+    #[test]
+    fn denied_networks_match_known_peers() {
+        let acl =
+            NetworkAcl::new(&[String::from("203.0.113.0/24")], &[], &[], false)
+                .unwrap();
+        assert!(acl.check_denied(ip("203.0.113.9")).is_err());
+        assert!(acl.check_denied(ip("203.0.114.9")).is_ok());
+        assert!(acl.check_denied(None).is_ok());
+    }
+
+    #[test]
+    fn admin_routes_can_be_kept_to_admin_listener() {
+        let acl = NetworkAcl::new(&[], &[], &[], true).unwrap();
+        assert!(acl.check_path("/admin/users", ip("10.1.2.3"), true).is_ok());
+        assert!(acl.check_path("/admin/users", ip("10.1.2.3"), false).is_err());
+        assert!(acl.check_path("/auth/login", ip("10.1.2.3"), false).is_ok());
+    }
+
+    #[test]
+    fn invalid_networks_are_rejected() {
+        assert!(NetworkAcl::new(
+            &[String::from("10.0.0.0/33")],
+            &[],
+            &[],
+            false
+        )
+        .is_err());
+    }
+}
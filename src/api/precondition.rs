@@ -0,0 +1,69 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::IF_MATCH, request::Parts, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::response::{ApiResponse, NoData};
+
+#[derive(Debug, Error)]
+pub enum PreconditionError {
+    #[error("If-Match header with the current version is required")]
+    Missing,
+    #[error("If-Match header must hold a single version number")]
+    Malformed,
+}
+
+impl ResponseStatusCode for PreconditionError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Missing => StatusCode::PRECONDITION_REQUIRED,
+            Self::Malformed => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl IntoResponse for PreconditionError {
+    fn into_response(self) -> Response {
+        ApiResponse::<NoData, _>::new(Err(self)).into_response()
+    }
+}
+
+/// Strong ETag sent with versioned rows, which is what `IfMatch` expects
+/// to get back.
+pub fn version_tag(version: i64) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{version}\""))
+        .expect("a quoted number is a valid header value")
+}
+
+/// Row version the client last saw, taken from `If-Match`. Both the `"3"`
+/// sent as ETag and a bare `3` are accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IfMatch(pub i64);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = PreconditionError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let value =
+            parts.headers.get(IF_MATCH).ok_or(PreconditionError::Missing)?;
+        let value =
+            value.to_str().map_err(|_| PreconditionError::Malformed)?.trim();
+        let version = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value);
+        version.parse().map(Self).map_err(|_| PreconditionError::Malformed)
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    routing::{get, put},
+    Json,
+    Router,
+};
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use sqlx::query;
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{
+    auth::CurrentUser,
+    clock::Clock,
+    response::ApiResponse,
+    Resources,
+};
+
+/// How long a heartbeat keeps a user listed on an issue.
+const PRESENCE_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+struct PresenceEntry {
+    name: String,
+    typing: bool,
+    expires_at: Instant,
+}
+
+#[derive(Debug)]
+pub struct Presence {
+    clock: Arc<dyn Clock>,
+    entries: Mutex<HashMap<(i64, i64), PresenceEntry>>,
+}
+
+impl Presence {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self { clock, entries: Mutex::default() }
+    }
+
+    fn heartbeat(
+        &self,
+        issue_id: i64,
+        user_id: i64,
+        name: String,
+        typing: bool,
+    ) {
+        let mut entries =
+            self.entries.lock().unwrap_or_else(|error| error.into_inner());
+        let now = self.clock.now();
+        entries.retain(|_, entry| entry.expires_at > now);
+        let entry =
+            PresenceEntry { name, typing, expires_at: now + PRESENCE_TTL };
+        entries.insert((issue_id, user_id), entry);
+    }
+
+    fn viewers(&self, issue_id: i64) -> Vec<ViewerResponse> {
+        let mut entries =
+            self.entries.lock().unwrap_or_else(|error| error.into_inner());
+        let now = self.clock.now();
+        entries.retain(|_, entry| entry.expires_at > now);
+        let mut viewers: Vec<_> = entries
+            .iter()
+            .filter(|((issue, _), _)| *issue == issue_id)
+            .map(|((_, user_id), entry)| ViewerResponse {
+                user_id: *user_id,
+                name: entry.name.clone(),
+                typing: entry.typing,
+            })
+            .collect();
+        viewers.sort_by_key(|viewer| viewer.user_id);
+        viewers
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct HeartbeatPayload {
+    #[serde(default)]
+    typing: bool,
+}
+
+#[derive(Debug, Error)]
+enum PresenceError {
+    #[error("Issue not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for PresenceError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for PresenceError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct ViewerResponse {
+    user_id: i64,
+    name: String,
+    typing: bool,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct PresenceListResponse {
+    list: Vec<ViewerResponse>,
+}
+
+impl ResponseStatusCode for PresenceListResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("HeartbeatPayload", schema_for!(HeartbeatPayload)),
+        ("PresenceListResponse", schema_for!(PresenceListResponse)),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/issue/:id",
+            put({
+                let resources = resources.clone();
+                move |id, user, body| put_heartbeat(id, user, body, resources)
+            }),
+        )
+        .route(
+            "/issue/:id",
+            get({
+                let resources = resources.clone();
+                move |id, user| get_viewers(id, user, resources)
+            }),
+        )
+}
+
+async fn ensure_issue_exists(
+    resources: &Resources,
+    issue_id: i64,
+) -> Result<(), PresenceError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                query("SELECT 1 FROM issues WHERE id = ?")
+                    .bind(issue_id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(())
+            })
+        })
+        .await
+}
+
+async fn put_heartbeat(
+    Path(issue_id): Path<i64>,
+    CurrentUser(user): CurrentUser,
+    Json(payload): Json<HeartbeatPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<PresenceListResponse, PresenceError> {
+    if let Err(error) = ensure_issue_exists(&resources, issue_id).await {
+        return ApiResponse::new(Err(error));
+    }
+    resources.presence.heartbeat(issue_id, user.id, user.name, payload.typing);
+    let list = resources.presence.viewers(issue_id);
+    ApiResponse::new(Ok(PresenceListResponse { list }))
+}
+
+async fn get_viewers(
+    Path(issue_id): Path<i64>,
+    CurrentUser(_): CurrentUser,
+    resources: Arc<Resources>,
+) -> ApiResponse<PresenceListResponse, PresenceError> {
+    if let Err(error) = ensure_issue_exists(&resources, issue_id).await {
+        return ApiResponse::new(Err(error));
+    }
+    let list = resources.presence.viewers(issue_id);
+    ApiResponse::new(Ok(PresenceListResponse { list }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use super::{Presence, PRESENCE_TTL};
+    use crate::api::clock::MockClock;
+
+    fn setup() -> (Arc<MockClock>, Presence) {
+        let clock = Arc::new(MockClock::new());
+        (clock.clone(), Presence::new(clock))
+    }
+
+    fn viewer_ids(presence: &Presence, issue_id: i64) -> Vec<i64> {
+        presence.viewers(issue_id).iter().map(|viewer| viewer.user_id).collect()
+    }
+
+    #[test]
+    fn viewer_leaves_after_ttl() {
+        let (clock, presence) = setup();
+        presence.heartbeat(1, 10, String::from("ann"), false);
+        clock.advance(PRESENCE_TTL - Duration::from_secs(1));
+        assert_eq!(viewer_ids(&presence, 1), [10]);
+        clock.advance(Duration::from_secs(1));
+        assert!(viewer_ids(&presence, 1).is_empty());
+    }
+
+    #[test]
+    fn heartbeat_keeps_viewer_listed() {
+        let (clock, presence) = setup();
+        presence.heartbeat(1, 20, String::from("bob"), false);
+        presence.heartbeat(1, 10, String::from("ann"), false);
+        clock.advance(PRESENCE_TTL / 2);
+        presence.heartbeat(1, 10, String::from("ann"), true);
+        clock.advance(PRESENCE_TTL / 2);
+        let viewers = presence.viewers(1);
+        assert_eq!(viewers.len(), 1);
+        assert_eq!(viewers[0].user_id, 10);
+        assert!(viewers[0].typing);
+    }
+
+    #[test]
+    fn viewers_are_per_issue() {
+        let (_, presence) = setup();
+        presence.heartbeat(1, 10, String::from("ann"), false);
+        presence.heartbeat(2, 20, String::from("bob"), false);
+        assert_eq!(viewer_ids(&presence, 1), [10]);
+        assert_eq!(viewer_ids(&presence, 2), [20]);
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use axum::{
+    extract::Request,
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{
+    response::{ApiResponse, NoData},
+    Resources,
+};
+
+/// While enabled, the database is only read, so that its file can be copied
+/// consistently.
+#[derive(Debug, Default)]
+pub struct ReadOnly {
+    enabled: AtomicBool,
+}
+
+impl ReadOnly {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled: AtomicBool::new(enabled) }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Server is in read-only mode")]
+struct ReadOnlyError;
+
+impl ResponseStatusCode for ReadOnlyError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+pub async fn reject_writes(request: Request, next: Next) -> Response {
+    let is_read = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    let read_only = request
+        .extensions()
+        .get::<Arc<Resources>>()
+        .expect("API resources must be installed as an extension")
+        .read_only
+        .is_enabled();
+    if read_only && !is_read {
+        return ApiResponse::<NoData, _>::new(Err(ReadOnlyError))
+            .into_response();
+    }
+    next.run(request).await
+}
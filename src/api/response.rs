@@ -1,17 +1,34 @@
 use std::error::Error;
 
 use axum::{
-    http::StatusCode,
+    http::{header::ETAG, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde::{ser::SerializeStruct, Serialize, Serializer};
+use serde_json::Value;
 
-use crate::status::ResponseStatusCode;
+use crate::{request_id::RequestId, status::ResponseStatusCode};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+use super::{precondition, undo::UndoToken};
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize,
+)]
+pub enum NoData {}
+
+impl ResponseStatusCode for NoData {
+    fn status_code(&self) -> StatusCode {
+        match *self {}
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ApiResponse<T, E> {
     result: Result<T, E>,
+    undo: Option<UndoToken>,
+    row_version: Option<i64>,
+    error_detail: Option<(&'static str, Value)>,
 }
 
 impl<T, E> ApiResponse<T, E>
@@ -20,7 +37,29 @@ where
     E: Error + ResponseStatusCode,
 {
     pub fn new(result: Result<T, E>) -> Self {
-        Self { result }
+        Self { result, undo: None, row_version: None, error_detail: None }
+    }
+
+    /// Offers a way to take back a successful destructive call.
+    pub fn with_undo(self, undo: Option<UndoToken>) -> Self {
+        Self { undo, ..self }
+    }
+
+    /// Sends the version of the returned row as its ETag, so that clients
+    /// can echo it back in `If-Match`.
+    pub fn with_row_version(self, version: fn(&T) -> i64) -> Self {
+        let row_version = self.result.as_ref().ok().map(version);
+        Self { row_version, ..self }
+    }
+
+    /// Sends a field with structured details of the error next to its
+    /// messages, so that clients do not have to parse them.
+    pub fn with_error_detail(
+        self,
+        detail: fn(&E) -> Option<(&'static str, Value)>,
+    ) -> Self {
+        let error_detail = self.result.as_ref().err().and_then(detail);
+        Self { error_detail, ..self }
     }
 }
 
@@ -43,7 +82,14 @@ where
     E: Error + ResponseStatusCode,
 {
     fn into_response(self) -> Response {
-        (self.status_code(), Json(self)).into_response()
+        let row_version = self.row_version;
+        let mut response = (self.status_code(), Json(self)).into_response();
+        if let Some(version) = row_version {
+            response
+                .headers_mut()
+                .insert(ETAG, precondition::version_tag(version));
+        }
+        response
     }
 }
 
@@ -57,16 +103,26 @@ where
         S: Serializer,
     {
         let mut struct_serializer =
-            serializer.serialize_struct("ApiResponse", 2)?;
+            serializer.serialize_struct("ApiResponse", 4)?;
         struct_serializer
             .serialize_field("status", &self.status_code().as_u16())?;
         match &self.result {
             Ok(data) => {
                 struct_serializer.serialize_field("data", data)?;
+                if let Some(undo) = &self.undo {
+                    struct_serializer.serialize_field("undo", undo)?;
+                }
             },
             Err(errors) => {
                 struct_serializer
                     .serialize_field("errors", &ErrorChain::new(errors))?;
+                if let Some((name, detail)) = &self.error_detail {
+                    struct_serializer.serialize_field(name, detail)?;
+                }
+                if let Some(id) = RequestId::current() {
+                    struct_serializer
+                        .serialize_field("request_id", id.as_str())?;
+                }
             },
         }
         struct_serializer.end()
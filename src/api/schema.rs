@@ -0,0 +1,125 @@
+use axum::{
+    extract::Path,
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json,
+    Router,
+};
+use schemars::Schema;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{
+    admin,
+    auth,
+    batch,
+    build,
+    canned,
+    check,
+    edit_lock,
+    export,
+    form,
+    i18n,
+    issue,
+    issue_type,
+    label,
+    milestone,
+    presence,
+    response::{ApiResponse, NoData},
+    search,
+    signature,
+    status,
+    sync,
+    token,
+    triage,
+    undo,
+    version,
+};
+
+const MEDIA_TYPE: &str = "application/schema+json";
+
+#[derive(Debug, Error)]
+enum SchemaError {
+    #[error("Schema not found")]
+    NotFound,
+}
+
+impl ResponseStatusCode for SchemaError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SchemaListResponse {
+    list: Vec<&'static str>,
+}
+
+impl ResponseStatusCode for SchemaListResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+/// Payload schemas describe request bodies, response schemas describe the
+/// `data` member of the response envelope.
+fn registry() -> Vec<(&'static str, Schema)> {
+    [
+        admin::schemas(),
+        auth::schemas(),
+        batch::schemas(),
+        build::schemas(),
+        canned::schemas(),
+        check::schemas(),
+        edit_lock::schemas(),
+        export::schemas(),
+        form::schemas(),
+        i18n::schemas(),
+        issue::schemas(),
+        issue_type::schemas(),
+        label::schemas(),
+        milestone::schemas(),
+        presence::schemas(),
+        search::schemas(),
+        signature::schemas(),
+        status::schemas(),
+        sync::schemas(),
+        token::schemas(),
+        triage::schemas(),
+        undo::schemas(),
+        version::schemas(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+pub fn router() -> Router {
+    Router::new().route("/", get(get_list)).route("/:name", get(get_by_name))
+}
+
+async fn get_list() -> ApiResponse<SchemaListResponse, SchemaError> {
+    let mut list: Vec<_> =
+        registry().into_iter().map(|(name, _)| name).collect();
+    list.sort_unstable();
+    ApiResponse::new(Ok(SchemaListResponse { list }))
+}
+
+async fn get_by_name(Path(name): Path<String>) -> Response {
+    let schema = registry()
+        .into_iter()
+        .find(|(candidate, _)| *candidate == name)
+        .map(|(_, schema)| schema);
+    match schema {
+        Some(schema) => {
+            ([(CONTENT_TYPE, MEDIA_TYPE)], Json(schema)).into_response()
+        },
+        None => ApiResponse::<NoData, _>::new(Err(SchemaError::NotFound))
+            .into_response(),
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use axum::{extract::Query, http::StatusCode};
+use futures::TryStreamExt;
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, Row};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{issue::IssueResponse, response::ApiResponse, Resources};
+
+const DEFAULT_LIMIT: i64 = 20;
+
+const MAX_LIMIT: i64 = 100;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Error)]
+pub enum SearchError {
+    #[error("Search query must not be empty")]
+    EmptyQuery,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+impl ResponseStatusCode for SearchError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::EmptyQuery => StatusCode::BAD_REQUEST,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct SearchHit {
+    #[serde(flatten)]
+    issue: IssueResponse,
+    rank: f64,
+    snippet: String,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SearchResponse {
+    list: Vec<SearchHit>,
+}
+
+impl ResponseStatusCode for SearchResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+/// Quotes every term so that user input is never parsed as FTS5 query
+/// syntax; the terms are then matched together.
+fn match_expression(text: &str) -> String {
+    text.split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![("SearchResponse", schema_for!(SearchResponse))]
+}
+
+pub async fn get_search(
+    Query(search): Query<SearchQuery>,
+    resources: Arc<Resources>,
+) -> ApiResponse<SearchResponse, SearchError> {
+    let expression = match_expression(&search.q);
+    if expression.is_empty() {
+        return ApiResponse::new(Err(SearchError::EmptyQuery));
+    }
+    let limit = search.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let mut hits = Vec::new();
+                let sql = "SELECT issues.id, issues.title, \
+                           issues.description, issues.status, \
+                           issues.milestone, issues.affects_version, \
+                           issues.fixed_in_version, issues.assignee, \
+                           issues.type, issues.created_at, \
+                           issues.updated_at, issues.version, \
+                           bm25(issues_fts) AS rank, \
+                           snippet(issues_fts, -1, '[', ']', '...', 12) \
+                           AS snippet \
+                           FROM issues_fts \
+                           JOIN issues ON issues.id = issues_fts.rowid \
+                           WHERE issues_fts MATCH ? \
+                           ORDER BY rank LIMIT ?";
+                let mut stream = query(sql)
+                    .bind(&expression)
+                    .bind(limit)
+                    .fetch(&mut **connection);
+                while let Some(row) = stream.try_next().await? {
+                    hits.push(SearchHit {
+                        issue: IssueResponse::from_row(&row)?,
+                        rank: row.try_get("rank")?,
+                        snippet: row.try_get("snippet")?,
+                    });
+                }
+                Ok(SearchResponse { list: hits })
+            })
+        })
+        .await
+        .into()
+}
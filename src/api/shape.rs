@@ -0,0 +1,254 @@
+use axum::{
+    body::{self, Body, HttpBody},
+    extract::{rejection::QueryRejection, Query, Request},
+    http::{
+        header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderMap,
+        HeaderValue,
+        Method,
+        StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::response::{ApiResponse, NoData};
+
+/// Largest body reshaped. Bigger or streamed bodies are sent as the handler
+/// encoded them rather than held in memory.
+const MAX_SHAPED_BODY: u64 = 1 << 20;
+
+const CASING_HEADER: &str = "x-response-casing";
+
+const ENVELOPE_HEADER: &str = "x-response-envelope";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Casing {
+    #[default]
+    Snake,
+    Camel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Encoding {
+    #[default]
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl Encoding {
+    /// Picks the first binary media type listed in `Accept`, leaving
+    /// anything else to the default JSON encoding.
+    fn from_headers(headers: &HeaderMap) -> Self {
+        headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|media_type| media_type.split(';').next().unwrap_or(""))
+            .find_map(|media_type| match media_type.trim() {
+                "application/msgpack" | "application/x-msgpack" => {
+                    Some(Self::MessagePack)
+                },
+                "application/cbor" => Some(Self::Cbor),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::MessagePack => "application/msgpack",
+            Self::Cbor => "application/cbor",
+        }
+    }
+
+    fn encode(self, value: &Value) -> Option<Vec<u8>> {
+        match self {
+            Self::Json => serde_json::to_vec(value).ok(),
+            Self::MessagePack => rmp_serde::to_vec_named(value).ok(),
+            Self::Cbor => {
+                let mut buffer = Vec::new();
+                ciborium::into_writer(value, &mut buffer).ok()?;
+                Some(buffer)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct FieldsQuery {
+    #[serde(default)]
+    fields: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct Shape {
+    casing: Casing,
+    bare: bool,
+    encoding: Encoding,
+    fields: Option<Vec<String>>,
+}
+
+impl Shape {
+    fn from_request(request: &Request) -> Result<Self, ShapeError> {
+        let headers = request.headers();
+        let casing = match headers.get(CASING_HEADER).map(|v| v.to_str()) {
+            None => Casing::Snake,
+            Some(Ok("snake_case")) => Casing::Snake,
+            Some(Ok("camelCase")) => Casing::Camel,
+            Some(_) => return Err(ShapeError::InvalidCasing),
+        };
+        let bare = match headers.get(ENVELOPE_HEADER).map(|v| v.to_str()) {
+            None | Some(Ok("full")) => false,
+            Some(Ok("bare")) => true,
+            Some(_) => return Err(ShapeError::InvalidEnvelope),
+        };
+        let encoding = Encoding::from_headers(headers);
+        let mut fields = None;
+        if request.method() == Method::GET {
+            let Query(query) =
+                Query::<FieldsQuery>::try_from_uri(request.uri())
+                    .map_err(ShapeError::Query)?;
+            fields = query.fields.map(|fields| {
+                fields
+                    .split(',')
+                    .filter(|field| !field.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            });
+        }
+        Ok(Self { casing, bare, encoding, fields })
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Error)]
+enum ShapeError {
+    #[error("Response casing must be snake_case or camelCase")]
+    InvalidCasing,
+    #[error("Response envelope must be full or bare")]
+    InvalidEnvelope,
+    #[error("Failed to parse fields parameter")]
+    Query(#[source] QueryRejection),
+}
+
+impl ResponseStatusCode for ShapeError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+pub async fn shape_response(request: Request, next: Next) -> Response {
+    let shape = match Shape::from_request(&request) {
+        Ok(shape) => shape,
+        Err(error) => {
+            return ApiResponse::<NoData, _>::new(Err(error)).into_response();
+        },
+    };
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes() == b"application/json");
+    let size = response.body().size_hint().exact();
+    if shape.is_default()
+        || !is_json
+        || size.is_none_or(|size| size > MAX_SHAPED_BODY)
+    {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = body::to_bytes(body, MAX_SHAPED_BODY as usize).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let Ok(mut value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    if shape.casing == Casing::Camel {
+        value = camel_case_keys(value);
+    }
+    if parts.status.is_success() {
+        if let Some(data) = value.get_mut("data") {
+            if let Some(fields) = &shape.fields {
+                project(data, fields);
+            }
+            if shape.bare {
+                value = data.take();
+            }
+        }
+    }
+    let Some(encoded) = shape.encoding.encode(&value) else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(shape.encoding.content_type()),
+    );
+    Response::from_parts(parts, Body::from(encoded))
+}
+
+/// Keeps only the requested fields of a single resource, or of each element
+/// of a `list` response.
+fn project(data: &mut Value, fields: &[String]) {
+    let retain = |value: &mut Value| {
+        if let Value::Object(object) = value {
+            object.retain(|key, _| fields.iter().any(|field| field == key));
+        }
+    };
+    match data.get_mut("list") {
+        Some(Value::Array(list)) => list.iter_mut().for_each(retain),
+        _ => retain(data),
+    }
+}
+
+fn camel_case_keys(value: Value) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| (camel_case(key), camel_case_keys(value)))
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(array) => {
+            Value::Array(array.into_iter().map(camel_case_keys).collect())
+        },
+        other => other,
+    }
+}
+
+/// Only plain snake_case identifiers are converted, so that data keys such
+/// as translation catalog entries pass through untouched.
+fn camel_case(key: String) -> String {
+    let is_identifier = key.starts_with(|ch: char| ch.is_ascii_lowercase())
+        && key.chars().all(|ch| {
+            ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '_'
+        });
+    if !is_identifier || !key.contains('_') {
+        return key;
+    }
+    let mut output = String::with_capacity(key.len());
+    let mut upper_next = false;
+    for ch in key.chars() {
+        if ch == '_' {
+            upper_next = true;
+        } else if upper_next {
+            output.push(ch.to_ascii_uppercase());
+            upper_next = false;
+        } else {
+            output.push(ch);
+        }
+    }
+    output
+}
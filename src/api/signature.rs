@@ -0,0 +1,568 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{
+    body::{self, Body},
+    extract::{OriginalUri, Path, Request},
+    http::{
+        header::{AUTHORIZATION, DATE},
+        HeaderMap,
+        StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json,
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::TryStreamExt;
+use hmac::{Hmac, Mac};
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{query, sqlite::SqliteRow, Row, SqliteConnection};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{
+    auth::{self, CurrentUser, UserResponse},
+    clock::Clock,
+    response::{ApiResponse, NoData},
+    token::generate_token,
+    Resources,
+};
+
+const SCHEME: &str = "HMAC-SHA256 ";
+
+const DIGEST_HEADER: &str = "digest";
+
+const DIGEST_PREFIX: &str = "SHA-256=";
+
+const NONCE_HEADER: &str = "x-signature-nonce";
+
+const KEY_ID_BYTES: usize = 8;
+
+/// Largest difference accepted between the `Date` of a signed request and
+/// the server clock, either way.
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(300);
+
+/// Signatures remembered at once by `ReplayGuard`. Signed requests are
+/// turned away while it is full rather than forgetting signatures early.
+const MAX_REMEMBERED_SIGNATURES: usize = 100_000;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct NewSigningKeyPayload {
+    name: String,
+}
+
+#[derive(Debug, Error)]
+enum SigningKeyError {
+    #[error("Signing key not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for SigningKeyError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for SigningKeyError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum SignatureError {
+    #[error("Authorization must be HMAC-SHA256 <key id>:<base64 signature>")]
+    Malformed,
+    #[error("Signed requests must carry a valid Date header")]
+    MissingDate,
+    #[error("Date of signed request is too far from the server clock")]
+    Skewed,
+    #[error(
+        "Signed requests must carry a Digest header of SHA-256={{base64}}"
+    )]
+    MissingDigest,
+    #[error("Digest does not match the request body")]
+    DigestMismatch,
+    #[error("Invalid request signature")]
+    InvalidSignature,
+    #[error("Request signature was already used")]
+    Replayed,
+    #[error("Too many signed requests, try again later")]
+    TooManySignatures,
+    #[error("Failed to read request body")]
+    Body,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for SignatureError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::InvalidSignature;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for SignatureError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Malformed => StatusCode::UNAUTHORIZED,
+            Self::MissingDate => StatusCode::UNAUTHORIZED,
+            Self::Skewed => StatusCode::UNAUTHORIZED,
+            Self::MissingDigest => StatusCode::UNAUTHORIZED,
+            Self::DigestMismatch => StatusCode::UNAUTHORIZED,
+            Self::InvalidSignature => StatusCode::UNAUTHORIZED,
+            Self::Replayed => StatusCode::UNAUTHORIZED,
+            Self::TooManySignatures => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Body => StatusCode::BAD_REQUEST,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct SigningKeyResponse {
+    id: i64,
+    name: String,
+    key_id: String,
+    created_at: i64,
+    last_used_at: Option<i64>,
+}
+
+impl SigningKeyResponse {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            key_id: row.try_get("key_id")?,
+            created_at: row.try_get("created_at")?,
+            last_used_at: row.try_get("last_used_at")?,
+        })
+    }
+}
+
+impl ResponseStatusCode for SigningKeyResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct NewSigningKeyResponse {
+    #[serde(flatten)]
+    info: SigningKeyResponse,
+    secret: String,
+}
+
+impl ResponseStatusCode for NewSigningKeyResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct SigningKeyListResponse {
+    list: Vec<SigningKeyResponse>,
+}
+
+impl ResponseStatusCode for SigningKeyListResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("NewSigningKeyPayload", schema_for!(NewSigningKeyPayload)),
+        ("SigningKeyResponse", schema_for!(SigningKeyResponse)),
+        ("NewSigningKeyResponse", schema_for!(NewSigningKeyResponse)),
+        ("SigningKeyListResponse", schema_for!(SigningKeyListResponse)),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/new",
+            post({
+                let resources = resources.clone();
+                move |user, body| post_new(user, body, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            delete({
+                let resources = resources.clone();
+                move |user, id| delete_by_id(user, id, resources)
+            }),
+        )
+        .route(
+            "/list/",
+            get({
+                let resources = resources.clone();
+                move |user| get_list(user, resources)
+            }),
+        )
+}
+
+/// Signatures seen within the accepted clock skew, so that a captured
+/// request cannot be sent again.
+#[derive(Debug)]
+pub struct ReplayGuard {
+    clock: Arc<dyn Clock>,
+    seen: Mutex<HashMap<Vec<u8>, Instant>>,
+}
+
+impl ReplayGuard {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self { clock, seen: Mutex::default() }
+    }
+
+    /// Remembers a signature seen for the first time. A request dated
+    /// anywhere within the skew is accepted on either side of it, so
+    /// signatures are remembered for twice as long.
+    fn first_use(&self, signature: &[u8]) -> Result<(), SignatureError> {
+        let mut seen =
+            self.seen.lock().unwrap_or_else(|error| error.into_inner());
+        let now = self.clock.now();
+        if seen.get(signature).is_some_and(|expires_at| *expires_at > now) {
+            return Err(SignatureError::Replayed);
+        }
+        // Expired signatures are only swept once they take up all the room.
+        if seen.len() >= MAX_REMEMBERED_SIGNATURES {
+            seen.retain(|_, expires_at| *expires_at > now);
+            if seen.len() >= MAX_REMEMBERED_SIGNATURES {
+                return Err(SignatureError::TooManySignatures);
+            }
+        }
+        seen.insert(signature.to_vec(), now + MAX_CLOCK_SKEW * 2);
+        Ok(())
+    }
+}
+
+struct SignatureCredentials {
+    key_id: String,
+    signature: Vec<u8>,
+}
+
+fn signature_credentials(
+    headers: &HeaderMap,
+) -> Option<Result<SignatureCredentials, SignatureError>> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let credentials = value.strip_prefix(SCHEME)?.trim();
+    let parsed = credentials.split_once(':').and_then(|(key_id, signature)| {
+        let signature = STANDARD.decode(signature).ok()?;
+        Some(SignatureCredentials { key_id: key_id.to_owned(), signature })
+    });
+    Some(parsed.ok_or(SignatureError::Malformed))
+}
+
+/// Text covered by the signature: method, path with query, `Date`,
+/// `Digest` and `X-Signature-Nonce`, one per line. The nonce may be empty,
+/// but identical requests signed within the same second need distinct
+/// nonces to get past the replay check.
+fn string_to_sign(lines: [&str; 5]) -> String {
+    lines.join("\n")
+}
+
+fn check_date(date: &str, now: SystemTime) -> Result<(), SignatureError> {
+    let date = httpdate::parse_http_date(date)
+        .map_err(|_| SignatureError::MissingDate)?;
+    let skew = now
+        .duration_since(date)
+        .or_else(|_| date.duration_since(now))
+        .map_err(|_| SignatureError::Skewed)?;
+    if skew > MAX_CLOCK_SKEW {
+        return Err(SignatureError::Skewed);
+    }
+    Ok(())
+}
+
+fn check_digest(digest: &str, body: &[u8]) -> Result<(), SignatureError> {
+    let expected = digest
+        .strip_prefix(DIGEST_PREFIX)
+        .and_then(|digest| STANDARD.decode(digest).ok())
+        .ok_or(SignatureError::MissingDigest)?;
+    if expected != Sha256::digest(body).as_slice() {
+        return Err(SignatureError::DigestMismatch);
+    }
+    Ok(())
+}
+
+fn check_signature(
+    secret: &str,
+    message: &str,
+    signature: &[u8],
+) -> Result<(), SignatureError> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    mac.verify_slice(signature).map_err(|_| SignatureError::InvalidSignature)
+}
+
+/// Authenticates requests signed with an HMAC key, as an alternative to
+/// bearer tokens for server-to-server clients. Requests carrying other
+/// credentials are let through untouched.
+pub async fn signature_auth(request: Request, next: Next) -> Response {
+    let Some(credentials) = signature_credentials(request.headers()) else {
+        return next.run(request).await;
+    };
+    let resources = request
+        .extensions()
+        .get::<Arc<Resources>>()
+        .cloned()
+        .expect("API resources must be installed as an extension");
+    let (mut parts, body) = request.into_parts();
+    // Size was already limited before reaching the API.
+    let Ok(bytes) = body::to_bytes(body, usize::MAX).await else {
+        let error = SignatureError::Body;
+        return ApiResponse::<NoData, _>::new(Err(error)).into_response();
+    };
+    let uri = match parts.extensions.get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.clone(),
+        None => parts.uri.clone(),
+    };
+    let path_and_query =
+        uri.path_and_query().map_or(uri.path(), |path| path.as_str());
+    let header =
+        |name| parts.headers.get(name).and_then(|value| value.to_str().ok());
+    let date = header(DATE.as_str()).unwrap_or("");
+    let digest = header(DIGEST_HEADER).unwrap_or("");
+    let nonce = header(NONCE_HEADER).unwrap_or("");
+    let message = string_to_sign([
+        parts.method.as_str(),
+        path_and_query,
+        date,
+        digest,
+        nonce,
+    ]);
+    let checked = credentials.and_then(|credentials| {
+        check_date(date, resources.clock.system_time())?;
+        check_digest(digest, &bytes)?;
+        Ok(credentials)
+    });
+    let result = match checked {
+        Ok(credentials) => {
+            let read_only = resources.read_only.is_enabled();
+            resources
+                .with_bare_conn(move |connection| {
+                    Box::pin(async move {
+                        user_for_signature(
+                            connection,
+                            &credentials,
+                            &message,
+                            !read_only,
+                        )
+                        .await
+                        .map(|user| (user, credentials.signature))
+                    })
+                })
+                .await
+        },
+        Err(error) => Err(error),
+    };
+    let result = result.and_then(|(user, signature)| {
+        resources.replays.first_use(&signature)?;
+        Ok(user)
+    });
+    match result {
+        Ok(user) => {
+            parts.extensions.insert(CurrentUser(user));
+            next.run(Request::from_parts(parts, Body::from(bytes))).await
+        },
+        Err(error) => ApiResponse::<NoData, _>::new(Err(error)).into_response(),
+    }
+}
+
+async fn user_for_signature(
+    connection: &mut SqliteConnection,
+    credentials: &SignatureCredentials,
+    message: &str,
+    touch: bool,
+) -> Result<UserResponse, SignatureError> {
+    let row =
+        query("SELECT id, user, secret FROM signing_keys WHERE key_id = ?")
+            .bind(&credentials.key_id)
+            .fetch_one(&mut *connection)
+            .await?;
+    let secret: String = row.try_get("secret")?;
+    check_signature(&secret, message, &credentials.signature)?;
+    // Usage is not recorded in read-only mode.
+    if touch {
+        query(
+            "UPDATE signing_keys SET last_used_at = unixepoch() WHERE id = ?",
+        )
+        .bind(row.try_get::<i64, _>("id")?)
+        .execute(&mut *connection)
+        .await?;
+    }
+    let user_id = row.try_get("user")?;
+    Ok(auth::user_by_id(connection, user_id).await?)
+}
+
+async fn post_new(
+    CurrentUser(user): CurrentUser,
+    Json(new_key): Json<NewSigningKeyPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<NewSigningKeyResponse, SigningKeyError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let mut key_id = [0; KEY_ID_BYTES];
+                OsRng.fill_bytes(&mut key_id);
+                let key_id: String =
+                    key_id.iter().map(|byte| format!("{byte:02x}")).collect();
+                let secret = generate_token();
+                let sql = "INSERT INTO signing_keys \
+                           (user, name, key_id, secret) \
+                           VALUES (?, ?, ?, ?) \
+                           RETURNING id, name, key_id, created_at, \
+                           last_used_at";
+                let row = query(sql)
+                    .bind(user.id)
+                    .bind(&new_key.name)
+                    .bind(&key_id)
+                    .bind(&secret)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                let info = SigningKeyResponse::from_row(&row)?;
+                Ok(NewSigningKeyResponse { info, secret })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn delete_by_id(
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<SigningKeyResponse, SigningKeyError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let sql = "DELETE FROM signing_keys WHERE id = ? AND user = ? \
+                           RETURNING id, name, key_id, created_at, \
+                           last_used_at";
+                let row = query(sql)
+                    .bind(id)
+                    .bind(user.id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(SigningKeyResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_list(
+    CurrentUser(user): CurrentUser,
+    resources: Arc<Resources>,
+) -> ApiResponse<SigningKeyListResponse, SigningKeyError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let mut list = Vec::new();
+                let sql = "SELECT id, name, key_id, created_at, last_used_at \
+                           FROM signing_keys WHERE user = ? ORDER BY id";
+                let mut stream =
+                    query(sql).bind(user.id).fetch(&mut **connection);
+                while let Some(row) = stream.try_next().await? {
+                    list.push(SigningKeyResponse::from_row(&row)?);
+                }
+                Ok(SigningKeyListResponse { list })
+            })
+        })
+        .await
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+
+    use super::{
+        check_date,
+        check_digest,
+        ReplayGuard,
+        SignatureError,
+        MAX_CLOCK_SKEW,
+        MAX_REMEMBERED_SIGNATURES,
+    };
+    use crate::api::clock::MockClock;
+
+    #[test]
+    fn date_must_be_within_skew() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let date = httpdate::fmt_http_date(now + MAX_CLOCK_SKEW);
+        assert!(check_date(&date, now).is_ok());
+        let date = httpdate::fmt_http_date(now - MAX_CLOCK_SKEW * 2);
+        assert!(matches!(check_date(&date, now), Err(SignatureError::Skewed)));
+        assert!(matches!(
+            check_date("yesterday", now),
+            Err(SignatureError::MissingDate)
+        ));
+    }
+
+    #[test]
+    fn digest_must_match_body() {
+        // SHA-256 of "{}".
+        let digest = "SHA-256=RBNvo1WzZ4oRRq0W9+hknpT7T8If536DEMBg9hyq/4o=";
+        assert!(check_digest(digest, b"{}").is_ok());
+        assert!(matches!(
+            check_digest(digest, b"[]"),
+            Err(SignatureError::DigestMismatch)
+        ));
+    }
+
+    #[test]
+    fn signature_is_accepted_once_until_forgotten() {
+        let clock = Arc::new(MockClock::new());
+        let guard = ReplayGuard::new(clock.clone());
+        assert!(guard.first_use(b"signature").is_ok());
+        assert!(matches!(
+            guard.first_use(b"signature"),
+            Err(SignatureError::Replayed)
+        ));
+        clock.advance(MAX_CLOCK_SKEW * 2);
+        assert!(guard.first_use(b"signature").is_ok());
+    }
+
+    #[test]
+    fn signatures_are_refused_while_full() {
+        let clock = Arc::new(MockClock::new());
+        let guard = ReplayGuard::new(clock.clone());
+        for index in 0..MAX_REMEMBERED_SIGNATURES {
+            assert!(guard.first_use(&index.to_le_bytes()).is_ok());
+        }
+        assert!(matches!(
+            guard.first_use(b"signature"),
+            Err(SignatureError::TooManySignatures)
+        ));
+        clock.advance(MAX_CLOCK_SKEW * 2);
+        assert!(guard.first_use(b"signature").is_ok());
+    }
+}
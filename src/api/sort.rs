@@ -0,0 +1,123 @@
+use std::marker::PhantomData;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::response::{ApiResponse, NoData};
+
+pub trait Sortable {
+    /// Pairs of accepted `sort` values and the columns they order by.
+    const COLUMNS: &'static [(&'static str, &'static str)];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn as_sql(self) -> &'static str {
+        match self {
+            Self::Asc => "ASC",
+            Self::Desc => "DESC",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RawSortParams {
+    #[serde(default)]
+    sort: Option<String>,
+    #[serde(default)]
+    order: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum SortError {
+    #[error("Cannot sort by {0:?}")]
+    UnknownColumn(String),
+    #[error("Sort order must be asc or desc, found {0:?}")]
+    InvalidOrder(String),
+    #[error("Failed to parse sort parameters")]
+    Query(#[source] axum::extract::rejection::QueryRejection),
+}
+
+impl ResponseStatusCode for SortError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+impl IntoResponse for SortError {
+    fn into_response(self) -> Response {
+        ApiResponse::<NoData, _>::new(Err(self)).into_response()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SortParams<T> {
+    column: &'static str,
+    order: SortOrder,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> SortParams<T> {
+    pub fn is_default(&self) -> bool {
+        self.column == "id" && self.order == SortOrder::Asc
+    }
+
+    /// Renders an `ORDER BY` clause body, falling back to ordering by id so
+    /// that ties keep a stable order.
+    pub fn order_by(&self) -> String {
+        let order = self.order.as_sql();
+        if self.column == "id" {
+            format!("id {order}")
+        } else {
+            format!("{} {order}, id {order}", self.column)
+        }
+    }
+}
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for SortParams<T>
+where
+    S: Send + Sync,
+    T: Sortable,
+{
+    type Rejection = SortError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let Query(raw) =
+            Query::<RawSortParams>::from_request_parts(parts, state)
+                .await
+                .map_err(SortError::Query)?;
+        let column = match raw.sort {
+            Some(sort) => T::COLUMNS
+                .iter()
+                .find(|(name, _)| *name == sort)
+                .map(|(_, column)| *column)
+                .ok_or(SortError::UnknownColumn(sort))?,
+            None => "id",
+        };
+        let order = match raw.order.as_deref() {
+            None | Some("asc") => SortOrder::Asc,
+            Some("desc") => SortOrder::Desc,
+            Some(other) => {
+                return Err(SortError::InvalidOrder(other.to_owned()));
+            },
+        };
+        Ok(Self { column, order, _marker: PhantomData })
+    }
+}
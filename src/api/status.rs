@@ -1,37 +1,109 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::Path,
+    extract::{Path, Query},
     http::StatusCode,
     routing::{delete, get, patch, post},
     Json,
     Router,
 };
 use futures::TryStreamExt;
+use schemars::{schema_for, JsonSchema, Schema};
 use serde::{Deserialize, Serialize};
-use sqlx::{query, Row};
+use serde_json::{json, Value};
+use sqlx::{query, query_scalar, sqlite::SqliteRow, Row, SqliteConnection};
 use thiserror::Error;
 
-use crate::status::ResponseStatusCode;
+use crate::{
+    domain::{DomainError, Name, NewStatus, StatusPatch},
+    status::ResponseStatusCode,
+};
 
-use super::{response::ApiResponse, Resources};
+use super::{
+    is_foreign_key_violation,
+    precondition::IfMatch,
+    response::ApiResponse,
+    sort::{SortParams, Sortable},
+    Resources,
+};
 
 const NAME_UNIQUE_CONSTRAINT: &str = "un_issue_statuses_name";
-const ISSUES_STATUS_FK: &str = "fk_issues_status";
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 struct NewStatusPayload {
     name: String,
+    #[serde(default)]
+    closed: bool,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 struct PatchStatusPayload {
     #[serde(default)]
     name: Option<String>,
+    #[serde(default)]
+    closed: Option<bool>,
+}
+
+impl TryFrom<&NewStatusPayload> for NewStatus {
+    type Error = DomainError;
+
+    fn try_from(payload: &NewStatusPayload) -> Result<Self, Self::Error> {
+        Ok(Self { name: Name::parse(&payload.name)?, closed: payload.closed })
+    }
+}
+
+impl TryFrom<&PatchStatusPayload> for StatusPatch {
+    type Error = DomainError;
+
+    fn try_from(payload: &PatchStatusPayload) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: payload.name.as_deref().map(Name::parse).transpose()?,
+            closed: payload.closed,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct BulkPatchOperation {
+    id: i64,
+    version: i64,
+    #[serde(flatten)]
+    payload: PatchStatusPayload,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct BulkDeleteOperation {
+    id: i64,
+    version: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum BulkOperation {
+    Create(NewStatusPayload),
+    Patch(BulkPatchOperation),
+    Delete(BulkDeleteOperation),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct StatusListQuery {
+    #[serde(default)]
+    name_contains: Option<String>,
+    #[serde(default)]
+    closed: Option<bool>,
+}
+
+struct StatusSort;
+
+impl Sortable for StatusSort {
+    const COLUMNS: &'static [(&'static str, &'static str)] =
+        &[("id", "id"), ("name", "name"), ("closed", "closed")];
 }
 
 #[derive(Debug, Error)]
 enum NewStatusError {
+    #[error(transparent)]
+    Invalid(#[from] DomainError),
     #[error("Status with the given name already exists")]
     AlreadyExists,
     #[error("Failed to manipulate database resources")]
@@ -54,6 +126,7 @@ impl From<sqlx::Error> for NewStatusError {
 impl ResponseStatusCode for NewStatusError {
     fn status_code(&self) -> StatusCode {
         match self {
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
             Self::AlreadyExists => StatusCode::FORBIDDEN,
             Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -92,6 +165,8 @@ enum DeleteStatusError {
     NotFound,
     #[error("Status cannot be deleted because it is in use")]
     InUse,
+    #[error("Status was modified since the given version")]
+    Stale,
     #[error("Failed to manipulate database resources")]
     Sqlx(#[source] sqlx::Error),
 }
@@ -99,9 +174,7 @@ enum DeleteStatusError {
 impl From<sqlx::Error> for DeleteStatusError {
     fn from(error: sqlx::Error) -> Self {
         if let sqlx::Error::Database(error) = &error {
-            if error.is_foreign_key_violation()
-                && error.constraint() == Some(ISSUES_STATUS_FK)
-            {
+            if is_foreign_key_violation(&**error) {
                 return Self::InUse;
             }
         }
@@ -117,6 +190,7 @@ impl ResponseStatusCode for DeleteStatusError {
         match self {
             Self::NotFound => StatusCode::NOT_FOUND,
             Self::InUse => StatusCode::FORBIDDEN,
+            Self::Stale => StatusCode::PRECONDITION_FAILED,
             Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -124,12 +198,18 @@ impl ResponseStatusCode for DeleteStatusError {
 
 #[derive(Debug, Error)]
 enum PatchStatusError {
+    #[error(transparent)]
+    Invalid(#[from] DomainError),
     #[error("At least one field must be patched, none were")]
     NoFieldsPatched,
     #[error("Status with the given name already exists")]
     AlreadyExists,
     #[error("Status not found")]
     NotFound,
+    #[error("Status was modified since the given version")]
+    Stale,
+    #[error("Status cannot be closed while it holds blocked issues")]
+    HoldsBlocked(Vec<i64>),
     #[error("Failed to manipulate database resources")]
     Sqlx(#[source] sqlx::Error),
 }
@@ -153,18 +233,100 @@ impl From<sqlx::Error> for PatchStatusError {
 impl ResponseStatusCode for PatchStatusError {
     fn status_code(&self) -> StatusCode {
         match self {
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
             Self::NoFieldsPatched => StatusCode::BAD_REQUEST,
             Self::NotFound => StatusCode::NOT_FOUND,
             Self::AlreadyExists => StatusCode::FORBIDDEN,
+            Self::Stale => StatusCode::PRECONDITION_FAILED,
+            Self::HoldsBlocked(_) => StatusCode::CONFLICT,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl PatchStatusError {
+    fn detail(&self) -> Option<(&'static str, Value)> {
+        match self {
+            Self::HoldsBlocked(blocked) => Some(("blocked", json!(blocked))),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum BulkOperationError {
+    #[error(transparent)]
+    Create(NewStatusError),
+    #[error(transparent)]
+    Patch(PatchStatusError),
+    #[error(transparent)]
+    Delete(DeleteStatusError),
+}
+
+impl ResponseStatusCode for BulkOperationError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Create(error) => error.status_code(),
+            Self::Patch(error) => error.status_code(),
+            Self::Delete(error) => error.status_code(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum BulkStatusError {
+    #[error("Bulk operation {index} failed, no operation was applied")]
+    Operation {
+        index: usize,
+        #[source]
+        source: BulkOperationError,
+    },
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+impl ResponseStatusCode for BulkStatusError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Operation { source, .. } => source.status_code(),
             Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl BulkStatusError {
+    fn detail(&self) -> Option<(&'static str, Value)> {
+        match self {
+            Self::Operation {
+                source: BulkOperationError::Patch(error),
+                ..
+            } => error.detail(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 struct StatusResponse {
     id: i64,
     name: String,
+    closed: bool,
+    version: i64,
+}
+
+impl StatusResponse {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            closed: row.try_get("closed")?,
+            version: row.try_get("version")?,
+        })
+    }
 }
 
 impl ResponseStatusCode for StatusResponse {
@@ -173,7 +335,7 @@ impl ResponseStatusCode for StatusResponse {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 struct StatusListResponse {
     list: Vec<StatusResponse>,
 }
@@ -184,6 +346,16 @@ impl ResponseStatusCode for StatusListResponse {
     }
 }
 
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("NewStatusPayload", schema_for!(NewStatusPayload)),
+        ("PatchStatusPayload", schema_for!(PatchStatusPayload)),
+        ("BulkStatusPayload", schema_for!(Vec<BulkOperation>)),
+        ("StatusResponse", schema_for!(StatusResponse)),
+        ("StatusListResponse", schema_for!(StatusListResponse)),
+    ]
+}
+
 pub fn router(resources: Arc<Resources>) -> Router {
     Router::new()
         .route(
@@ -193,6 +365,13 @@ pub fn router(resources: Arc<Resources>) -> Router {
                 move |body| post_new(body, resources)
             }),
         )
+        .route(
+            "/bulk",
+            post({
+                let resources = resources.clone();
+                move |body| post_bulk(body, resources)
+            }),
+        )
         .route(
             "/id/:id",
             get({
@@ -211,112 +390,140 @@ pub fn router(resources: Arc<Resources>) -> Router {
             "/id/:id",
             delete({
                 let resources = resources.clone();
-                move |id| delete_by_id(id, resources)
+                move |id, if_match| delete_by_id(id, if_match, resources)
             }),
         )
         .route(
             "/name/:name",
             delete({
                 let resources = resources.clone();
-                move |name| delete_by_name(name, resources)
+                move |name, if_match| delete_by_name(name, if_match, resources)
             }),
         )
         .route(
             "/id/:id",
             patch({
                 let resources = resources.clone();
-                move |id, payload| patch_by_id(id, payload, resources)
+                move |id, if_match, payload| {
+                    patch_by_id(id, if_match, payload, resources)
+                }
             }),
         )
         .route(
             "/name/:name",
             patch({
                 let resources = resources.clone();
-                move |name, payload| patch_by_name(name, payload, resources)
+                move |name, if_match, payload| {
+                    patch_by_name(name, if_match, payload, resources)
+                }
             }),
         )
         .route(
             "/list/",
             get({
                 let resources = resources.clone();
-                move || get_list(resources)
+                move |query, sort| get_list(query, sort, resources)
             }),
         )
 }
 
+async fn insert_status(
+    connection: &mut SqliteConnection,
+    new_status: &NewStatusPayload,
+) -> Result<StatusResponse, NewStatusError> {
+    let new_status = NewStatus::try_from(new_status)?;
+    let sql = "INSERT INTO issue_statuses (name, closed) \
+               VALUES (?, ?) RETURNING id, name, closed, version";
+    let row = query(sql)
+        .bind(new_status.name.as_str())
+        .bind(new_status.closed)
+        .fetch_one(connection)
+        .await?;
+    Ok(StatusResponse::from_row(&row)?)
+}
+
 async fn post_new(
     Json(new_status): Json<NewStatusPayload>,
     resources: Arc<Resources>,
 ) -> ApiResponse<StatusResponse, NewStatusError> {
-    resources
+    let result = resources
         .with_bare_conn(move |connection| {
-            Box::pin(async move {
-                let row = query(
-                    "INSERT INTO statuses (name) VALUES (?) RETURNING id",
-                )
-                .bind(&new_status.name)
-                .fetch_one(&mut **connection)
-                .await?;
-                let id = row.try_get("id")?;
-                Ok(StatusResponse { id, name: new_status.name })
-            })
+            Box::pin(
+                async move { insert_status(connection, &new_status).await },
+            )
         })
-        .await
-        .into()
+        .await;
+    ApiResponse::new(result).with_row_version(|status| status.version)
 }
 
 async fn get_by_id(
     Path(id): Path<i64>,
     resources: Arc<Resources>,
 ) -> ApiResponse<StatusResponse, GetStatusError> {
-    resources
+    let result = resources
         .with_bare_conn(|connection| {
             Box::pin(async move {
-                let row = query("SELECT name FROM statuses WHERE id = ?")
-                    .bind(&id)
-                    .fetch_one(&mut **connection)
-                    .await?;
-                let name = row.try_get("name")?;
-                Ok(StatusResponse { id, name })
+                let sql = "SELECT id, name, closed, version \
+                           FROM issue_statuses WHERE id = ?";
+                let row =
+                    query(sql).bind(id).fetch_one(&mut **connection).await?;
+                Ok(StatusResponse::from_row(&row)?)
             })
         })
-        .await
-        .into()
+        .await;
+    ApiResponse::new(result).with_row_version(|status| status.version)
 }
 
 async fn get_by_name(
     Path(name): Path<String>,
     resources: Arc<Resources>,
 ) -> ApiResponse<StatusResponse, GetStatusError> {
-    resources
+    let result = resources
         .with_bare_conn(|connection| {
             Box::pin(async move {
-                let row = query("SELECT id FROM statuses WHERE name = ?")
-                    .bind(&name)
-                    .fetch_one(&mut **connection)
-                    .await?;
-                let id = row.try_get("id")?;
-                Ok(StatusResponse { id, name })
+                let sql = "SELECT id, name, closed, version \
+                           FROM issue_statuses WHERE name = ?";
+                let row =
+                    query(sql).bind(&name).fetch_one(&mut **connection).await?;
+                Ok(StatusResponse::from_row(&row)?)
             })
         })
-        .await
-        .into()
+        .await;
+    ApiResponse::new(result).with_row_version(|status| status.version)
+}
+
+async fn delete_status_by_id(
+    connection: &mut SqliteConnection,
+    id: i64,
+    version: i64,
+) -> Result<StatusResponse, DeleteStatusError> {
+    let sql = "DELETE FROM issue_statuses \
+               WHERE id = ? AND version = ? \
+               RETURNING id, name, closed, version";
+    let row = query(sql)
+        .bind(id)
+        .bind(version)
+        .fetch_optional(&mut *connection)
+        .await?;
+    let Some(row) = row else {
+        query("SELECT 1 FROM issue_statuses WHERE id = ?")
+            .bind(id)
+            .fetch_one(connection)
+            .await?;
+        return Err(DeleteStatusError::Stale);
+    };
+    Ok(StatusResponse::from_row(&row)?)
 }
 
 async fn delete_by_id(
     Path(id): Path<i64>,
+    IfMatch(version): IfMatch,
     resources: Arc<Resources>,
 ) -> ApiResponse<StatusResponse, DeleteStatusError> {
     resources
         .with_bare_conn(|connection| {
             Box::pin(async move {
-                let row =
-                    query("DELETE FROM statuses WHERE id = ? RETURNING name")
-                        .bind(&id)
-                        .fetch_one(&mut **connection)
-                        .await?;
-                let name = row.try_get("name")?;
-                Ok(StatusResponse { id, name })
+                delete_status_by_id(connection, id, version).await
             })
         })
         .await
@@ -325,87 +532,187 @@ async fn delete_by_id(
 
 async fn delete_by_name(
     Path(name): Path<String>,
+    IfMatch(version): IfMatch,
     resources: Arc<Resources>,
 ) -> ApiResponse<StatusResponse, DeleteStatusError> {
     resources
         .with_bare_conn(|connection| {
             Box::pin(async move {
-                let row =
-                    query("DELETE FROM statuses WHERE name = ? RETURNING id")
+                let sql = "DELETE FROM issue_statuses \
+                           WHERE name = ? AND version = ? \
+                           RETURNING id, name, closed, version";
+                let row = query(sql)
+                    .bind(&name)
+                    .bind(version)
+                    .fetch_optional(&mut **connection)
+                    .await?;
+                let Some(row) = row else {
+                    query("SELECT 1 FROM issue_statuses WHERE name = ?")
                         .bind(&name)
                         .fetch_one(&mut **connection)
                         .await?;
-                let id = row.try_get("id")?;
-                Ok(StatusResponse { id, name })
+                    return Err(DeleteStatusError::Stale);
+                };
+                Ok(StatusResponse::from_row(&row)?)
             })
         })
         .await
         .into()
 }
 
+async fn patch_status_by_id(
+    connection: &mut SqliteConnection,
+    id: i64,
+    version: i64,
+    payload: &PatchStatusPayload,
+) -> Result<StatusResponse, PatchStatusError> {
+    let patch = StatusPatch::try_from(payload)?;
+    if patch.is_empty() {
+        return Err(PatchStatusError::NoFieldsPatched);
+    }
+    if patch.closed == Some(true) {
+        check_closable(connection, id).await?;
+    }
+    let sql = "UPDATE issue_statuses \
+               SET name = COALESCE(?, name), \
+               closed = COALESCE(?, closed), \
+               version = version + 1 \
+               WHERE id = ? AND version = ? \
+               RETURNING id, name, closed, version";
+    let row = query(sql)
+        .bind(patch.name.as_ref().map(Name::as_str))
+        .bind(patch.closed)
+        .bind(id)
+        .bind(version)
+        .fetch_optional(&mut *connection)
+        .await?;
+    let Some(row) = row else {
+        query("SELECT 1 FROM issue_statuses WHERE id = ?")
+            .bind(id)
+            .fetch_one(connection)
+            .await?;
+        return Err(PatchStatusError::Stale);
+    };
+    Ok(StatusResponse::from_row(&row)?)
+}
+
+/// Closing a status closes every issue in it, so it is refused while one of
+/// them is blocked by an open issue in another status.
+async fn check_closable(
+    connection: &mut SqliteConnection,
+    id: i64,
+) -> Result<(), PatchStatusError> {
+    let sql = "SELECT DISTINCT blocked.id \
+               FROM issues AS blocked \
+               JOIN issue_blockings ON issue_blockings.blocked = blocked.id \
+               JOIN issues AS blocker ON blocker.id = issue_blockings.blocker \
+               JOIN issue_statuses ON issue_statuses.id = blocker.status \
+               WHERE blocked.status = ?1 AND blocker.status <> ?1 \
+               AND NOT issue_statuses.closed \
+               ORDER BY blocked.id";
+    let blocked: Vec<i64> =
+        query_scalar(sql).bind(id).fetch_all(connection).await?;
+    if !blocked.is_empty() {
+        return Err(PatchStatusError::HoldsBlocked(blocked));
+    }
+    Ok(())
+}
+
 async fn patch_by_id(
     Path(id): Path<i64>,
+    IfMatch(version): IfMatch,
     Json(payload): Json<PatchStatusPayload>,
     resources: Arc<Resources>,
 ) -> ApiResponse<StatusResponse, PatchStatusError> {
-    let Some(new_name) = payload.name else {
-        return ApiResponse::new(Err(PatchStatusError::NoFieldsPatched));
-    };
-    resources
+    let result = resources
         .with_bare_conn(|connection| {
             Box::pin(async move {
-                query("UPDATE statuses SET name = ? WHERE id = ?")
-                    .bind(&new_name)
-                    .bind(&id)
-                    .execute(&mut **connection)
-                    .await?;
-                Ok(StatusResponse { id, name: new_name })
+                patch_status_by_id(connection, id, version, &payload).await
             })
         })
-        .await
-        .into()
+        .await;
+    ApiResponse::new(result)
+        .with_row_version(|status| status.version)
+        .with_error_detail(PatchStatusError::detail)
 }
 
 async fn patch_by_name(
     Path(name): Path<String>,
+    IfMatch(version): IfMatch,
     Json(payload): Json<PatchStatusPayload>,
     resources: Arc<Resources>,
 ) -> ApiResponse<StatusResponse, PatchStatusError> {
-    let Some(new_name) = payload.name else {
-        return ApiResponse::new(Err(PatchStatusError::NoFieldsPatched));
+    let patch = match StatusPatch::try_from(&payload) {
+        Ok(patch) if patch.is_empty() => {
+            return ApiResponse::new(Err(PatchStatusError::NoFieldsPatched));
+        },
+        Ok(patch) => patch,
+        Err(error) => return ApiResponse::new(Err(error.into())),
     };
-    resources
+    let result = resources
         .with_bare_conn(|connection| {
             Box::pin(async move {
-                let sql =
-                    "UPDATE statuses SET name = ? WHERE name = ? RETURNING id";
-                let row = query(sql)
-                    .bind(&new_name)
+                if patch.closed == Some(true) {
+                    let id = query_scalar(
+                        "SELECT id FROM issue_statuses WHERE name = ?",
+                    )
                     .bind(&name)
                     .fetch_one(&mut **connection)
                     .await?;
-                let id = row.try_get("id")?;
-                Ok(StatusResponse { id, name: new_name })
+                    check_closable(connection, id).await?;
+                }
+                let sql = "UPDATE issue_statuses \
+                           SET name = COALESCE(?, name), \
+                           closed = COALESCE(?, closed), \
+                           version = version + 1 \
+                           WHERE name = ? AND version = ? \
+                           RETURNING id, name, closed, version";
+                let row = query(sql)
+                    .bind(patch.name.as_ref().map(Name::as_str))
+                    .bind(patch.closed)
+                    .bind(&name)
+                    .bind(version)
+                    .fetch_optional(&mut **connection)
+                    .await?;
+                let Some(row) = row else {
+                    query("SELECT 1 FROM issue_statuses WHERE name = ?")
+                        .bind(&name)
+                        .fetch_one(&mut **connection)
+                        .await?;
+                    return Err(PatchStatusError::Stale);
+                };
+                Ok(StatusResponse::from_row(&row)?)
             })
         })
-        .await
-        .into()
+        .await;
+    ApiResponse::new(result)
+        .with_row_version(|status| status.version)
+        .with_error_detail(PatchStatusError::detail)
 }
 
 async fn get_list(
+    Query(list_query): Query<StatusListQuery>,
+    sort: SortParams<StatusSort>,
     resources: Arc<Resources>,
 ) -> ApiResponse<StatusListResponse, GetStatusError> {
     resources
         .with_bare_conn(|connection| {
             Box::pin(async move {
                 let mut statuses = Vec::new();
-                let mut stream =
-                    query("SELECT id, name FROM statuses WHERE ORDER BY id")
-                        .fetch(&mut **connection);
+                let sql = format!(
+                    "SELECT id, name, closed, version FROM issue_statuses \
+                     WHERE (?1 IS NULL \
+                         OR instr(lower(name), lower(?1)) > 0) \
+                     AND (?2 IS NULL OR closed = ?2) \
+                     ORDER BY {}",
+                    sort.order_by(),
+                );
+                let mut stream = query(&sql)
+                    .bind(&list_query.name_contains)
+                    .bind(list_query.closed)
+                    .fetch(&mut **connection);
                 while let Some(row) = stream.try_next().await? {
-                    let id = row.try_get("id")?;
-                    let name = row.try_get("name")?;
-                    statuses.push(StatusResponse { id, name });
+                    statuses.push(StatusResponse::from_row(&row)?);
                 }
                 Ok(StatusListResponse { list: statuses })
             })
@@ -413,3 +720,48 @@ async fn get_list(
         .await
         .into()
 }
+
+async fn post_bulk(
+    Json(operations): Json<Vec<BulkOperation>>,
+    resources: Arc<Resources>,
+) -> ApiResponse<StatusListResponse, BulkStatusError> {
+    let result = resources
+        .with_retrying_transaction(|transaction| {
+            let operations = operations.clone();
+            Box::pin(async move {
+                let mut statuses = Vec::with_capacity(operations.len());
+                for (index, operation) in operations.iter().enumerate() {
+                    let connection = &mut **transaction;
+                    let result = match operation {
+                        BulkOperation::Create(new_status) => {
+                            insert_status(connection, new_status)
+                                .await
+                                .map_err(BulkOperationError::Create)
+                        },
+                        BulkOperation::Patch(patch) => patch_status_by_id(
+                            connection,
+                            patch.id,
+                            patch.version,
+                            &patch.payload,
+                        )
+                        .await
+                        .map_err(BulkOperationError::Patch),
+                        BulkOperation::Delete(delete) => delete_status_by_id(
+                            connection,
+                            delete.id,
+                            delete.version,
+                        )
+                        .await
+                        .map_err(BulkOperationError::Delete),
+                    };
+                    let status = result.map_err(|source| {
+                        BulkStatusError::Operation { index, source }
+                    })?;
+                    statuses.push(status);
+                }
+                Ok(StatusListResponse { list: statuses })
+            })
+        })
+        .await;
+    ApiResponse::new(result).with_error_detail(BulkStatusError::detail)
+}
@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    routing::{get, post},
+    Json,
+    Router,
+};
+use schemars::{schema_for, Schema};
+use serde::Deserialize;
+
+use crate::{
+    status::ResponseStatusCode,
+    sync::{
+        self,
+        ApplyReport,
+        ChangeBatch,
+        ChangeFeed,
+        InstanceInfo,
+        SyncError,
+        MAX_BATCH,
+    },
+};
+
+use super::{auth::AdminUser, response::ApiResponse, Resources};
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChangesQuery {
+    #[serde(default)]
+    since: i64,
+    /// Instance asking for the changes, whose own changes are left out.
+    peer: Option<String>,
+    limit: Option<i64>,
+}
+
+impl ResponseStatusCode for InstanceInfo {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+impl ResponseStatusCode for ChangeFeed {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+impl ResponseStatusCode for ApplyReport {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+impl ResponseStatusCode for SyncError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::SameInstance => StatusCode::CONFLICT,
+            Self::Remote(_) | Self::Http(_) => StatusCode::BAD_GATEWAY,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("InstanceInfo", schema_for!(InstanceInfo)),
+        ("ChangeFeed", schema_for!(ChangeFeed)),
+        ("ChangeBatch", schema_for!(ChangeBatch)),
+        ("ApplyReport", schema_for!(ApplyReport)),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/instance",
+            get({
+                let resources = resources.clone();
+                move |admin| get_instance(admin, resources)
+            }),
+        )
+        .route(
+            "/changes",
+            get({
+                let resources = resources.clone();
+                move |admin, query| get_changes(admin, query, resources)
+            }),
+        )
+        .route(
+            "/apply",
+            post({
+                let resources = resources.clone();
+                move |admin, body| post_apply(admin, body, resources)
+            }),
+        )
+}
+
+async fn get_instance(
+    _admin: AdminUser,
+    resources: Arc<Resources>,
+) -> ApiResponse<InstanceInfo, SyncError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move { Ok(sync::instance(connection).await?) })
+        })
+        .await
+        .into()
+}
+
+async fn get_changes(
+    _admin: AdminUser,
+    Query(changes): Query<ChangesQuery>,
+    resources: Arc<Resources>,
+) -> ApiResponse<ChangeFeed, SyncError> {
+    resources
+        .with_transaction(|transaction| {
+            Box::pin(async move {
+                let feed = sync::changes_since(
+                    transaction,
+                    changes.since,
+                    changes.peer.as_deref(),
+                    changes.limit.unwrap_or(MAX_BATCH),
+                )
+                .await?;
+                Ok(feed)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn post_apply(
+    _admin: AdminUser,
+    Json(batch): Json<ChangeBatch>,
+    resources: Arc<Resources>,
+) -> ApiResponse<ApplyReport, SyncError> {
+    let result = resources
+        .with_transaction(|transaction| {
+            Box::pin(async move { sync::apply(transaction, &batch).await })
+        })
+        .await;
+    if let Ok(report) = &result {
+        tracing::info!(
+            applied = report.applied,
+            conflicts = report.conflicts.len(),
+            "Applied changes from another instance"
+        );
+    }
+    ApiResponse::new(result)
+}
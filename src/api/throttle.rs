@@ -0,0 +1,103 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use axum::{
+    extract::Request,
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+
+use crate::status::ResponseStatusCode;
+
+use super::{
+    response::{ApiResponse, NoData},
+    Resources,
+};
+
+const QUEUE_POSITION_HEADER: &str = "x-queue-position";
+
+/// Bounds how many expensive requests run at once, queueing a few more and
+/// turning the rest away, so that they do not starve the SQLite writer.
+#[derive(Debug)]
+pub struct Throttle {
+    permits: Semaphore,
+    waiting: AtomicUsize,
+    max_waiting: usize,
+}
+
+impl Throttle {
+    pub fn new(concurrency: usize, max_waiting: usize) -> Self {
+        Self {
+            permits: Semaphore::new(concurrency),
+            waiting: AtomicUsize::new(0),
+            max_waiting,
+        }
+    }
+}
+
+/// Leaves the queue when dropped, including when the client gives up.
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Too many expensive requests in progress, {waiting} already waiting")]
+struct Throttled {
+    waiting: usize,
+}
+
+impl ResponseStatusCode for Throttled {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::TOO_MANY_REQUESTS
+    }
+}
+
+/// Queued requests report the position they were given in
+/// `X-Queue-Position`.
+pub async fn limit(request: Request, next: Next) -> Response {
+    let resources = request
+        .extensions()
+        .get::<Arc<Resources>>()
+        .cloned()
+        .expect("API resources must be installed as an extension");
+    let throttle = &resources.throttle;
+    let (permit, position) = match throttle.permits.try_acquire() {
+        Ok(permit) => (permit, None),
+        Err(_) => {
+            let position = throttle.waiting.fetch_add(1, Ordering::Relaxed);
+            let slot = QueueSlot(&throttle.waiting);
+            if position >= throttle.max_waiting {
+                drop(slot);
+                let error = Throttled { waiting: position };
+                let mut response =
+                    ApiResponse::<NoData, _>::new(Err(error)).into_response();
+                response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, HeaderValue::from_static("1"));
+                return response;
+            }
+            let permit = throttle
+                .permits
+                .acquire()
+                .await
+                .expect("throttle semaphore is never closed");
+            drop(slot);
+            (permit, Some(position + 1))
+        },
+    };
+    let mut response = next.run(request).await;
+    drop(permit);
+    if let Some(position) = position {
+        response.headers_mut().insert(QUEUE_POSITION_HEADER, position.into());
+    }
+    response
+}
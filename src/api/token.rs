@@ -0,0 +1,276 @@
+use std::sync::Arc;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{
+    extract::{Path, Request},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json,
+    Router,
+};
+use futures::TryStreamExt;
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{query, sqlite::SqliteRow, Row, SqliteConnection};
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{
+    auth::{self, AuthError, CurrentUser, UserResponse},
+    response::ApiResponse,
+    Resources,
+};
+
+const TOKEN_BYTES: usize = 32;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct NewTokenPayload {
+    name: String,
+}
+
+#[derive(Debug, Error)]
+enum TokenError {
+    #[error("Token not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for TokenError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for TokenError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct TokenResponse {
+    id: i64,
+    name: String,
+    created_at: i64,
+    last_used_at: Option<i64>,
+}
+
+impl TokenResponse {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            created_at: row.try_get("created_at")?,
+            last_used_at: row.try_get("last_used_at")?,
+        })
+    }
+}
+
+impl ResponseStatusCode for TokenResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct NewTokenResponse {
+    #[serde(flatten)]
+    info: TokenResponse,
+    token: String,
+}
+
+impl ResponseStatusCode for NewTokenResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct TokenListResponse {
+    list: Vec<TokenResponse>,
+}
+
+impl ResponseStatusCode for TokenListResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("NewTokenPayload", schema_for!(NewTokenPayload)),
+        ("TokenResponse", schema_for!(TokenResponse)),
+        ("NewTokenResponse", schema_for!(NewTokenResponse)),
+        ("TokenListResponse", schema_for!(TokenListResponse)),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/new",
+            post({
+                let resources = resources.clone();
+                move |user, body| post_new(user, body, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            delete({
+                let resources = resources.clone();
+                move |user, id| delete_by_id(user, id, resources)
+            }),
+        )
+        .route(
+            "/list/",
+            get({
+                let resources = resources.clone();
+                move |user| get_list(user, resources)
+            }),
+        )
+}
+
+pub async fn bearer_auth(mut request: Request, next: Next) -> Response {
+    let Some(token) = bearer_token(request.headers()) else {
+        return next.run(request).await;
+    };
+    let resources = request
+        .extensions()
+        .get::<Arc<Resources>>()
+        .cloned()
+        .expect("API resources must be installed as an extension");
+    let read_only = resources.read_only.is_enabled();
+    let result = resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                user_for_token(connection, &token, !read_only).await
+            })
+        })
+        .await;
+    match result {
+        Ok(user) => {
+            request.extensions_mut().insert(CurrentUser(user));
+            next.run(request).await
+        },
+        Err(error) => error.into_response(),
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let token = value.strip_prefix("Bearer ")?.trim();
+    Some(token.to_owned())
+}
+
+pub fn hash_token(token: &str) -> String {
+    let digest = Sha256::digest(token.as_bytes());
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn generate_token() -> String {
+    let mut bytes = [0; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+async fn user_for_token(
+    connection: &mut SqliteConnection,
+    token: &str,
+    touch: bool,
+) -> Result<UserResponse, AuthError> {
+    // Usage is not recorded in read-only mode.
+    let sql = if touch {
+        "UPDATE api_tokens SET last_used_at = unixepoch() \
+         WHERE token_hash = ? RETURNING user"
+    } else {
+        "SELECT user FROM api_tokens WHERE token_hash = ?"
+    };
+    let row = query(sql)
+        .bind(hash_token(token))
+        .fetch_optional(&mut *connection)
+        .await?
+        .ok_or(AuthError::InvalidToken)?;
+    let user_id = row.try_get("user")?;
+    Ok(auth::user_by_id(connection, user_id).await?)
+}
+
+async fn post_new(
+    CurrentUser(user): CurrentUser,
+    Json(new_token): Json<NewTokenPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<NewTokenResponse, TokenError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let token = generate_token();
+                let sql = "INSERT INTO api_tokens (user, name, token_hash) \
+                           VALUES (?, ?, ?) \
+                           RETURNING id, name, created_at, last_used_at";
+                let row = query(sql)
+                    .bind(user.id)
+                    .bind(&new_token.name)
+                    .bind(hash_token(&token))
+                    .fetch_one(&mut **connection)
+                    .await?;
+                let info = TokenResponse::from_row(&row)?;
+                Ok(NewTokenResponse { info, token })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn delete_by_id(
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<TokenResponse, TokenError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let sql = "DELETE FROM api_tokens WHERE id = ? AND user = ? \
+                           RETURNING id, name, created_at, last_used_at";
+                let row = query(sql)
+                    .bind(id)
+                    .bind(user.id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(TokenResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_list(
+    CurrentUser(user): CurrentUser,
+    resources: Arc<Resources>,
+) -> ApiResponse<TokenListResponse, TokenError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let mut tokens = Vec::new();
+                let sql = "SELECT id, name, created_at, last_used_at \
+                           FROM api_tokens WHERE user = ? ORDER BY id";
+                let mut stream =
+                    query(sql).bind(user.id).fetch(&mut **connection);
+                while let Some(row) = stream.try_next().await? {
+                    tokens.push(TokenResponse::from_row(&row)?);
+                }
+                Ok(TokenListResponse { list: tokens })
+            })
+        })
+        .await
+        .into()
+}
@@ -0,0 +1,623 @@
+use std::sync::Arc;
+
+use axum::{extract::Path, http::StatusCode, routing::post, Json, Router};
+use futures::TryStreamExt;
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{query, Row, SqliteConnection};
+use thiserror::Error;
+
+use crate::{
+    domain::{check_status_change, StatusChangeError},
+    status::ResponseStatusCode,
+};
+
+use super::{
+    auth::CurrentUser,
+    is_foreign_key_violation,
+    issue::{self, IssueDetailResponse, IssueResponse, OpenBlockers},
+    response::ApiResponse,
+    Resources,
+};
+
+const DEFAULT_SESSION_SIZE: u32 = 10;
+
+const MAX_SESSION_SIZE: u32 = 50;
+
+const DEFAULT_SESSION_MINUTES: u32 = 15;
+
+const MAX_SESSION_MINUTES: u32 = 120;
+
+/// Issues waiting for triage: unassigned, open, unlabeled and not reserved
+/// by a triage session that is still running.
+const UNTRIAGED: &str = "assignee IS NULL \
+    AND status IN (SELECT id FROM issue_statuses WHERE NOT closed) \
+    AND NOT EXISTS ( \
+        SELECT 1 FROM issue_labels WHERE issue_labels.issue = issues.id \
+    ) \
+    AND NOT EXISTS ( \
+        SELECT 1 FROM triage_session_items \
+        JOIN triage_sessions \
+            ON triage_sessions.id = triage_session_items.session \
+        WHERE triage_session_items.issue = issues.id \
+        AND triage_sessions.submitted_at IS NULL \
+        AND triage_sessions.expires_at > unixepoch() \
+    )";
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct TriagePayload {
+    #[serde(default)]
+    label_ids: Vec<i64>,
+    #[serde(default)]
+    status_id: Option<i64>,
+    #[serde(default)]
+    assignee_id: Option<i64>,
+}
+
+#[derive(Debug, Error)]
+enum TriageError {
+    #[error("At least one label, status or assignee must be given, none were")]
+    NothingToApply,
+    #[error("Issue not found")]
+    NotFound,
+    #[error("Referenced label, status or user not found")]
+    ReferenceNotFound,
+    #[error("Status is not allowed for issues of this type")]
+    StatusNotAllowed,
+    #[error("Issue is blocked by open issues {0}")]
+    Blocked(OpenBlockers),
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for TriageError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if is_foreign_key_violation(&**error) {
+                return Self::ReferenceNotFound;
+            }
+        }
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for TriageError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NothingToApply => StatusCode::BAD_REQUEST,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::ReferenceNotFound => StatusCode::BAD_REQUEST,
+            Self::StatusNotAllowed => StatusCode::BAD_REQUEST,
+            Self::Blocked(_) => StatusCode::CONFLICT,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl TriageError {
+    fn detail(&self) -> Option<(&'static str, Value)> {
+        match self {
+            Self::Blocked(blockers) => blockers.detail(),
+            _ => None,
+        }
+    }
+}
+
+impl TriagePayload {
+    fn is_empty(&self) -> bool {
+        self.label_ids.is_empty()
+            && self.status_id.is_none()
+            && self.assignee_id.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct NewTriageSessionPayload {
+    /// Issues to reserve, at most 50.
+    #[serde(default)]
+    size: Option<u32>,
+    /// Minutes the issues stay reserved, at most 120.
+    #[serde(default)]
+    minutes: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct TriageDecision {
+    issue_id: i64,
+    #[serde(flatten)]
+    triage: TriagePayload,
+}
+
+/// Reserved issues without a decision are released untouched.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct SubmitTriageSessionPayload {
+    decisions: Vec<TriageDecision>,
+}
+
+#[derive(Debug, Error)]
+enum TriageSessionError {
+    #[error("Session size must be between 1 and {MAX_SESSION_SIZE}")]
+    InvalidSize,
+    #[error(
+        "Session length must be between 1 and {MAX_SESSION_MINUTES} minutes"
+    )]
+    InvalidMinutes,
+    #[error("No label, status or assignee given for issue {0}")]
+    NothingToApply(i64),
+    #[error("Triage session not found")]
+    NotFound,
+    #[error("Triage session has expired")]
+    Expired,
+    #[error("Triage session was already submitted")]
+    AlreadySubmitted,
+    #[error("Issue {0} is not part of the triage session")]
+    NotInSession(i64),
+    #[error("Status is not allowed for the type of issue {0}")]
+    StatusNotAllowed(i64),
+    #[error("Issue {0} is blocked by open issues {1}")]
+    Blocked(i64, OpenBlockers),
+    #[error("Referenced label, status or user not found")]
+    ReferenceNotFound,
+    #[error("Failed to encode triage decision")]
+    Encode(#[source] serde_json::Error),
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for TriageSessionError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if is_foreign_key_violation(&**error) {
+                return Self::ReferenceNotFound;
+            }
+        }
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for TriageSessionError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidSize => StatusCode::BAD_REQUEST,
+            Self::InvalidMinutes => StatusCode::BAD_REQUEST,
+            Self::NothingToApply(_) => StatusCode::BAD_REQUEST,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Expired => StatusCode::GONE,
+            Self::AlreadySubmitted => StatusCode::CONFLICT,
+            Self::NotInSession(_) => StatusCode::BAD_REQUEST,
+            Self::StatusNotAllowed(_) => StatusCode::BAD_REQUEST,
+            Self::Blocked(..) => StatusCode::CONFLICT,
+            Self::ReferenceNotFound => StatusCode::BAD_REQUEST,
+            Self::Encode(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl TriageSessionError {
+    fn detail(&self) -> Option<(&'static str, Value)> {
+        match self {
+            Self::Blocked(_, blockers) => blockers.detail(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TriageQueueError {
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+impl ResponseStatusCode for TriageQueueError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct TriageOption {
+    id: i64,
+    name: String,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct TriageActions {
+    statuses: Vec<TriageOption>,
+    labels: Vec<TriageOption>,
+    assignees: Vec<TriageOption>,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct TriageQueueResponse {
+    list: Vec<IssueResponse>,
+    actions: TriageActions,
+}
+
+impl ResponseStatusCode for TriageQueueResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct TriageSessionResponse {
+    id: i64,
+    expires_at: i64,
+    list: Vec<IssueResponse>,
+}
+
+impl ResponseStatusCode for TriageSessionResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct SubmittedTriageSessionResponse {
+    id: i64,
+    submitted_at: i64,
+    /// Issues a decision was applied to, as they are after triage.
+    list: Vec<IssueResponse>,
+}
+
+impl ResponseStatusCode for SubmittedTriageSessionResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("TriagePayload", schema_for!(TriagePayload)),
+        ("TriageQueueResponse", schema_for!(TriageQueueResponse)),
+        ("NewTriageSessionPayload", schema_for!(NewTriageSessionPayload)),
+        ("SubmitTriageSessionPayload", schema_for!(SubmitTriageSessionPayload)),
+        ("TriageSessionResponse", schema_for!(TriageSessionResponse)),
+        (
+            "SubmittedTriageSessionResponse",
+            schema_for!(SubmittedTriageSessionResponse),
+        ),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/session",
+            post({
+                let resources = resources.clone();
+                move |user, body| post_session(user, body, resources)
+            }),
+        )
+        .route(
+            "/session/:id/submit",
+            post({
+                let resources = resources.clone();
+                move |user, id, body| post_submit(user, id, body, resources)
+            }),
+        )
+}
+
+pub fn issue_router(resources: Arc<Resources>) -> Router {
+    Router::new().route(
+        "/id/:id/triage",
+        post({
+            let resources = resources.clone();
+            move |id, body| post_triage(id, body, resources)
+        }),
+    )
+}
+
+async fn options(
+    connection: &mut SqliteConnection,
+    sql: &str,
+) -> Result<Vec<TriageOption>, sqlx::Error> {
+    let mut options = Vec::new();
+    let mut stream = query(sql).fetch(connection);
+    while let Some(row) = stream.try_next().await? {
+        let id = row.try_get("id")?;
+        let name = row.try_get("name")?;
+        options.push(TriageOption { id, name });
+    }
+    Ok(options)
+}
+
+/// Assignees are only offered to signed-in users, so that the queue does not
+/// tell anyone which accounts exist.
+pub async fn get_queue(
+    user: Option<CurrentUser>,
+    resources: Arc<Resources>,
+) -> ApiResponse<TriageQueueResponse, TriageQueueError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let mut issues = Vec::new();
+                let sql = format!(
+                    "SELECT id, title, description, status, milestone, \
+                     affects_version, fixed_in_version, assignee, type, \
+                     created_at, updated_at, version \
+                     FROM issues WHERE {UNTRIAGED} ORDER BY id"
+                );
+                let mut stream = query(&sql).fetch(&mut **connection);
+                while let Some(row) = stream.try_next().await? {
+                    issues.push(IssueResponse::from_row(&row)?);
+                }
+                drop(stream);
+                let actions = TriageActions {
+                    statuses: options(
+                        connection,
+                        "SELECT id, name FROM issue_statuses ORDER BY id",
+                    )
+                    .await?,
+                    labels: options(
+                        connection,
+                        "SELECT id, name FROM labels ORDER BY name",
+                    )
+                    .await?,
+                    assignees: match user {
+                        Some(_) => {
+                            options(
+                                connection,
+                                "SELECT id, name FROM users ORDER BY name",
+                            )
+                            .await?
+                        },
+                        None => Vec::new(),
+                    },
+                };
+                Ok(TriageQueueResponse { list: issues, actions })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn post_triage(
+    Path(id): Path<i64>,
+    Json(payload): Json<TriagePayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<IssueDetailResponse, TriageError> {
+    if payload.is_empty() {
+        return ApiResponse::new(Err(TriageError::NothingToApply));
+    }
+    let edit_lock = resources.edit_locks.holder(id);
+    let result = resources
+        .with_retrying_transaction(|transaction| {
+            let payload = payload.clone();
+            let edit_lock = edit_lock.clone();
+            Box::pin(async move {
+                match check_status(transaction, id, &payload).await? {
+                    Some(StatusChangeError::NotAllowed) => {
+                        return Err(TriageError::StatusNotAllowed);
+                    },
+                    Some(StatusChangeError::Blocked(ids)) => {
+                        return Err(TriageError::Blocked(ids.into()));
+                    },
+                    None => (),
+                }
+                apply(transaction, id, &payload).await?;
+                Ok(issue::detail_for_issue(transaction, id, edit_lock).await?)
+            })
+        })
+        .await;
+    ApiResponse::new(result).with_error_detail(TriageError::detail)
+}
+
+/// Triage cannot leave an issue in a status its type does not allow, nor
+/// close an issue that open issues still block.
+async fn check_status(
+    connection: &mut SqliteConnection,
+    id: i64,
+    payload: &TriagePayload,
+) -> Result<Option<StatusChangeError>, sqlx::Error> {
+    let Some(status_id) = payload.status_id else {
+        return Ok(None);
+    };
+    let change =
+        issue::status_change(connection, id, None, Some(status_id)).await?;
+    Ok(check_status_change(&change, false).err())
+}
+
+async fn apply(
+    connection: &mut SqliteConnection,
+    id: i64,
+    payload: &TriagePayload,
+) -> Result<(), sqlx::Error> {
+    let sql = "UPDATE issues SET \
+               status = COALESCE(?, status), \
+               assignee = COALESCE(?, assignee), \
+               updated_at = unixepoch(), \
+               version = version + 1 \
+               WHERE id = ? RETURNING id";
+    query(sql)
+        .bind(payload.status_id)
+        .bind(payload.assignee_id)
+        .bind(id)
+        .fetch_one(&mut *connection)
+        .await?;
+    for label_id in &payload.label_ids {
+        query(
+            "INSERT OR IGNORE INTO issue_labels (issue, label) VALUES (?, ?)",
+        )
+        .bind(id)
+        .bind(label_id)
+        .execute(&mut *connection)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn issue_by_id(
+    connection: &mut SqliteConnection,
+    id: i64,
+) -> Result<IssueResponse, sqlx::Error> {
+    let sql = "SELECT id, title, description, status, milestone, \
+               affects_version, fixed_in_version, assignee, type, \
+               created_at, updated_at, version \
+               FROM issues WHERE id = ?";
+    let row = query(sql).bind(id).fetch_one(connection).await?;
+    IssueResponse::from_row(&row)
+}
+
+/// Reserves the oldest untriaged issues for the current user, so that
+/// teammates triaging at the same time are given different ones.
+async fn post_session(
+    CurrentUser(user): CurrentUser,
+    Json(payload): Json<NewTriageSessionPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<TriageSessionResponse, TriageSessionError> {
+    let size = payload.size.unwrap_or(DEFAULT_SESSION_SIZE);
+    if !(1..=MAX_SESSION_SIZE).contains(&size) {
+        return ApiResponse::new(Err(TriageSessionError::InvalidSize));
+    }
+    let minutes = payload.minutes.unwrap_or(DEFAULT_SESSION_MINUTES);
+    if !(1..=MAX_SESSION_MINUTES).contains(&minutes) {
+        return ApiResponse::new(Err(TriageSessionError::InvalidMinutes));
+    }
+    resources
+        .with_retrying_transaction(|transaction| {
+            Box::pin(async move {
+                let row = query(
+                    "INSERT INTO triage_sessions (user, expires_at) \
+                     VALUES (?, unixepoch() + ?) RETURNING id, expires_at",
+                )
+                .bind(user.id)
+                .bind(i64::from(minutes) * 60)
+                .fetch_one(&mut **transaction)
+                .await?;
+                let id: i64 = row.try_get("id")?;
+                let expires_at = row.try_get("expires_at")?;
+                let sql = format!(
+                    "INSERT INTO triage_session_items (session, issue) \
+                     SELECT ?, id FROM issues WHERE {UNTRIAGED} \
+                     ORDER BY id LIMIT ?"
+                );
+                query(&sql)
+                    .bind(id)
+                    .bind(size)
+                    .execute(&mut **transaction)
+                    .await?;
+                let mut list = Vec::new();
+                let sql = "SELECT id, title, description, status, milestone, \
+                           affects_version, fixed_in_version, assignee, type, \
+                           created_at, updated_at, version \
+                           FROM issues \
+                           JOIN triage_session_items \
+                               ON triage_session_items.issue = issues.id \
+                           WHERE triage_session_items.session = ? \
+                           ORDER BY id";
+                let mut stream = query(sql).bind(id).fetch(&mut **transaction);
+                while let Some(row) = stream.try_next().await? {
+                    list.push(IssueResponse::from_row(&row)?);
+                }
+                Ok(TriageSessionResponse { id, expires_at, list })
+            })
+        })
+        .await
+        .into()
+}
+
+/// Applies and records the decisions taken in a session, all or nothing,
+/// and releases its remaining issues.
+async fn post_submit(
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<SubmitTriageSessionPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<SubmittedTriageSessionResponse, TriageSessionError> {
+    if let Some(decision) =
+        payload.decisions.iter().find(|decision| decision.triage.is_empty())
+    {
+        let error = TriageSessionError::NothingToApply(decision.issue_id);
+        return ApiResponse::new(Err(error));
+    }
+    let result = resources
+        .with_retrying_transaction(|transaction| {
+            let decisions = payload.decisions.clone();
+            Box::pin(async move {
+                let row = query(
+                    "SELECT user, submitted_at, expires_at > unixepoch() \
+                     AS active FROM triage_sessions WHERE id = ?",
+                )
+                .bind(id)
+                .fetch_one(&mut **transaction)
+                .await?;
+                if row.try_get::<i64, _>("user")? != user.id {
+                    return Err(TriageSessionError::NotFound);
+                }
+                if row.try_get::<Option<i64>, _>("submitted_at")?.is_some() {
+                    return Err(TriageSessionError::AlreadySubmitted);
+                }
+                if !row.try_get::<bool, _>("active")? {
+                    return Err(TriageSessionError::Expired);
+                }
+                let mut list = Vec::new();
+                for decision in &decisions {
+                    let recorded = serde_json::to_string(&decision.triage)
+                        .map_err(TriageSessionError::Encode)?;
+                    query(
+                        "UPDATE triage_session_items SET decision = ? \
+                         WHERE session = ? AND issue = ? RETURNING issue",
+                    )
+                    .bind(recorded)
+                    .bind(id)
+                    .bind(decision.issue_id)
+                    .fetch_optional(&mut **transaction)
+                    .await?
+                    .ok_or(
+                        TriageSessionError::NotInSession(decision.issue_id),
+                    )?;
+                    let rejection = check_status(
+                        transaction,
+                        decision.issue_id,
+                        &decision.triage,
+                    )
+                    .await?;
+                    match rejection {
+                        Some(StatusChangeError::NotAllowed) => {
+                            return Err(TriageSessionError::StatusNotAllowed(
+                                decision.issue_id,
+                            ));
+                        },
+                        Some(StatusChangeError::Blocked(ids)) => {
+                            return Err(TriageSessionError::Blocked(
+                                decision.issue_id,
+                                ids.into(),
+                            ));
+                        },
+                        None => (),
+                    }
+                    apply(transaction, decision.issue_id, &decision.triage)
+                        .await?;
+                    list.push(
+                        issue_by_id(transaction, decision.issue_id).await?,
+                    );
+                }
+                let submitted_at = query(
+                    "UPDATE triage_sessions SET submitted_at = unixepoch() \
+                     WHERE id = ? RETURNING submitted_at",
+                )
+                .bind(id)
+                .fetch_one(&mut **transaction)
+                .await?
+                .try_get("submitted_at")?;
+                Ok(SubmittedTriageSessionResponse { id, submitted_at, list })
+            })
+        })
+        .await;
+    ApiResponse::new(result).with_error_detail(TriageSessionError::detail)
+}
@@ -0,0 +1,205 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{extract::Path, http::StatusCode, routing::post, Router};
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::Serialize;
+use sqlx::query;
+use thiserror::Error;
+
+use crate::status::ResponseStatusCode;
+
+use super::{
+    clock::Clock,
+    is_foreign_key_violation,
+    response::ApiResponse,
+    Resources,
+};
+
+const TOKEN_BYTES: usize = 16;
+
+/// Restores what a destructive call removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum UndoAction {
+    AttachLabel { issue_id: i64, label_id: i64 },
+}
+
+/// Handed out along with the result of a destructive call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct UndoToken {
+    token: String,
+    expires_in: u64,
+}
+
+#[derive(Debug, Clone)]
+struct PendingUndo {
+    action: UndoAction,
+    expires_at: Instant,
+}
+
+/// Destructive calls that can still be taken back, kept in memory since
+/// they only matter for a few seconds.
+#[derive(Debug)]
+pub struct Undo {
+    clock: Arc<dyn Clock>,
+    window: Duration,
+    pending: Mutex<HashMap<String, PendingUndo>>,
+}
+
+impl Undo {
+    pub fn new(clock: Arc<dyn Clock>, window: Duration) -> Self {
+        Self { clock, window, pending: Mutex::default() }
+    }
+
+    /// Returns nothing when the undo window is disabled.
+    pub fn register(&self, action: UndoAction) -> Option<UndoToken> {
+        if self.window.is_zero() {
+            return None;
+        }
+        let mut bytes = [0; TOKEN_BYTES];
+        OsRng.fill_bytes(&mut bytes);
+        let token: String =
+            bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+        let mut pending =
+            self.pending.lock().unwrap_or_else(|error| error.into_inner());
+        let now = self.clock.now();
+        pending.retain(|_, undo| undo.expires_at > now);
+        let expires_at = now + self.window;
+        pending.insert(token.clone(), PendingUndo { action, expires_at });
+        Some(UndoToken { token, expires_in: self.window.as_secs() })
+    }
+
+    /// Each token can only be redeemed once.
+    fn take(&self, token: &str) -> Option<UndoAction> {
+        let mut pending =
+            self.pending.lock().unwrap_or_else(|error| error.into_inner());
+        let now = self.clock.now();
+        pending.retain(|_, undo| undo.expires_at > now);
+        pending.remove(token).map(|undo| undo.action)
+    }
+}
+
+#[derive(Debug, Error)]
+enum UndoError {
+    #[error("Undo token not found or expired")]
+    NotFound,
+    #[error("Entity to be restored no longer exists")]
+    Gone,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for UndoError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if is_foreign_key_violation(&**error) {
+                return Self::Gone;
+            }
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for UndoError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Gone => StatusCode::GONE,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl ResponseStatusCode for UndoAction {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("UndoAction", schema_for!(UndoAction)),
+        ("UndoToken", schema_for!(UndoToken)),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new().route(
+        "/:token",
+        post({
+            let resources = resources.clone();
+            move |token| post_undo(token, resources)
+        }),
+    )
+}
+
+async fn post_undo(
+    Path(token): Path<String>,
+    resources: Arc<Resources>,
+) -> ApiResponse<UndoAction, UndoError> {
+    let Some(action) = resources.undo.take(&token) else {
+        return ApiResponse::new(Err(UndoError::NotFound));
+    };
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                match action {
+                    UndoAction::AttachLabel { issue_id, label_id } => {
+                        query(
+                            "INSERT OR IGNORE INTO issue_labels \
+                             (issue, label) VALUES (?, ?)",
+                        )
+                        .bind(issue_id)
+                        .bind(label_id)
+                        .execute(&mut **connection)
+                        .await?;
+                    },
+                }
+                Ok(action)
+            })
+        })
+        .await
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use super::{Undo, UndoAction};
+    use crate::api::clock::MockClock;
+
+    const ACTION: UndoAction =
+        UndoAction::AttachLabel { issue_id: 1, label_id: 2 };
+
+    #[test]
+    fn token_is_redeemed_once_within_window() {
+        let clock = Arc::new(MockClock::new());
+        let undo = Undo::new(clock.clone(), Duration::from_secs(10));
+        let token = undo.register(ACTION).unwrap();
+        assert_eq!(token.expires_in, 10);
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(undo.take(&token.token), Some(ACTION));
+        assert_eq!(undo.take(&token.token), None);
+    }
+
+    #[test]
+    fn token_expires_after_window() {
+        let clock = Arc::new(MockClock::new());
+        let undo = Undo::new(clock.clone(), Duration::from_secs(10));
+        let token = undo.register(ACTION).unwrap();
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(undo.take(&token.token), None);
+    }
+
+    #[test]
+    fn zero_window_disables_undo() {
+        let undo = Undo::new(Arc::new(MockClock::new()), Duration::ZERO);
+        assert!(undo.register(ACTION).is_none());
+    }
+}
@@ -0,0 +1,395 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    middleware,
+    routing::{delete, get, patch, post},
+    Json,
+    Router,
+};
+use futures::TryStreamExt;
+use schemars::{schema_for, JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, sqlite::SqliteRow, Row};
+use thiserror::Error;
+
+use crate::{release_notes, status::ResponseStatusCode};
+
+use super::{response::ApiResponse, throttle, Resources};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct NewVersionPayload {
+    name: String,
+    #[serde(default)]
+    release_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct PatchVersionPayload {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    release_date: Option<String>,
+}
+
+#[derive(Debug, Error)]
+enum NewVersionError {
+    #[error("Version with the given name already exists")]
+    AlreadyExists,
+    #[error("Release date must be formatted as YYYY-MM-DD")]
+    InvalidReleaseDate,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for NewVersionError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if error.is_unique_violation() {
+                return Self::AlreadyExists;
+            }
+            if error.is_check_violation() {
+                return Self::InvalidReleaseDate;
+            }
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for NewVersionError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::AlreadyExists => StatusCode::FORBIDDEN,
+            Self::InvalidReleaseDate => StatusCode::BAD_REQUEST,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum GetVersionError {
+    #[error("Version not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for GetVersionError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for GetVersionError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum PatchVersionError {
+    #[error("At least one field must be patched, none were")]
+    NoFieldsPatched,
+    #[error("Version with the given name already exists")]
+    AlreadyExists,
+    #[error("Release date must be formatted as YYYY-MM-DD")]
+    InvalidReleaseDate,
+    #[error("Version not found")]
+    NotFound,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for PatchVersionError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(error) = &error {
+            if error.is_unique_violation() {
+                return Self::AlreadyExists;
+            }
+            if error.is_check_violation() {
+                return Self::InvalidReleaseDate;
+            }
+        }
+        if let sqlx::Error::RowNotFound = &error {
+            return Self::NotFound;
+        }
+        Self::Sqlx(error)
+    }
+}
+
+impl ResponseStatusCode for PatchVersionError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NoFieldsPatched => StatusCode::BAD_REQUEST,
+            Self::AlreadyExists => StatusCode::FORBIDDEN,
+            Self::InvalidReleaseDate => StatusCode::BAD_REQUEST,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct VersionResponse {
+    id: i64,
+    name: String,
+    release_date: Option<String>,
+}
+
+impl VersionResponse {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            release_date: row.try_get("release_date")?,
+        })
+    }
+}
+
+impl ResponseStatusCode for VersionResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct VersionListResponse {
+    list: Vec<VersionResponse>,
+}
+
+impl ResponseStatusCode for VersionListResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+struct ReleaseNotesResponse {
+    version_id: i64,
+    markdown: String,
+}
+
+impl ResponseStatusCode for ReleaseNotesResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("NewVersionPayload", schema_for!(NewVersionPayload)),
+        ("PatchVersionPayload", schema_for!(PatchVersionPayload)),
+        ("VersionResponse", schema_for!(VersionResponse)),
+        ("VersionListResponse", schema_for!(VersionListResponse)),
+        ("ReleaseNotesResponse", schema_for!(ReleaseNotesResponse)),
+    ]
+}
+
+pub fn router(resources: Arc<Resources>) -> Router {
+    Router::new()
+        .route(
+            "/new",
+            post({
+                let resources = resources.clone();
+                move |body| post_new(body, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            get({
+                let resources = resources.clone();
+                move |id| get_by_id(id, resources)
+            }),
+        )
+        .route(
+            "/name/:name",
+            get({
+                let resources = resources.clone();
+                move |name| get_by_name(name, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            delete({
+                let resources = resources.clone();
+                move |id| delete_by_id(id, resources)
+            }),
+        )
+        .route(
+            "/id/:id",
+            patch({
+                let resources = resources.clone();
+                move |id, payload| patch_by_id(id, payload, resources)
+            }),
+        )
+        .route(
+            "/id/:id/release-notes",
+            get({
+                let resources = resources.clone();
+                move |id| get_release_notes(id, resources)
+            })
+            .layer(middleware::from_fn(throttle::limit)),
+        )
+        .route(
+            "/list/",
+            get({
+                let resources = resources.clone();
+                move || get_list(resources)
+            }),
+        )
+}
+
+async fn post_new(
+    Json(new_version): Json<NewVersionPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<VersionResponse, NewVersionError> {
+    resources
+        .with_bare_conn(move |connection| {
+            Box::pin(async move {
+                let sql = "INSERT INTO versions (name, release_date) \
+                           VALUES (?, ?) RETURNING id";
+                let row = query(sql)
+                    .bind(&new_version.name)
+                    .bind(&new_version.release_date)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                let id = row.try_get("id")?;
+                Ok(VersionResponse {
+                    id,
+                    name: new_version.name,
+                    release_date: new_version.release_date,
+                })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_by_id(
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<VersionResponse, GetVersionError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let sql =
+                    "SELECT id, name, release_date FROM versions WHERE id = ?";
+                let row =
+                    query(sql).bind(id).fetch_one(&mut **connection).await?;
+                Ok(VersionResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_by_name(
+    Path(name): Path<String>,
+    resources: Arc<Resources>,
+) -> ApiResponse<VersionResponse, GetVersionError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let sql = "SELECT id, name, release_date FROM versions \
+                           WHERE name = ?";
+                let row =
+                    query(sql).bind(&name).fetch_one(&mut **connection).await?;
+                Ok(VersionResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn delete_by_id(
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<VersionResponse, GetVersionError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let sql = "DELETE FROM versions WHERE id = ? \
+                           RETURNING id, name, release_date";
+                let row =
+                    query(sql).bind(id).fetch_one(&mut **connection).await?;
+                Ok(VersionResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn patch_by_id(
+    Path(id): Path<i64>,
+    Json(payload): Json<PatchVersionPayload>,
+    resources: Arc<Resources>,
+) -> ApiResponse<VersionResponse, PatchVersionError> {
+    if payload.name.is_none() && payload.release_date.is_none() {
+        return ApiResponse::new(Err(PatchVersionError::NoFieldsPatched));
+    }
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let sql = "UPDATE versions SET \
+                           name = COALESCE(?, name), \
+                           release_date = COALESCE(?, release_date) \
+                           WHERE id = ? \
+                           RETURNING id, name, release_date";
+                let row = query(sql)
+                    .bind(&payload.name)
+                    .bind(&payload.release_date)
+                    .bind(id)
+                    .fetch_one(&mut **connection)
+                    .await?;
+                Ok(VersionResponse::from_row(&row)?)
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_release_notes(
+    Path(id): Path<i64>,
+    resources: Arc<Resources>,
+) -> ApiResponse<ReleaseNotesResponse, GetVersionError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let notes = release_notes::for_version(connection, id).await?;
+                Ok(ReleaseNotesResponse {
+                    version_id: id,
+                    markdown: notes.to_markdown(),
+                })
+            })
+        })
+        .await
+        .into()
+}
+
+async fn get_list(
+    resources: Arc<Resources>,
+) -> ApiResponse<VersionListResponse, GetVersionError> {
+    resources
+        .with_bare_conn(|connection| {
+            Box::pin(async move {
+                let mut versions = Vec::new();
+                let mut stream = query(
+                    "SELECT id, name, release_date FROM versions ORDER BY id",
+                )
+                .fetch(&mut **connection);
+                while let Some(row) = stream.try_next().await? {
+                    versions.push(VersionResponse::from_row(&row)?);
+                }
+                Ok(VersionListResponse { list: versions })
+            })
+        })
+        .await
+        .into()
+}
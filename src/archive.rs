@@ -0,0 +1,564 @@
+use std::collections::{HashMap, HashSet};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{
+    query,
+    sqlite::SqliteRow,
+    Column,
+    Row,
+    SqliteConnection,
+    TypeInfo,
+    ValueRef,
+};
+use thiserror::Error;
+
+pub const FORMAT: &str = "portable-issuer-export";
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("Archive format {0:?} is not supported")]
+    UnsupportedFormat(String),
+    #[error("Archive format version {0} is not supported")]
+    UnsupportedVersion(u32),
+    #[error("Archive is malformed")]
+    Decode(
+        #[source]
+        #[from]
+        serde_json::Error,
+    ),
+    #[error("Archive refers to {table} {id}, which it does not contain")]
+    MissingReference { table: &'static str, id: i64 },
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+/// Converts a row to a JSON object keyed by column name, keeping the
+/// storage class SQLite gave each value.
+pub(crate) fn row_object(
+    row: &SqliteRow,
+) -> Result<Map<String, Value>, sqlx::Error> {
+    let mut object = Map::new();
+    for column in row.columns() {
+        let index = column.ordinal();
+        let raw = row.try_get_raw(index)?;
+        let value = if raw.is_null() {
+            Value::Null
+        } else {
+            match raw.type_info().name() {
+                "INTEGER" => Value::from(row.try_get::<i64, _>(index)?),
+                "REAL" => Value::from(row.try_get::<f64, _>(index)?),
+                "BLOB" => Value::from(row.try_get::<Vec<u8>, _>(index)?),
+                _ => Value::from(row.try_get::<String, _>(index)?),
+            }
+        };
+        object.insert(column.name().to_owned(), value);
+    }
+    Ok(object)
+}
+
+/// The document produced by `GET /api/v1/export`. Only the rows needed to
+/// recreate issues are read back; unknown tables and columns are ignored.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct Archive {
+    format: String,
+    format_version: u32,
+    #[serde(default)]
+    tables: ArchiveTables,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(default)]
+struct ArchiveTables {
+    issue_statuses: Vec<StatusRow>,
+    issue_types: Vec<TypeRow>,
+    issue_type_statuses: Vec<TypeStatusRow>,
+    labels: Vec<NamedRow>,
+    milestones: Vec<MilestoneRow>,
+    versions: Vec<VersionRow>,
+    users: Vec<NamedRow>,
+    issues: Vec<IssueRow>,
+    issue_labels: Vec<IssueLabelRow>,
+    issue_blockings: Vec<BlockingRow>,
+    issue_checks: Vec<CheckRow>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct NamedRow {
+    id: i64,
+    name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct StatusRow {
+    id: i64,
+    name: String,
+    #[serde(default)]
+    closed: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct TypeRow {
+    id: i64,
+    name: String,
+    #[serde(default)]
+    template: String,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct TypeStatusRow {
+    #[serde(rename = "type")]
+    type_id: i64,
+    status: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct MilestoneRow {
+    id: i64,
+    name: String,
+    #[serde(default)]
+    description: String,
+    due_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct VersionRow {
+    id: i64,
+    name: String,
+    release_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct IssueRow {
+    id: i64,
+    title: String,
+    #[serde(default)]
+    description: String,
+    status: i64,
+    parent: Option<i64>,
+    milestone: Option<i64>,
+    affects_version: Option<i64>,
+    fixed_in_version: Option<i64>,
+    assignee: Option<i64>,
+    #[serde(rename = "type")]
+    type_id: Option<i64>,
+    created_at: Option<i64>,
+    updated_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct IssueLabelRow {
+    issue: i64,
+    label: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct BlockingRow {
+    blocker: i64,
+    blocked: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct CheckRow {
+    issue: i64,
+    name: String,
+    state: String,
+    url: Option<String>,
+    summary: Option<String>,
+    created_at: Option<i64>,
+}
+
+impl Archive {
+    pub fn parse(bytes: &[u8]) -> Result<Self, ArchiveError> {
+        let archive: Self = serde_json::from_slice(bytes)?;
+        archive.check()?;
+        Ok(archive)
+    }
+
+    pub fn check(&self) -> Result<(), ArchiveError> {
+        if self.format != FORMAT {
+            return Err(ArchiveError::UnsupportedFormat(self.format.clone()));
+        }
+        if self.format_version != FORMAT_VERSION {
+            return Err(ArchiveError::UnsupportedVersion(self.format_version));
+        }
+        Ok(())
+    }
+}
+
+/// How many rows of a named kind were created, and how many already existed
+/// under the same name and were used instead.
+#[derive(Debug, Clone, Copy, Default, Serialize, JsonSchema)]
+pub struct MergeCount {
+    pub created: usize,
+    pub reused: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
+pub struct ArchiveReport {
+    pub issues: usize,
+    pub statuses: MergeCount,
+    pub issue_types: MergeCount,
+    pub labels: MergeCount,
+    pub milestones: MergeCount,
+    pub versions: MergeCount,
+    /// Assignees with no local user of the same name, left unassigned.
+    pub unmatched_users: Vec<String>,
+}
+
+/// Archive ids mapped to ids in the database being imported into.
+#[derive(Debug, Default)]
+struct IdMap {
+    table: &'static str,
+    ids: HashMap<i64, i64>,
+}
+
+impl IdMap {
+    fn new(table: &'static str) -> Self {
+        Self { table, ids: HashMap::new() }
+    }
+
+    fn get(&self, id: i64) -> Result<i64, ArchiveError> {
+        self.ids
+            .get(&id)
+            .copied()
+            .ok_or(ArchiveError::MissingReference { table: self.table, id })
+    }
+
+    fn get_optional(
+        &self,
+        id: Option<i64>,
+    ) -> Result<Option<i64>, ArchiveError> {
+        id.map(|id| self.get(id)).transpose()
+    }
+}
+
+async fn existing_id(
+    conn: &mut SqliteConnection,
+    table: &str,
+    name: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    let sql = format!("SELECT id FROM {table} WHERE name = ?");
+    let row = query(&sql).bind(name).fetch_optional(conn).await?;
+    row.map(|row| row.try_get("id")).transpose()
+}
+
+/// Adds the archived issues to the database. Statuses, types, labels,
+/// milestones and versions are matched by name, so importing into a
+/// database that already has them reuses the existing rows; issues always
+/// get new ids. Meant to run inside a transaction, so that a failure leaves
+/// nothing behind.
+pub async fn import(
+    conn: &mut SqliteConnection,
+    archive: &Archive,
+) -> Result<ArchiveReport, ArchiveError> {
+    archive.check()?;
+    let tables = &archive.tables;
+    let mut report = ArchiveReport::default();
+
+    let mut statuses = IdMap::new("status");
+    for status in &tables.issue_statuses {
+        let id = match existing_id(conn, "issue_statuses", &status.name).await?
+        {
+            Some(id) => {
+                report.statuses.reused += 1;
+                id
+            },
+            None => {
+                report.statuses.created += 1;
+                query(
+                    "INSERT INTO issue_statuses (name, closed) VALUES (?, ?) \
+                     RETURNING id",
+                )
+                .bind(&status.name)
+                .bind(status.closed != 0)
+                .fetch_one(&mut *conn)
+                .await?
+                .try_get("id")?
+            },
+        };
+        statuses.ids.insert(status.id, id);
+    }
+
+    let mut issue_types = IdMap::new("issue type");
+    let mut created_types = HashSet::new();
+    for issue_type in &tables.issue_types {
+        let id =
+            match existing_id(conn, "issue_types", &issue_type.name).await? {
+                Some(id) => {
+                    report.issue_types.reused += 1;
+                    id
+                },
+                None => {
+                    report.issue_types.created += 1;
+                    let id = query(
+                    "INSERT INTO issue_types (name, template) VALUES (?, ?) \
+                     RETURNING id",
+                )
+                .bind(&issue_type.name)
+                .bind(&issue_type.template)
+                .fetch_one(&mut *conn)
+                .await?
+                .try_get("id")?;
+                    created_types.insert(id);
+                    id
+                },
+            };
+        issue_types.ids.insert(issue_type.id, id);
+    }
+    // Statuses allowed by types that already existed are left as they are.
+    for allowed in &tables.issue_type_statuses {
+        let type_id = issue_types.get(allowed.type_id)?;
+        if !created_types.contains(&type_id) {
+            continue;
+        }
+        query(
+            "INSERT OR IGNORE INTO issue_type_statuses (type, status) \
+             VALUES (?, ?)",
+        )
+        .bind(type_id)
+        .bind(statuses.get(allowed.status)?)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    let mut labels = IdMap::new("label");
+    for label in &tables.labels {
+        let id = match existing_id(conn, "labels", &label.name).await? {
+            Some(id) => {
+                report.labels.reused += 1;
+                id
+            },
+            None => {
+                report.labels.created += 1;
+                query("INSERT INTO labels (name) VALUES (?) RETURNING id")
+                    .bind(&label.name)
+                    .fetch_one(&mut *conn)
+                    .await?
+                    .try_get("id")?
+            },
+        };
+        labels.ids.insert(label.id, id);
+    }
+
+    let mut milestones = IdMap::new("milestone");
+    for milestone in &tables.milestones {
+        let id = match existing_id(conn, "milestones", &milestone.name).await? {
+            Some(id) => {
+                report.milestones.reused += 1;
+                id
+            },
+            None => {
+                report.milestones.created += 1;
+                query(
+                    "INSERT INTO milestones (name, description, due_date) \
+                     VALUES (?, ?, ?) RETURNING id",
+                )
+                .bind(&milestone.name)
+                .bind(&milestone.description)
+                .bind(&milestone.due_date)
+                .fetch_one(&mut *conn)
+                .await?
+                .try_get("id")?
+            },
+        };
+        milestones.ids.insert(milestone.id, id);
+    }
+
+    let mut versions = IdMap::new("version");
+    for version in &tables.versions {
+        let id = match existing_id(conn, "versions", &version.name).await? {
+            Some(id) => {
+                report.versions.reused += 1;
+                id
+            },
+            None => {
+                report.versions.created += 1;
+                query(
+                    "INSERT INTO versions (name, release_date) VALUES (?, ?) \
+                     RETURNING id",
+                )
+                .bind(&version.name)
+                .bind(&version.release_date)
+                .fetch_one(&mut *conn)
+                .await?
+                .try_get("id")?
+            },
+        };
+        versions.ids.insert(version.id, id);
+    }
+
+    // Users carry no credentials in the archive, so they are never created.
+    let mut users = HashMap::new();
+    let mut user_names = HashMap::new();
+    for user in &tables.users {
+        user_names.insert(user.id, user.name.as_str());
+        if let Some(id) = existing_id(conn, "users", &user.name).await? {
+            users.insert(user.id, id);
+        }
+    }
+
+    let mut issues = IdMap::new("issue");
+    for issue in &tables.issues {
+        let assignee = match issue.assignee {
+            Some(assignee) => {
+                let local = users.get(&assignee).copied();
+                if local.is_none() {
+                    let name = user_names.get(&assignee).ok_or(
+                        ArchiveError::MissingReference {
+                            table: "user",
+                            id: assignee,
+                        },
+                    )?;
+                    if !report.unmatched_users.iter().any(|seen| seen == name) {
+                        report.unmatched_users.push((*name).to_owned());
+                    }
+                }
+                local
+            },
+            None => None,
+        };
+        let id = query(
+            "INSERT INTO issues \
+             (title, description, status, milestone, affects_version, \
+             fixed_in_version, assignee, type, created_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id",
+        )
+        .bind(&issue.title)
+        .bind(&issue.description)
+        .bind(statuses.get(issue.status)?)
+        .bind(milestones.get_optional(issue.milestone)?)
+        .bind(versions.get_optional(issue.affects_version)?)
+        .bind(versions.get_optional(issue.fixed_in_version)?)
+        .bind(assignee)
+        .bind(issue_types.get_optional(issue.type_id)?)
+        .bind(issue.created_at)
+        .bind(issue.updated_at)
+        .fetch_one(&mut *conn)
+        .await?
+        .try_get("id")?;
+        issues.ids.insert(issue.id, id);
+        report.issues += 1;
+    }
+    // Parents may come after their children, so they are linked once every
+    // issue exists. Setting the parent touches `updated_at`, which is then
+    // put back.
+    for issue in &tables.issues {
+        let Some(parent) = issue.parent else {
+            continue;
+        };
+        let id = issues.get(issue.id)?;
+        query("UPDATE issues SET parent = ? WHERE id = ?")
+            .bind(issues.get(parent)?)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+        query("UPDATE issues SET updated_at = ? WHERE id = ?")
+            .bind(issue.updated_at)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    for link in &tables.issue_labels {
+        query(
+            "INSERT OR IGNORE INTO issue_labels (issue, label) VALUES (?, ?)",
+        )
+        .bind(issues.get(link.issue)?)
+        .bind(labels.get(link.label)?)
+        .execute(&mut *conn)
+        .await?;
+    }
+    for blocking in &tables.issue_blockings {
+        query("INSERT INTO issue_blockings (blocker, blocked) VALUES (?, ?)")
+            .bind(issues.get(blocking.blocker)?)
+            .bind(issues.get(blocking.blocked)?)
+            .execute(&mut *conn)
+            .await?;
+    }
+    for check in &tables.issue_checks {
+        query(
+            "INSERT INTO issue_checks \
+             (issue, name, state, url, summary, created_at) \
+             VALUES (?, ?, ?, ?, ?, COALESCE(?, unixepoch()))",
+        )
+        .bind(issues.get(check.issue)?)
+        .bind(&check.name)
+        .bind(&check.state)
+        .bind(&check.url)
+        .bind(&check.summary)
+        .bind(check.created_at)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migration::migrated_pool;
+    use serde_json::json;
+
+    fn archive(tables: serde_json::Value) -> Archive {
+        let document = json!({
+            "format": FORMAT,
+            "format_version": FORMAT_VERSION,
+            "tables": tables,
+        });
+        Archive::parse(document.to_string().as_bytes()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reimport_reuses_rows_with_same_name() {
+        let pool = migrated_pool().await;
+        let archive = archive(json!({
+            "issue_statuses": [{ "id": 7, "name": "open", "closed": 0 }],
+            "labels": [{ "id": 3, "name": "bug" }],
+            "issues": [{ "id": 5, "title": "Crash", "status": 7 }],
+            "issue_labels": [{ "issue": 5, "label": 3 }],
+        }));
+        let mut conn = pool.acquire().await.unwrap();
+        let first = import(&mut conn, &archive).await.unwrap();
+        assert_eq!(first.statuses.created, 1);
+        assert_eq!(first.labels.created, 1);
+        let second = import(&mut conn, &archive).await.unwrap();
+        assert_eq!(second.statuses.reused, 1);
+        assert_eq!(second.labels.reused, 1);
+        assert_eq!(second.issues, 1);
+        let links: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM issue_labels")
+                .fetch_one(&mut *conn)
+                .await
+                .unwrap();
+        assert_eq!(links, 2);
+    }
+
+    #[tokio::test]
+    async fn dangling_reference_is_rejected() {
+        let pool = migrated_pool().await;
+        let archive = archive(json!({
+            "issues": [{ "id": 1, "title": "Crash", "status": 9 }],
+        }));
+        let mut conn = pool.acquire().await.unwrap();
+        let result = import(&mut conn, &archive).await;
+        assert!(matches!(
+            result,
+            Err(ArchiveError::MissingReference { table: "status", id: 9 })
+        ));
+    }
+
+    #[test]
+    fn other_formats_are_rejected() {
+        let document = json!({ "format": "other", "format_version": 1 });
+        let result = Archive::parse(document.to_string().as_bytes());
+        assert!(matches!(result, Err(ArchiveError::UnsupportedFormat(_))));
+    }
+}
@@ -0,0 +1,119 @@
+use axum::http::{
+    header::{ETAG, RETRY_AFTER},
+    HeaderName,
+    HeaderValue,
+    Method,
+};
+use thiserror::Error;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::request_id::REQUEST_ID_HEADER;
+
+/// Allows any origin when given as the only origin.
+pub const ANY_ORIGIN: &str = "*";
+
+const DEFAULT_METHODS: &[Method] = &[
+    Method::GET,
+    Method::HEAD,
+    Method::POST,
+    Method::PUT,
+    Method::PATCH,
+    Method::DELETE,
+];
+
+const DEFAULT_HEADERS: &[&str] = &[
+    "authorization",
+    "content-type",
+    "if-match",
+    "if-none-match",
+    "x-request-id",
+    "x-response-casing",
+    "x-response-envelope",
+];
+
+#[derive(Debug, Error)]
+pub enum CorsConfigError {
+    #[error("Invalid CORS origin {0:?}")]
+    InvalidOrigin(String),
+    #[error("Wildcard CORS origin cannot be combined with other origins")]
+    MixedWildcard,
+    #[error("Invalid CORS method {0:?}")]
+    InvalidMethod(String),
+    #[error("Invalid CORS header {0:?}")]
+    InvalidHeader(String),
+}
+
+/// Cross-origin access granted to browsers on other origins. Empty method
+/// and header lists fall back to what the API itself uses.
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    origins: Option<Vec<HeaderValue>>,
+    methods: Vec<Method>,
+    headers: Vec<HeaderName>,
+}
+
+impl CorsConfig {
+    pub fn new(
+        origins: &[String],
+        methods: &[String],
+        headers: &[String],
+    ) -> Result<Self, CorsConfigError> {
+        let origins = match origins {
+            [origin] if origin == ANY_ORIGIN => None,
+            _ if origins.iter().any(|origin| origin == ANY_ORIGIN) => {
+                return Err(CorsConfigError::MixedWildcard);
+            },
+            _ => Some(
+                origins
+                    .iter()
+                    .map(|origin| {
+                        HeaderValue::from_str(origin).map_err(|_| {
+                            CorsConfigError::InvalidOrigin(origin.clone())
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+        };
+        let methods = if methods.is_empty() {
+            DEFAULT_METHODS.to_vec()
+        } else {
+            methods
+                .iter()
+                .map(|method| {
+                    Method::from_bytes(method.as_bytes()).map_err(|_| {
+                        CorsConfigError::InvalidMethod(method.clone())
+                    })
+                })
+                .collect::<Result<_, _>>()?
+        };
+        let headers = if headers.is_empty() {
+            DEFAULT_HEADERS
+                .iter()
+                .copied()
+                .map(HeaderName::from_static)
+                .collect()
+        } else {
+            headers
+                .iter()
+                .map(|header| {
+                    HeaderName::from_bytes(header.as_bytes()).map_err(|_| {
+                        CorsConfigError::InvalidHeader(header.clone())
+                    })
+                })
+                .collect::<Result<_, _>>()?
+        };
+        Ok(Self { origins, methods, headers })
+    }
+
+    pub fn layer(&self) -> CorsLayer {
+        let origin = match &self.origins {
+            Some(origins) => AllowOrigin::list(origins.iter().cloned()),
+            None => AllowOrigin::any(),
+        };
+        CorsLayer::new()
+            .allow_origin(origin)
+            .allow_methods(self.methods.clone())
+            .allow_headers(self.headers.clone())
+            .expose_headers([ETAG, RETRY_AFTER, REQUEST_ID_HEADER])
+    }
+}
@@ -0,0 +1,122 @@
+use sqlx::{query, Pool, Row};
+
+use crate::RDBMS;
+
+const STATUSES: &[(&str, bool)] =
+    &[("open", false), ("in progress", false), ("closed", true)];
+
+const LABELS: &[&str] = &["bug", "feature", "documentation"];
+
+const MILESTONES: &[(&str, &str)] =
+    &[("1.0", "First stable release"), ("1.1", "Follow-up improvements")];
+
+/// Status, milestone and labels refer to positions in the lists above.
+struct DemoIssue {
+    title: &'static str,
+    description: &'static str,
+    status: usize,
+    milestone: Option<usize>,
+    labels: &'static [usize],
+}
+
+const ISSUES: &[DemoIssue] = &[
+    DemoIssue {
+        title: "Crash when the database file is read-only",
+        description: "Starting the server against a read-only database \
+                      panics instead of reporting an error.",
+        status: 0,
+        milestone: Some(0),
+        labels: &[0],
+    },
+    DemoIssue {
+        title: "Dark mode for the web interface",
+        description: "Follow the system color scheme.",
+        status: 1,
+        milestone: Some(1),
+        labels: &[1],
+    },
+    DemoIssue {
+        title: "Document the command line options",
+        description: "Every flag should be described in the README.",
+        status: 0,
+        milestone: Some(0),
+        labels: &[2],
+    },
+    DemoIssue {
+        title: "Labels are not sorted by name",
+        description: "The label list comes back in insertion order.",
+        status: 2,
+        milestone: Some(0),
+        labels: &[0],
+    },
+    DemoIssue {
+        title: "Export issues as CSV",
+        description: "Counterpart of the CSV importer.",
+        status: 0,
+        milestone: None,
+        labels: &[1],
+    },
+];
+
+/// Fills an empty database with a few statuses, labels, milestones and
+/// issues, so that a demo deployment has something to show.
+pub async fn seed(pool: &Pool<RDBMS>) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let mut status_ids = Vec::new();
+    for (name, closed) in STATUSES {
+        let id: i64 = query(
+            "INSERT INTO issue_statuses (name, closed) VALUES (?, ?) \
+             RETURNING id",
+        )
+        .bind(name)
+        .bind(closed)
+        .fetch_one(&mut *transaction)
+        .await
+        .and_then(|row| row.try_get("id"))?;
+        status_ids.push(id);
+    }
+    let mut label_ids = Vec::new();
+    for name in LABELS {
+        let id: i64 =
+            query("INSERT INTO labels (name) VALUES (?) RETURNING id")
+                .bind(name)
+                .fetch_one(&mut *transaction)
+                .await
+                .and_then(|row| row.try_get("id"))?;
+        label_ids.push(id);
+    }
+    let mut milestone_ids = Vec::new();
+    for (name, description) in MILESTONES {
+        let id: i64 = query(
+            "INSERT INTO milestones (name, description) VALUES (?, ?) \
+             RETURNING id",
+        )
+        .bind(name)
+        .bind(description)
+        .fetch_one(&mut *transaction)
+        .await
+        .and_then(|row| row.try_get("id"))?;
+        milestone_ids.push(id);
+    }
+    for issue in ISSUES {
+        let id: i64 = query(
+            "INSERT INTO issues (title, description, status, milestone) \
+             VALUES (?, ?, ?, ?) RETURNING id",
+        )
+        .bind(issue.title)
+        .bind(issue.description)
+        .bind(status_ids[issue.status])
+        .bind(issue.milestone.map(|index| milestone_ids[index]))
+        .fetch_one(&mut *transaction)
+        .await
+        .and_then(|row| row.try_get("id"))?;
+        for label in issue.labels {
+            query("INSERT INTO issue_labels (issue, label) VALUES (?, ?)")
+                .bind(id)
+                .bind(label_ids[*label])
+                .execute(&mut *transaction)
+                .await?;
+        }
+    }
+    transaction.commit().await
+}
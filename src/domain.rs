@@ -0,0 +1,254 @@
+//! Issue tracker values and the rules they obey, kept apart from storage
+//! and transport. The API maps request payloads into these types before
+//! anything is written.
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DomainError {
+    #[error("Name must not be blank")]
+    BlankName,
+    #[error("Title must not be blank")]
+    BlankTitle,
+}
+
+/// Name of a status, label, issue type or saved reply, without surrounding
+/// whitespace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Name(String);
+
+impl Name {
+    pub fn parse(name: &str) -> Result<Self, DomainError> {
+        match name.trim() {
+            "" => Err(DomainError::BlankName),
+            name => Ok(Self(name.to_owned())),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Issue title, without surrounding whitespace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Title(String);
+
+impl Title {
+    pub fn parse(title: &str) -> Result<Self, DomainError> {
+        match title.trim() {
+            "" => Err(DomainError::BlankTitle),
+            title => Ok(Self(title.to_owned())),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewStatus {
+    pub name: Name,
+    pub closed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusPatch {
+    pub name: Option<Name>,
+    pub closed: Option<bool>,
+}
+
+impl StatusPatch {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.closed.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewLabel {
+    pub name: Name,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewIssue {
+    pub title: Title,
+    pub description: String,
+    pub status_id: i64,
+    pub affects_version_id: Option<i64>,
+    pub fixed_in_version_id: Option<i64>,
+    pub assignee_id: Option<i64>,
+    pub type_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Changes to an issue. Optional references are `None` when left alone and
+/// `Some(None)` when cleared.
+pub struct IssuePatch {
+    pub title: Option<Title>,
+    pub description: Option<String>,
+    pub status_id: Option<i64>,
+    pub affects_version_id: Option<Option<i64>>,
+    pub fixed_in_version_id: Option<Option<i64>>,
+    pub assignee_id: Option<Option<i64>>,
+    pub type_id: Option<Option<i64>>,
+}
+
+impl IssuePatch {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.description.is_none()
+            && self.status_id.is_none()
+            && self.affects_version_id.is_none()
+            && self.fixed_in_version_id.is_none()
+            && self.assignee_id.is_none()
+            && self.type_id.is_none()
+    }
+}
+
+/// What deciding on a change of status of an issue depends on, loaded
+/// beforehand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusChange {
+    /// Status the issue ends up in.
+    pub status_id: i64,
+    /// Statuses its type allows, empty when any status is.
+    pub allowed_status_ids: Vec<i64>,
+    /// Whether the issue moves into a closed status.
+    pub closes: bool,
+    /// Open issues that block the issue.
+    pub open_blocker_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatusChangeError {
+    NotAllowed,
+    Blocked(Vec<i64>),
+}
+
+/// An issue may only be in a status its type allows, and may only be closed
+/// once its blockers are, unless `force` lets an administrator close it
+/// anyway.
+pub fn check_status_change(
+    change: &StatusChange,
+    force: bool,
+) -> Result<(), StatusChangeError> {
+    if !change.allowed_status_ids.is_empty()
+        && !change.allowed_status_ids.contains(&change.status_id)
+    {
+        return Err(StatusChangeError::NotAllowed);
+    }
+    if change.closes && !force && !change.open_blocker_ids.is_empty() {
+        return Err(StatusChangeError::Blocked(
+            change.open_blocker_ids.clone(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_status_change,
+        DomainError,
+        IssuePatch,
+        Name,
+        StatusChange,
+        StatusChangeError,
+        StatusPatch,
+        Title,
+    };
+
+    #[test]
+    fn name_is_trimmed() {
+        assert_eq!(
+            Name::parse("  in progress\n").unwrap().as_str(),
+            "in progress"
+        );
+    }
+
+    #[test]
+    fn blank_name_is_rejected() {
+        assert_eq!(Name::parse(""), Err(DomainError::BlankName));
+        assert_eq!(Name::parse(" \t\n"), Err(DomainError::BlankName));
+    }
+
+    #[test]
+    fn title_is_trimmed() {
+        assert_eq!(
+            Title::parse(" Crash on startup ").unwrap().as_str(),
+            "Crash on startup"
+        );
+    }
+
+    #[test]
+    fn blank_title_is_rejected() {
+        assert_eq!(Title::parse("   "), Err(DomainError::BlankTitle));
+    }
+
+    #[test]
+    fn status_patch_is_empty_without_fields() {
+        let patch = StatusPatch { name: None, closed: None };
+        assert!(patch.is_empty());
+        let patch = StatusPatch { name: None, closed: Some(false) };
+        assert!(!patch.is_empty());
+    }
+
+    #[test]
+    fn issue_patch_is_empty_without_fields() {
+        let mut patch = IssuePatch {
+            title: None,
+            description: None,
+            status_id: None,
+            affects_version_id: None,
+            fixed_in_version_id: None,
+            assignee_id: None,
+            type_id: None,
+        };
+        assert!(patch.is_empty());
+        patch.description = Some(String::new());
+        assert!(!patch.is_empty());
+        patch.description = None;
+        patch.assignee_id = Some(None);
+        assert!(!patch.is_empty());
+    }
+
+    fn status_change() -> StatusChange {
+        StatusChange {
+            status_id: 2,
+            allowed_status_ids: Vec::new(),
+            closes: true,
+            open_blocker_ids: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn any_status_is_allowed_without_a_list() {
+        assert_eq!(check_status_change(&status_change(), false), Ok(()));
+    }
+
+    #[test]
+    fn status_outside_the_allowed_ones_is_rejected() {
+        let mut change = status_change();
+        change.allowed_status_ids = vec![1, 3];
+        assert_eq!(
+            check_status_change(&change, true),
+            Err(StatusChangeError::NotAllowed)
+        );
+        change.allowed_status_ids.push(2);
+        assert_eq!(check_status_change(&change, false), Ok(()));
+    }
+
+    #[test]
+    fn closing_with_open_blockers_is_rejected_unless_forced() {
+        let mut change = status_change();
+        change.open_blocker_ids = vec![4, 7];
+        assert_eq!(
+            check_status_change(&change, false),
+            Err(StatusChangeError::Blocked(vec![4, 7]))
+        );
+        assert_eq!(check_status_change(&change, true), Ok(()));
+        change.closes = false;
+        assert_eq!(check_status_change(&change, false), Ok(()));
+    }
+}
@@ -0,0 +1,449 @@
+use std::path::Path;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use futures::TryStreamExt;
+use sha2::{Digest, Sha256};
+use sqlx::{
+    query,
+    query_scalar,
+    sqlite::SqliteConnectOptions,
+    Pool,
+    Row,
+    SqlitePool,
+};
+use thiserror::Error;
+
+use crate::RDBMS;
+
+/// A table kept by `retain`, with the comma-separated columns to keep, or
+/// `*` for all of them.
+pub struct KeptTable {
+    pub table: &'static str,
+    pub columns: &'static str,
+}
+
+struct ScrubbedColumn {
+    table: &'static str,
+    column: &'static str,
+    prefix: &'static str,
+}
+
+const SCRUBBED_COLUMNS: &[ScrubbedColumn] = &[
+    ScrubbedColumn { table: "users", column: "name", prefix: "user" },
+    ScrubbedColumn {
+        table: "users",
+        column: "password_hash",
+        prefix: "password",
+    },
+    ScrubbedColumn { table: "api_tokens", column: "name", prefix: "token" },
+    ScrubbedColumn {
+        table: "api_tokens",
+        column: "token_hash",
+        prefix: "token-hash",
+    },
+    ScrubbedColumn {
+        table: "sessions",
+        column: "token_hash",
+        prefix: "session",
+    },
+    ScrubbedColumn {
+        table: "user_identities",
+        column: "subject",
+        prefix: "subject",
+    },
+    ScrubbedColumn {
+        table: "issue_statuses",
+        column: "name",
+        prefix: "status",
+    },
+    ScrubbedColumn { table: "issue_types", column: "name", prefix: "type" },
+    ScrubbedColumn {
+        table: "issue_types",
+        column: "template",
+        prefix: "template",
+    },
+    ScrubbedColumn { table: "labels", column: "name", prefix: "label" },
+    ScrubbedColumn {
+        table: "maintenance",
+        column: "message",
+        prefix: "maintenance",
+    },
+    ScrubbedColumn { table: "milestones", column: "name", prefix: "milestone" },
+    ScrubbedColumn {
+        table: "milestones",
+        column: "description",
+        prefix: "description",
+    },
+    ScrubbedColumn { table: "versions", column: "name", prefix: "version" },
+    ScrubbedColumn { table: "issues", column: "title", prefix: "title" },
+    ScrubbedColumn {
+        table: "issues",
+        column: "description",
+        prefix: "description",
+    },
+    ScrubbedColumn { table: "issue_checks", column: "url", prefix: "url" },
+    ScrubbedColumn {
+        table: "issue_checks",
+        column: "summary",
+        prefix: "summary",
+    },
+    ScrubbedColumn {
+        table: "i18n_overrides",
+        column: "value",
+        prefix: "message",
+    },
+    ScrubbedColumn {
+        table: "canned_replies",
+        column: "name",
+        prefix: "reply-name",
+    },
+    ScrubbedColumn { table: "canned_replies", column: "body", prefix: "reply" },
+    ScrubbedColumn { table: "signing_keys", column: "name", prefix: "client" },
+    ScrubbedColumn {
+        table: "signing_keys",
+        column: "secret",
+        prefix: "secret",
+    },
+];
+
+#[derive(Debug, Error)]
+pub enum DumpError {
+    #[error("Output file already exists")]
+    OutputExists,
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+pub async fn dump(
+    pool: &Pool<RDBMS>,
+    output: &Path,
+    anonymize: bool,
+) -> Result<(), DumpError> {
+    let mut connection = pool.acquire().await?;
+    let result = query("VACUUM INTO ?")
+        .bind(output.to_string_lossy().into_owned())
+        .execute(&mut *connection)
+        .await;
+    if result.is_err() {
+        // The driver steps a failed statement once more before it notices
+        // that nobody waits for it, so wait for the connection to be idle
+        // before the caller is free to touch `output`.
+        query("SELECT 1").execute(&mut *connection).await?;
+    }
+    drop(connection);
+    // SQLite refuses to overwrite a file that is not empty, and it checks this
+    // when it opens the file rather than beforehand. A database file fails
+    // with a message, while any other content fails as `SQLITE_NOTADB`.
+    result.map_err(|error| match &error {
+        sqlx::Error::Database(database)
+            if database.message() == "output file already exists"
+                || database.code().as_deref() == Some("26") =>
+        {
+            DumpError::OutputExists
+        },
+        _ => DumpError::Sqlx(error),
+    })?;
+    if anonymize {
+        let options = SqliteConnectOptions::new().filename(output);
+        let output_pool = SqlitePool::connect_with(options).await?;
+        // A fresh salt per dump keeps equal values equal within the dump,
+        // while short or guessable values cannot be looked up by hashing
+        // candidates.
+        let mut salt = [0; 16];
+        OsRng.fill_bytes(&mut salt);
+        scrub(&output_pool, &salt).await?;
+        query("VACUUM").execute(&output_pool).await?;
+        output_pool.close().await;
+    }
+    Ok(())
+}
+
+/// Reduces the dump at `output` to `tables`, dropping every other table,
+/// column and trigger. The migration history stays so that the copy still
+/// records its schema version.
+pub async fn retain(
+    output: &Path,
+    tables: &[KeptTable],
+) -> Result<(), DumpError> {
+    let options =
+        SqliteConnectOptions::new().filename(output).foreign_keys(false);
+    let output_pool = SqlitePool::connect_with(options).await?;
+    let result = strip(&output_pool, tables).await;
+    output_pool.close().await;
+    Ok(result?)
+}
+
+async fn strip(
+    pool: &Pool<RDBMS>,
+    tables: &[KeptTable],
+) -> Result<(), sqlx::Error> {
+    let triggers: Vec<String> =
+        query_scalar("SELECT name FROM sqlite_schema WHERE type = 'trigger'")
+            .fetch_all(pool)
+            .await?;
+    for trigger in triggers {
+        query(&format!("DROP TRIGGER \"{trigger}\"")).execute(pool).await?;
+    }
+    // Virtual tables come first, since dropping them also drops their
+    // shadow tables.
+    let existing: Vec<String> = query_scalar(
+        "SELECT name FROM sqlite_schema
+        WHERE type = 'table'
+            AND name NOT LIKE 'sqlite_%'
+            AND name <> '_sqlx_migrations'
+        ORDER BY sql LIKE 'CREATE VIRTUAL%' DESC",
+    )
+    .fetch_all(pool)
+    .await?;
+    for table in existing {
+        let Some(kept) = tables.iter().find(|kept| kept.table == table) else {
+            query(&format!("DROP TABLE IF EXISTS \"{table}\""))
+                .execute(pool)
+                .await?;
+            continue;
+        };
+        if kept.columns == "*" {
+            continue;
+        }
+        let columns: Vec<&str> =
+            kept.columns.split(',').map(str::trim).collect();
+        let existing: Vec<String> =
+            query_scalar("SELECT name FROM pragma_table_info(?)")
+                .bind(&table)
+                .fetch_all(pool)
+                .await?;
+        for column in existing {
+            if !columns.contains(&column.as_str()) {
+                query(&format!(
+                    "ALTER TABLE \"{table}\" DROP COLUMN \"{column}\""
+                ))
+                .execute(pool)
+                .await?;
+            }
+        }
+    }
+    // Dropped rows linger in free pages until the file is rebuilt.
+    query("VACUUM").execute(pool).await?;
+    Ok(())
+}
+
+async fn scrub(pool: &Pool<RDBMS>, salt: &[u8]) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    for scrubbed in SCRUBBED_COLUMNS {
+        let select = format!(
+            "SELECT rowid AS rowid, {column} FROM {table}",
+            column = scrubbed.column,
+            table = scrubbed.table,
+        );
+        let mut replacements = Vec::new();
+        let mut stream = query(&select).fetch(&mut *transaction);
+        while let Some(row) = stream.try_next().await? {
+            let rowid: i64 = row.try_get("rowid")?;
+            let value: Option<String> = row.try_get(scrubbed.column)?;
+            if let Some(value) = value {
+                replacements
+                    .push((rowid, hashed(salt, scrubbed.prefix, &value)));
+            }
+        }
+        drop(stream);
+
+        let update = format!(
+            "UPDATE {table} SET {column} = ? WHERE rowid = ?",
+            column = scrubbed.column,
+            table = scrubbed.table,
+        );
+        for (rowid, replacement) in replacements {
+            query(&update)
+                .bind(replacement)
+                .bind(rowid)
+                .execute(&mut *transaction)
+                .await?;
+        }
+    }
+    // Deleting from an external content index only records tombstones, so
+    // the original terms would survive in the index until it is rebuilt.
+    query("INSERT INTO issues_fts (issues_fts) VALUES ('rebuild')")
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await
+}
+
+fn hashed(salt: &[u8], prefix: &str, value: &str) -> String {
+    let digest =
+        Sha256::new().chain_update(salt).chain_update(value).finalize();
+    let mut output = format!("{prefix}-");
+    for byte in &digest[..8] {
+        output.push_str(&format!("{byte:02x}"));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::fs;
+
+    use super::*;
+    use crate::migration::{migrated_pool, MIGRATOR};
+
+    /// Text columns copied as they are: identifiers, enumerations and dates
+    /// that neither identify anyone nor carry free text.
+    const KEPT_COLUMNS: &[(&str, &str)] = &[
+        ("_sqlx_migrations", "description"),
+        ("i18n_overrides", "lang"),
+        ("i18n_overrides", "key"),
+        ("issue_checks", "name"),
+        ("issue_checks", "state"),
+        ("milestones", "due_date"),
+        ("signing_keys", "key_id"),
+        ("sync_changes", "entity"),
+        ("sync_changes", "global_id"),
+        ("sync_changes", "origin"),
+        ("sync_ids", "entity"),
+        ("sync_ids", "global_id"),
+        ("sync_instance", "instance_id"),
+        ("sync_peers", "instance_id"),
+        ("sync_pending_parents", "parent"),
+        ("triage_session_items", "decision"),
+        ("user_identities", "issuer"),
+        ("versions", "release_date"),
+    ];
+
+    #[tokio::test]
+    async fn dump_leaves_existing_output_alone() {
+        let dir = std::env::temp_dir();
+        let database =
+            dir.join(format!("portable-issuer-dump-{}.db", std::process::id()));
+        let output = dir
+            .join(format!("portable-issuer-dump-{}.bin", std::process::id()));
+        let options = SqliteConnectOptions::new()
+            .filename(&database)
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        MIGRATOR.run(&pool).await.unwrap();
+        fs::write(&output, "keep").await.unwrap();
+        let result = dump(&pool, &output, false).await;
+        let contents = fs::read(&output).await.unwrap();
+        fs::remove_file(&output).await.unwrap();
+        assert!(matches!(result, Err(DumpError::OutputExists)));
+        assert_eq!(contents, b"keep");
+
+        dump(&pool, &output, false).await.unwrap();
+        let size = fs::metadata(&output).await.unwrap().len();
+        let result = dump(&pool, &output, false).await;
+        fs::remove_file(&output).await.unwrap();
+        assert!(matches!(result, Err(DumpError::OutputExists)));
+        pool.close().await;
+        fs::remove_file(&database).await.unwrap();
+        assert!(size > 0);
+    }
+
+    #[tokio::test]
+    async fn scrub_replaces_credentials() {
+        let pool = migrated_pool().await;
+        query("INSERT INTO users (name, password_hash) VALUES ('ann', 'h')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        scrub(&pool, b"salt").await.unwrap();
+        let row = query("SELECT name, password_hash FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            row.get::<String, _>("name"),
+            hashed(b"salt", "user", "ann")
+        );
+        assert_eq!(
+            row.get::<String, _>("password_hash"),
+            hashed(b"salt", "password", "h")
+        );
+        assert_ne!(
+            hashed(b"salt", "user", "ann"),
+            hashed(b"pepper", "user", "ann")
+        );
+    }
+
+    #[tokio::test]
+    async fn strip_drops_everything_not_kept() {
+        let pool = migrated_pool().await;
+        query("PRAGMA foreign_keys = OFF").execute(&pool).await.unwrap();
+        query("INSERT INTO users (name, password_hash) VALUES ('ann', 'h')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let tables = [
+            KeptTable { table: "users", columns: "id, name" },
+            KeptTable { table: "labels", columns: "*" },
+        ];
+        strip(&pool, &tables).await.unwrap();
+        let remaining: Vec<String> = query_scalar(
+            "SELECT name FROM sqlite_schema \
+             WHERE type IN ('table', 'trigger') AND name NOT LIKE 'sqlite_%' \
+             ORDER BY name",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(remaining, ["_sqlx_migrations", "labels", "users"]);
+        let row = query("SELECT * FROM users").fetch_one(&pool).await.unwrap();
+        assert_eq!(row.columns().len(), 2);
+        assert_eq!(row.get::<String, _>("name"), "ann");
+    }
+
+    #[tokio::test]
+    async fn scrub_rebuilds_search_index() {
+        let pool = migrated_pool().await;
+        query("INSERT INTO issue_statuses (name) VALUES ('open')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        query(
+            "INSERT INTO issues (title, description, status) \
+             VALUES ('hush', 'confidential', 1)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        scrub(&pool, b"salt").await.unwrap();
+        let leaked: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM issues_fts_data \
+             WHERE instr(block, CAST('confidenti' AS BLOB)) > 0",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(leaked, 0);
+    }
+
+    #[tokio::test]
+    async fn every_text_column_is_classified() {
+        let pool = migrated_pool().await;
+        let mut stream = query(
+            "SELECT m.name AS tbl, p.name AS col FROM sqlite_master m \
+             JOIN pragma_table_info(m.name) p \
+             WHERE m.type = 'table' AND p.type LIKE '%TEXT%'",
+        )
+        .fetch(&pool);
+        let mut unclassified = Vec::new();
+        while let Some(row) = stream.try_next().await.unwrap() {
+            let table: String = row.get("tbl");
+            let column: String = row.get("col");
+            let scrubbed = SCRUBBED_COLUMNS.iter().any(|scrubbed| {
+                scrubbed.table == table && scrubbed.column == column
+            });
+            let kept =
+                KEPT_COLUMNS.contains(&(table.as_str(), column.as_str()));
+            if !scrubbed && !kept {
+                unclassified.push(format!("{table}.{column}"));
+            }
+        }
+        assert!(
+            unclassified.is_empty(),
+            "text columns neither scrubbed nor kept: {unclassified:?}",
+        );
+    }
+}
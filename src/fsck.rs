@@ -0,0 +1,261 @@
+use std::path::Path;
+
+use futures::TryStreamExt;
+use serde::Serialize;
+use sqlx::{query, Pool, Row};
+use thiserror::Error;
+use tokio::{fs, io};
+
+use crate::RDBMS;
+
+struct OrphanKind {
+    name: &'static str,
+    table: &'static str,
+    select: &'static str,
+    repair: &'static str,
+    dependents: &'static [Dependent],
+}
+
+/// Rows that foreign key actions delete or change along with a repaired
+/// orphan. `select` takes the id of the orphan as its only parameter.
+struct Dependent {
+    table: &'static str,
+    select: &'static str,
+}
+
+const ISSUE_DEPENDENTS: &[Dependent] = &[
+    Dependent {
+        table: "issues",
+        select: "SELECT json_object('id', id, 'parent', parent) AS data
+            FROM issues WHERE parent = ?1",
+    },
+    Dependent {
+        table: "issue_blockings",
+        select: "SELECT json_object(
+                'id', id,
+                'blocker', blocker,
+                'blocked', blocked
+            ) AS data
+            FROM issue_blockings WHERE blocker = ?1 OR blocked = ?1",
+    },
+    Dependent {
+        table: "issue_checks",
+        select: "SELECT json_object(
+                'id', id,
+                'issue', issue,
+                'name', name,
+                'state', state,
+                'url', url,
+                'summary', summary,
+                'created_at', created_at
+            ) AS data
+            FROM issue_checks WHERE issue = ?1",
+    },
+    Dependent {
+        table: "issue_labels",
+        select: "SELECT json_object('issue', issue, 'label', label) AS data
+            FROM issue_labels WHERE issue = ?1",
+    },
+    Dependent {
+        table: "triage_session_items",
+        select: "SELECT json_object(
+                'session', session,
+                'issue', issue,
+                'decision', decision
+            ) AS data
+            FROM triage_session_items WHERE issue = ?1",
+    },
+    Dependent {
+        table: "sync_pending_parents",
+        select: "SELECT json_object('issue', issue, 'parent', parent) AS data
+            FROM sync_pending_parents WHERE issue = ?1",
+    },
+];
+
+const ORPHAN_KINDS: &[OrphanKind] = &[
+    OrphanKind {
+        name: "issue without status",
+        table: "issues",
+        select: "SELECT issues.id AS id, json_object(
+                'id', issues.id,
+                'title', issues.title,
+                'description', issues.description,
+                'status', issues.status,
+                'parent', issues.parent,
+                'milestone', issues.milestone,
+                'affects_version', issues.affects_version,
+                'fixed_in_version', issues.fixed_in_version,
+                'assignee', issues.assignee,
+                'type', issues.type,
+                'created_at', issues.created_at,
+                'updated_at', issues.updated_at,
+                'version', issues.version
+            ) AS data
+            FROM issues
+            LEFT JOIN issue_statuses ON issue_statuses.id = issues.status
+            WHERE issue_statuses.id IS NULL",
+        repair: "DELETE FROM issues WHERE id = ?",
+        dependents: ISSUE_DEPENDENTS,
+    },
+    OrphanKind {
+        name: "issue with missing parent",
+        table: "issues",
+        select: "SELECT child.id AS id, json_object(
+                'id', child.id,
+                'parent', child.parent
+            ) AS data
+            FROM issues AS child
+            LEFT JOIN issues AS parent ON parent.id = child.parent
+            WHERE child.parent IS NOT NULL AND parent.id IS NULL",
+        repair: "UPDATE issues SET parent = NULL, version = version + 1
+            WHERE id = ?",
+        dependents: &[],
+    },
+    OrphanKind {
+        name: "blocking with missing issue",
+        table: "issue_blockings",
+        select: "SELECT issue_blockings.id AS id, json_object(
+                'id', issue_blockings.id,
+                'blocker', issue_blockings.blocker,
+                'blocked', issue_blockings.blocked
+            ) AS data
+            FROM issue_blockings
+            LEFT JOIN issues AS blocker ON blocker.id = issue_blockings.blocker
+            LEFT JOIN issues AS blocked ON blocked.id = issue_blockings.blocked
+            WHERE blocker.id IS NULL OR blocked.id IS NULL",
+        repair: "DELETE FROM issue_blockings WHERE id = ?",
+        dependents: &[],
+    },
+];
+
+#[derive(Debug, Error)]
+pub enum FsckError {
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+    #[error("Failed to encode report")]
+    Encode(#[source] serde_json::Error),
+    #[error("Failed to write report file")]
+    WriteReport(#[source] io::Error),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ForeignKeyViolation {
+    pub table: String,
+    pub rowid: Option<i64>,
+    pub parent: String,
+    pub constraint_index: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Orphan {
+    pub kind: &'static str,
+    pub table: &'static str,
+    pub id: i64,
+    pub data: serde_json::Value,
+    /// Rows the repair deletes or changes along with this one.
+    pub dependents: Vec<DependentRow>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DependentRow {
+    pub table: &'static str,
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+    pub integrity_errors: Vec<String>,
+    pub foreign_key_violations: Vec<ForeignKeyViolation>,
+    pub orphans: Vec<Orphan>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.integrity_errors.is_empty()
+            && self.foreign_key_violations.is_empty()
+            && self.orphans.is_empty()
+    }
+}
+
+pub async fn check(pool: &Pool<RDBMS>) -> Result<Report, FsckError> {
+    let mut conn = pool.acquire().await?;
+    let mut report = Report::default();
+
+    let mut stream = query("PRAGMA integrity_check").fetch(&mut *conn);
+    while let Some(row) = stream.try_next().await? {
+        let message: String = row.try_get(0)?;
+        if message != "ok" {
+            report.integrity_errors.push(message);
+        }
+    }
+    drop(stream);
+
+    let mut stream = query("PRAGMA foreign_key_check").fetch(&mut *conn);
+    while let Some(row) = stream.try_next().await? {
+        report.foreign_key_violations.push(ForeignKeyViolation {
+            table: row.try_get("table")?,
+            rowid: row.try_get("rowid")?,
+            parent: row.try_get("parent")?,
+            constraint_index: row.try_get("fkid")?,
+        });
+    }
+    drop(stream);
+
+    for kind in ORPHAN_KINDS {
+        let rows = query(kind.select).fetch_all(&mut *conn).await?;
+        for row in rows {
+            let id = row.try_get("id")?;
+            let mut dependents = Vec::new();
+            for dependent in kind.dependents {
+                let rows =
+                    query(dependent.select).bind(id).fetch_all(&mut *conn);
+                for row in rows.await? {
+                    dependents.push(DependentRow {
+                        table: dependent.table,
+                        data: json_data(row.try_get("data")?),
+                    });
+                }
+            }
+            report.orphans.push(Orphan {
+                kind: kind.name,
+                table: kind.table,
+                id,
+                data: json_data(row.try_get("data")?),
+                dependents,
+            });
+        }
+    }
+
+    conn.close().await?;
+    Ok(report)
+}
+
+fn json_data(data: String) -> serde_json::Value {
+    serde_json::from_str(&data).unwrap_or(serde_json::Value::String(data))
+}
+
+pub async fn repair(
+    pool: &Pool<RDBMS>,
+    report: &Report,
+    report_path: &Path,
+) -> Result<(), FsckError> {
+    let contents =
+        serde_json::to_vec_pretty(report).map_err(FsckError::Encode)?;
+    fs::write(report_path, contents).await.map_err(FsckError::WriteReport)?;
+
+    let mut transaction = pool.begin().await?;
+    for orphan in &report.orphans {
+        let Some(kind) =
+            ORPHAN_KINDS.iter().find(|kind| kind.name == orphan.kind)
+        else {
+            continue;
+        };
+        query(kind.repair).bind(orphan.id).execute(&mut *transaction).await?;
+    }
+    transaction.commit().await?;
+    Ok(())
+}
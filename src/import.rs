@@ -0,0 +1,189 @@
+use std::{collections::HashMap, path::Path};
+
+use futures::TryStreamExt;
+use sqlx::{query, Pool, Row};
+use thiserror::Error;
+
+use crate::{
+    domain::{NewIssue, Title},
+    RDBMS,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IssueField {
+    Title,
+    Description,
+    Status,
+}
+
+impl IssueField {
+    const ALL: [Self; 3] = [Self::Title, Self::Description, Self::Status];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Description => "description",
+            Self::Status => "status",
+        }
+    }
+
+    fn is_required(self) -> bool {
+        match self {
+            Self::Title | Self::Status => true,
+            Self::Description => false,
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|field| field.name() == name)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("Unknown issue field {0:?}")]
+    UnknownField(String),
+    #[error("Column mapping {0:?} must be in the form field=Column")]
+    InvalidMapping(String),
+    #[error("Required column {column:?} for field {field} is missing")]
+    MissingColumn { field: &'static str, column: String },
+    #[error("Failed to read CSV file")]
+    Csv(
+        #[source]
+        #[from]
+        csv::Error,
+    ),
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnMapping {
+    columns: HashMap<IssueField, String>,
+}
+
+impl ColumnMapping {
+    pub fn parse<'a>(
+        mappings: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Self, ImportError> {
+        let mut columns: HashMap<_, _> = IssueField::ALL
+            .into_iter()
+            .map(|field| (field, field.name().to_owned()))
+            .collect();
+        for mapping in mappings {
+            let (field, column) = mapping
+                .split_once('=')
+                .ok_or_else(|| ImportError::InvalidMapping(mapping.into()))?;
+            let field = IssueField::parse(field)
+                .ok_or_else(|| ImportError::UnknownField(field.into()))?;
+            columns.insert(field, column.to_owned());
+        }
+        Ok(Self { columns })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RowError {
+    pub line: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub errors: Vec<RowError>,
+}
+
+pub async fn import_csv(
+    pool: &Pool<RDBMS>,
+    path: &Path,
+    mapping: &ColumnMapping,
+    dry_run: bool,
+) -> Result<ImportReport, ImportError> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let headers = reader.headers()?.clone();
+    let mut indices = HashMap::new();
+    for field in IssueField::ALL {
+        let column = &mapping.columns[&field];
+        match headers.iter().position(|header| header == column) {
+            Some(index) => {
+                indices.insert(field, index);
+            },
+            None if field.is_required() => {
+                return Err(ImportError::MissingColumn {
+                    field: field.name(),
+                    column: column.clone(),
+                });
+            },
+            None => (),
+        }
+    }
+
+    let mut statuses = HashMap::new();
+    let mut stream = query("SELECT id, name FROM issue_statuses").fetch(pool);
+    while let Some(row) = stream.try_next().await? {
+        let name: String = row.try_get("name")?;
+        statuses.insert(name, row.try_get::<i64, _>("id")?);
+    }
+    drop(stream);
+
+    let mut report = ImportReport::default();
+    let mut issues = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map_or(0, |position| position.line());
+        let value = |field| {
+            indices
+                .get(&field)
+                .and_then(|&index| record.get(index))
+                .map(str::trim)
+                .unwrap_or_default()
+        };
+        let title = Title::parse(value(IssueField::Title));
+        let status_name = value(IssueField::Status);
+        if let Err(error) = &title {
+            report.errors.push(RowError { line, message: error.to_string() });
+        }
+        let status = statuses.get(status_name).copied();
+        if status.is_none() {
+            report.errors.push(RowError {
+                line,
+                message: format!("unknown status {status_name:?}"),
+            });
+        }
+        if let (Ok(title), Some(status_id)) = (title, status) {
+            issues.push(NewIssue {
+                title,
+                description: value(IssueField::Description).to_owned(),
+                status_id,
+                affects_version_id: None,
+                fixed_in_version_id: None,
+                assignee_id: None,
+                type_id: None,
+            });
+        }
+    }
+
+    if dry_run || !report.errors.is_empty() {
+        return Ok(report);
+    }
+
+    let mut transaction = pool.begin().await?;
+    for issue in &issues {
+        query(
+            "INSERT INTO issues (title, description, status) VALUES (?, ?, ?)",
+        )
+        .bind(issue.title.as_str())
+        .bind(&issue.description)
+        .bind(issue.status_id)
+        .execute(&mut *transaction)
+        .await?;
+    }
+    transaction.commit().await?;
+    report.imported = issues.len();
+    Ok(report)
+}
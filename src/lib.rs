@@ -1,19 +1,142 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
+use axum::{
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use cors::CorsConfig;
+use maintenance::Maintenance;
+use session::SessionConfig;
 use sqlx::{Pool, Sqlite};
+use tower_http::compression::{
+    predicate::{NotForContentType, Predicate, SizeAbove},
+    CompressionLayer,
+};
+use upgrade::UpgradeState;
 
-mod status;
 mod api;
+mod maintenance;
+mod request_id;
 mod static_files;
+mod telemetry;
+
+pub mod archive;
+pub mod cors;
+pub mod demo;
+pub mod domain;
+pub mod dump;
+pub mod fsck;
+pub mod import;
+pub mod migration;
+pub mod release_notes;
+pub mod session;
+pub mod status;
+pub mod sync;
+pub mod upgrade;
+pub mod users;
+pub mod version;
+
+pub use api::{
+    AdminListener,
+    BodyLimits,
+    NetworkAcl,
+    NetworkAclError,
+    OidcConfig,
+    PasswordError,
+};
+
+/// HTTP behaviour configured by the operator.
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    pub cors: Option<CorsConfig>,
+    pub body_limits: BodyLimits,
+    /// How long destructive calls can be taken back; zero disables undo.
+    pub undo_window: Duration,
+    pub network: NetworkAcl,
+    /// Directory administrators can write backups to through the API;
+    /// server-side backups are refused when unset.
+    pub backup_dir: Option<PathBuf>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            cors: None,
+            body_limits: BodyLimits::default(),
+            undo_window: Duration::from_secs(30),
+            network: NetworkAcl::default(),
+            backup_dir: None,
+        }
+    }
+}
 
 pub type RDBMS = Sqlite;
 
-pub fn router(static_path: impl Into<PathBuf>, pool: Pool<RDBMS>) -> Router {
-    Router::new()
-        .nest("/api/v1/", api::router(pool))
-        .nest("/static/", static_files::router(static_path))
+/// Responses known to be smaller than this many bytes are sent as they are,
+/// since compressing them saves little.
+const COMPRESSION_MIN_SIZE: u16 = 1024;
+
+pub fn router(
+    static_path: impl Into<PathBuf>,
+    pool: Pool<RDBMS>,
+    upgrade: UpgradeState,
+    sessions: SessionConfig,
+    oidc: Option<OidcConfig>,
+    read_only: bool,
+    http: HttpConfig,
+) -> Router {
+    let maintenance = Arc::new(Maintenance::new(pool.clone()));
+    let network = Arc::new(http.network.clone());
+    telemetry::handle();
+    let router = Router::new()
+        .route("/healthz", get(get_health))
+        .route(
+            "/readyz",
+            get({
+                let pool = pool.clone();
+                move || get_ready(pool)
+            }),
+        )
+        .route(
+            "/metrics",
+            get({
+                let pool = pool.clone();
+                move || telemetry::get_metrics(pool)
+            }),
+        )
+        .nest(
+            "/api/v1/",
+            api::router(
+                pool,
+                upgrade,
+                sessions,
+                oidc,
+                read_only,
+                maintenance.clone(),
+                &http,
+            ),
+        )
+        .nest("/static/", static_files::router(static_path, maintenance))
         .route("/", get(get_root))
+        .layer(middleware::from_fn(move |request, next| {
+            api::reject_denied(network.clone(), request, next)
+        }))
+        .layer(middleware::from_fn(telemetry::track_requests))
+        .layer(middleware::from_fn(request_id::propagate))
+        .layer(
+            CompressionLayer::new().compress_when(
+                SizeAbove::new(COMPRESSION_MIN_SIZE)
+                    .and(NotForContentType::GRPC)
+                    .and(NotForContentType::IMAGES),
+            ),
+        );
+    match http.cors {
+        Some(cors) => router.layer(cors.layer()),
+        None => router,
+    }
 }
 
 async fn get_root() -> impl IntoResponse {
@@ -23,3 +146,19 @@ async fn get_root() -> impl IntoResponse {
         "Permanent redirect",
     )
 }
+
+/// The process is up and serving requests.
+async fn get_health() -> impl IntoResponse {
+    (StatusCode::OK, "ok")
+}
+
+/// The database can be reached, so requests can be served.
+async fn get_ready(pool: Pool<RDBMS>) -> impl IntoResponse {
+    match sqlx::query("SELECT 1").execute(&pool).await {
+        Ok(_) => (StatusCode::OK, "ok"),
+        Err(error) => {
+            tracing::warn!(error = error.to_string(), "Readiness check failed");
+            (StatusCode::SERVICE_UNAVAILABLE, "database unavailable")
+        },
+    }
+}
@@ -1,43 +1,215 @@
-use std::{error::Error, io, path::PathBuf};
+use std::{
+    env,
+    error::Error,
+    ffi::OsString,
+    io,
+    net::{self, SocketAddr},
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    time::Duration,
+};
 
-use clap::Parser;
-use sqlx::{migrate::MigrateError, sqlite::SqliteConnectOptions, SqlitePool};
+use axum::Extension;
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use clap::{
+    parser::ValueSource,
+    ArgGroup,
+    Args,
+    CommandFactory,
+    FromArgMatches,
+    Parser,
+    Subcommand,
+    ValueEnum,
+};
+use futures::future;
+use listenfd::ListenFd;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use portable_issuer::{
+    archive::{self, Archive, ArchiveError},
+    cors::{CorsConfig, CorsConfigError},
+    demo,
+    dump::{self, DumpError},
+    fsck::{self, FsckError},
+    import::{self, ColumnMapping, ImportError},
+    migration::{self, MigrationError, MigrationPlan},
+    release_notes::{self, Scope},
+    session::{SessionConfig, SessionConfigError},
+    sync::{self, SyncError},
+    upgrade::{self, UpgradeCheckConfig, UpgradeState},
+    users::{self, UserAdminError},
+    version::BUILD_INFO,
+    AdminListener,
+    BodyLimits,
+    HttpConfig,
+    NetworkAcl,
+    NetworkAclError,
+    OidcConfig,
+};
+use serde_json::json;
+use sqlx::{
+    sqlite::{
+        SqliteConnectOptions,
+        SqliteJournalMode,
+        SqlitePoolOptions,
+        SqliteSynchronous,
+    },
+    ConnectOptions,
+    SqlitePool,
+};
 use thiserror::Error;
-use tokio::{net::TcpListener, signal};
+use tokio::{fs, net::TcpListener, signal};
 use tracing::level_filters::LevelFilter;
-use tracing_subscriber::{filter::FromEnvError, EnvFilter};
+use tracing_subscriber::{
+    filter::FromEnvError,
+    layer::SubscriberExt,
+    util::{SubscriberInitExt, TryInitError},
+    EnvFilter,
+};
 
 #[derive(Debug, Error)]
-enum LogSetupError {
+enum TelemetrySetupError {
     #[error("Failed to create environment filter")]
     EnvFilter(
         #[source]
         #[from]
         FromEnvError,
     ),
+    #[error("Failed to create OTLP trace exporter")]
+    Exporter(#[source] ExporterBuildError),
     #[error("Failed to initialize logging")]
-    Init(#[source] Box<dyn Error + Send + Sync + 'static>),
+    Init(#[source] TryInitError),
 }
 
 #[derive(Debug, Error)]
 enum AppError {
     #[error("Failed to bind a TCP listener")]
     Bind(#[source] io::Error),
+    #[error("Failed to take the socket passed by systemd")]
+    SocketActivation(#[source] io::Error),
+    #[error("No --bind-addr given and no socket passed by systemd")]
+    NoListener,
     #[error("Failed to serve app")]
     Serve(#[source] io::Error),
     #[error("Failed to connect to the pool")]
     PoolConnect(#[source] sqlx::Error),
-    #[error("Failed to migrate database updates")]
-    Migrate(#[source] MigrateError),
+    #[error("Failed to migrate database")]
+    Migration(#[source] MigrationError),
+    #[error("Failed to seed demo data")]
+    Seed(#[source] sqlx::Error),
+    #[error("Failed to read session secret file")]
+    ReadSessionSecret(#[source] io::Error),
+    #[error("Invalid session configuration")]
+    SessionConfig(#[source] SessionConfigError),
+    #[error("Failed to read OpenID Connect client secret file")]
+    ReadOidcSecret(#[source] io::Error),
+    #[error("Failed to load TLS certificate or key")]
+    TlsConfig(#[source] io::Error),
+    #[error("Invalid CORS configuration")]
+    CorsConfig(#[source] CorsConfigError),
+    #[error("Invalid network restrictions")]
+    NetworkAcl(#[source] NetworkAclError),
+}
+
+#[derive(Debug, Error)]
+enum CommandError {
+    #[error("Failed to connect to the pool")]
+    PoolConnect(#[source] sqlx::Error),
+    #[error("Failed to check database")]
+    Fsck(
+        #[from]
+        #[source]
+        FsckError,
+    ),
+    #[error("Database check found {0} problem(s)")]
+    ProblemsFound(usize),
+    #[error("Failed to dump database")]
+    Dump(
+        #[from]
+        #[source]
+        DumpError,
+    ),
+    #[error("Failed to import")]
+    Import(
+        #[from]
+        #[source]
+        ImportError,
+    ),
+    #[error("Import rejected because of {0} invalid row(s)")]
+    InvalidRows(usize),
+    #[error("Failed to read archive")]
+    ReadArchive(#[source] io::Error),
+    #[error("Failed to import archive")]
+    Archive(
+        #[from]
+        #[source]
+        ArchiveError,
+    ),
+    #[error("No version or milestone with the given name")]
+    UnknownRelease,
+    #[error("Failed to generate changelog")]
+    Changelog(#[source] sqlx::Error),
+    #[error("Failed to encode changelog")]
+    ChangelogEncode(#[source] serde_json::Error),
+    #[error("Failed to migrate database")]
+    Migration(
+        #[from]
+        #[source]
+        MigrationError,
+    ),
+    #[error("Database has {0} pending migration(s), run `migrate` first")]
+    PendingMigrations(usize),
+    #[error("Failed to seed demo data")]
+    Seed(#[source] sqlx::Error),
+    #[error("Failed to back up database")]
+    Backup(#[source] DumpError),
+    #[error("Failed to read password from standard input")]
+    ReadPassword(#[source] io::Error),
+    #[error("Failed to manage user")]
+    UserAdmin(
+        #[from]
+        #[source]
+        UserAdminError,
+    ),
+    #[error("Failed to read sync token")]
+    ReadToken(#[source] io::Error),
+    #[error("Failed to sync with remote instance")]
+    Sync(
+        #[from]
+        #[source]
+        SyncError,
+    ),
+}
+
+#[derive(Debug, Error)]
+enum ConfigError {
+    #[error("Failed to read configuration file {}", .0.display())]
+    Read(PathBuf, #[source] io::Error),
+    #[error("Failed to parse configuration file")]
+    Parse(#[source] toml::de::Error),
+    #[error("Unknown setting {0:?} in configuration file")]
+    UnknownSetting(String),
+    #[error(
+        "Setting {0:?} must be a string, number, boolean or array of them"
+    )]
+    InvalidValue(String),
 }
 
 #[derive(Debug, Error)]
 enum MainError {
-    #[error("Failed to setup logging")]
-    LogSetup(
+    #[error("Failed to load configuration")]
+    Config(
         #[from]
         #[source]
-        LogSetupError,
+        ConfigError,
+    ),
+    #[error("Failed to setup telemetry")]
+    TelemetrySetup(
+        #[from]
+        #[source]
+        TelemetrySetupError,
     ),
     #[error("Failed to run server")]
     App(
@@ -45,62 +217,1280 @@ enum MainError {
         #[source]
         AppError,
     ),
+    #[error("Failed to run command")]
+    Command(
+        #[from]
+        #[source]
+        CommandError,
+    ),
 }
 
 #[derive(Debug, Parser)]
+#[clap(args_conflicts_with_subcommands = true, arg_required_else_help = true)]
 struct Cli {
-    #[clap(short = 'b', long = "bind-addr")]
-    bind_addr: String,
-    #[clap(short = 's', long = "static")]
+    #[clap(long = "version-json", exclusive = true)]
+    version_json: bool,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Runs the HTTP server, migrating the database first.
+    Serve(Box<ServeArgs>),
+    /// Applies pending migrations, backing up the database first.
+    Migrate(MigrateArgs),
+    /// Writes a consistent copy of the database, even while it is served.
+    Backup(BackupArgs),
+    /// Fills a migrated database with demo statuses, labels, milestones and
+    /// issues.
+    Seed(SeedArgs),
+    /// Manages users without going through the API.
+    #[clap(subcommand)]
+    Admin(AdminCommand),
+    /// Checks database integrity, foreign keys and orphaned rows.
+    Fsck(FsckArgs),
+    /// Writes a compacted copy of the database, optionally scrubbed of
+    /// user content.
+    Dump(DumpArgs),
+    /// Imports issues from external sources.
+    #[clap(subcommand)]
+    Import(ImportCommand),
+    /// Prints release notes for a version or milestone, grouped by label.
+    Changelog(ChangelogArgs),
+    /// Exchanges issue changes with another instance, reporting rows
+    /// edited on both sides instead of overwriting them.
+    Sync(SyncArgs),
+}
+
+#[derive(Debug, Subcommand)]
+enum AdminCommand {
+    /// Makes a user an administrator. Registering never does, so this is
+    /// how the first administrator of an instance is appointed.
+    Grant(AdminUserArgs),
+    /// Takes administration rights away from a user.
+    Revoke(AdminUserArgs),
+    /// Sets the password read from standard input and ends every session
+    /// of the user.
+    ResetPassword(AdminUserArgs),
+}
+
+#[derive(Debug, Subcommand)]
+enum ImportCommand {
+    /// Imports issues from a CSV file, mapping issue fields to columns.
+    Csv(ImportCsvArgs),
+    /// Merges an archive downloaded from `/api/v1/export` into the
+    /// database.
+    Archive(ImportArchiveArgs),
+}
+
+#[derive(Debug, Args)]
+struct ServeArgs {
+    /// TOML file of settings named after the long options, e.g.
+    /// `bind-addr = ["0.0.0.0:80"]`. Every option can also be set through
+    /// `PORTABLE_ISSUER_` followed by its long name in upper snake case,
+    /// with lists separated by commas. The command line takes precedence
+    /// over the environment, which takes precedence over the file.
+    #[clap(long = "config", env = "PORTABLE_ISSUER_CONFIG")]
+    config: Option<PathBuf>,
+    /// Address to listen on, in addition to sockets passed by systemd
+    /// through `LISTEN_FDS`. Can be repeated.
+    #[clap(
+        short = 'b',
+        long = "bind-addr",
+        env = "PORTABLE_ISSUER_BIND_ADDR",
+        value_delimiter = ','
+    )]
+    bind_addrs: Vec<String>,
+    /// Address of a listener reserved for admin routes, which are then no
+    /// longer served on the other listeners.
+    #[clap(long = "admin-bind-addr", env = "PORTABLE_ISSUER_ADMIN_BIND_ADDR")]
+    admin_bind_addr: Option<String>,
+    #[clap(short = 's', long = "static", env = "PORTABLE_ISSUER_STATIC")]
     static_path: PathBuf,
-    #[clap(short = 'd', long = "database", default_value = "database.bin")]
+    #[clap(
+        short = 'd',
+        long = "database",
+        default_value = "database.bin",
+        env = "PORTABLE_ISSUER_DATABASE"
+    )]
     database: PathBuf,
+    /// Keeps the database in memory instead of `--database`. Everything is
+    /// lost when the server stops.
+    #[clap(
+        long = "ephemeral",
+        conflicts_with = "database",
+        env = "PORTABLE_ISSUER_EPHEMERAL"
+    )]
+    ephemeral: bool,
+    /// Fills the in-memory database with demo statuses, labels, milestones
+    /// and issues.
+    #[clap(
+        long = "seed-demo",
+        requires = "ephemeral",
+        env = "PORTABLE_ISSUER_SEED_DEMO"
+    )]
+    seed_demo: bool,
+    /// Maximum number of open database connections.
+    #[clap(
+        long = "db-max-connections",
+        default_value_t = 10,
+        env = "PORTABLE_ISSUER_DB_MAX_CONNECTIONS"
+    )]
+    db_max_connections: u32,
+    /// Seconds to wait for a free database connection before failing.
+    #[clap(
+        long = "db-acquire-timeout",
+        default_value_t = 30,
+        env = "PORTABLE_ISSUER_DB_ACQUIRE_TIMEOUT"
+    )]
+    db_acquire_timeout: u64,
+    /// Seconds after which an unused database connection is closed.
+    #[clap(
+        long = "db-idle-timeout",
+        default_value_t = 600,
+        env = "PORTABLE_ISSUER_DB_IDLE_TIMEOUT"
+    )]
+    db_idle_timeout: u64,
+    /// Uses write-ahead logging, so that readers do not wait for writers.
+    #[clap(
+        long = "db-wal",
+        conflicts_with = "ephemeral",
+        env = "PORTABLE_ISSUER_DB_WAL"
+    )]
+    db_wal: bool,
+    /// How often SQLite waits for data to reach the disk.
+    #[clap(
+        long = "db-synchronous",
+        value_enum,
+        default_value_t = Synchronous::Full,
+        env = "PORTABLE_ISSUER_DB_SYNCHRONOUS"
+    )]
+    db_synchronous: Synchronous,
+    /// Seconds a connection waits for a locked database before failing.
+    #[clap(
+        long = "db-busy-timeout",
+        default_value_t = 5,
+        env = "PORTABLE_ISSUER_DB_BUSY_TIMEOUT"
+    )]
+    db_busy_timeout: u64,
+    /// Starts with all writes rejected. Administrators can lift this at
+    /// runtime through `/api/v1/admin/read-only`.
+    #[clap(long = "read-only", env = "PORTABLE_ISSUER_READ_ONLY")]
+    read_only: bool,
+    /// Prints the migrations that would be applied to the database and
+    /// exits without applying them. Otherwise the database is backed up
+    /// next to itself before pending migrations are applied.
+    #[clap(long = "migrate-dry-run", env = "PORTABLE_ISSUER_MIGRATE_DRY_RUN")]
+    migrate_dry_run: bool,
+    /// OTLP/HTTP endpoint that request and database traces are exported
+    /// to, e.g. `http://localhost:4318/v1/traces`.
+    #[clap(long = "otlp-endpoint", env = "PORTABLE_ISSUER_OTLP_ENDPOINT")]
+    otlp_endpoint: Option<String>,
+    /// Origin allowed to call the API from a browser. Repeat for several
+    /// origins, or pass `*` to allow any. CORS is disabled when absent.
+    #[clap(
+        long = "cors-origin",
+        env = "PORTABLE_ISSUER_CORS_ORIGIN",
+        value_delimiter = ','
+    )]
+    cors_origins: Vec<String>,
+    /// Method allowed in cross-origin requests. Defaults to the methods the
+    /// API uses.
+    #[clap(
+        long = "cors-method",
+        requires = "cors_origins",
+        env = "PORTABLE_ISSUER_CORS_METHOD",
+        value_delimiter = ','
+    )]
+    cors_methods: Vec<String>,
+    /// Request header allowed in cross-origin requests. Defaults to the
+    /// headers the API reads.
+    #[clap(
+        long = "cors-header",
+        requires = "cors_origins",
+        env = "PORTABLE_ISSUER_CORS_HEADER",
+        value_delimiter = ','
+    )]
+    cors_headers: Vec<String>,
+    /// Largest request body accepted, in bytes.
+    #[clap(
+        long = "max-body-size",
+        default_value_t = BodyLimits::default().max_bytes,
+        env = "PORTABLE_ISSUER_MAX_BODY_SIZE"
+    )]
+    max_body_size: usize,
+    /// Deepest nesting of JSON arrays and objects accepted in request
+    /// bodies.
+    #[clap(
+        long = "max-json-depth",
+        default_value_t = BodyLimits::default().max_json_depth,
+        env = "PORTABLE_ISSUER_MAX_JSON_DEPTH"
+    )]
+    max_json_depth: usize,
+    /// Seconds during which a label detach can be undone; 0 disables undo.
+    #[clap(
+        long = "undo-window",
+        default_value_t = HttpConfig::default().undo_window.as_secs(),
+        env = "PORTABLE_ISSUER_UNDO_WINDOW"
+    )]
+    undo_window: u64,
+    /// Directory administrators can write backups to through
+    /// `/api/v1/admin/backup`. Only downloads are offered when absent.
+    #[clap(long = "backup-dir", env = "PORTABLE_ISSUER_BACKUP_DIR")]
+    backup_dir: Option<PathBuf>,
+    /// Address or CIDR block of peers refused on every route. Can be
+    /// repeated.
+    #[clap(
+        long = "deny-network",
+        env = "PORTABLE_ISSUER_DENY_NETWORK",
+        value_delimiter = ','
+    )]
+    deny_networks: Vec<String>,
+    /// Address or CIDR block of peers allowed to reach admin endpoints.
+    /// Can be repeated; all peers are allowed when none is given.
+    #[clap(
+        long = "admin-network",
+        env = "PORTABLE_ISSUER_ADMIN_NETWORK",
+        value_delimiter = ','
+    )]
+    admin_networks: Vec<String>,
+    /// Address or CIDR block of peers allowed to reach auth endpoints.
+    /// Can be repeated; all peers are allowed when none is given.
+    #[clap(
+        long = "auth-network",
+        env = "PORTABLE_ISSUER_AUTH_NETWORK",
+        value_delimiter = ','
+    )]
+    auth_networks: Vec<String>,
+    /// PEM certificate chain to serve HTTPS with, so that no reverse proxy
+    /// is needed.
+    #[clap(
+        long = "tls-cert",
+        requires = "tls_key",
+        env = "PORTABLE_ISSUER_TLS_CERT"
+    )]
+    tls_cert: Option<PathBuf>,
+    /// PEM private key of `--tls-cert`.
+    #[clap(
+        long = "tls-key",
+        requires = "tls_cert",
+        env = "PORTABLE_ISSUER_TLS_KEY"
+    )]
+    tls_key: Option<PathBuf>,
+    /// Seconds that in-flight requests are given to finish on shutdown
+    /// before their connections are closed.
+    #[clap(
+        long = "shutdown-timeout",
+        default_value_t = 30,
+        env = "PORTABLE_ISSUER_SHUTDOWN_TIMEOUT"
+    )]
+    shutdown_timeout: u64,
+    #[clap(
+        long = "upgrade-check-url",
+        env = "PORTABLE_ISSUER_UPGRADE_CHECK_URL"
+    )]
+    upgrade_check_url: Option<String>,
+    /// Seconds between upgrade checks, at least one.
+    #[clap(
+        long = "upgrade-check-interval",
+        default_value_t = 86400,
+        value_parser = clap::value_parser!(u64).range(1..),
+        env = "PORTABLE_ISSUER_UPGRADE_CHECK_INTERVAL"
+    )]
+    upgrade_check_interval: u64,
+    /// File with the secret used to sign session cookies. A random secret
+    /// is generated when absent, so sessions do not survive restarts.
+    #[clap(
+        long = "session-secret-file",
+        env = "PORTABLE_ISSUER_SESSION_SECRET_FILE"
+    )]
+    session_secret_file: Option<PathBuf>,
+    /// Session lifetime in seconds.
+    #[clap(
+        long = "session-ttl",
+        default_value_t = 1209600,
+        env = "PORTABLE_ISSUER_SESSION_TTL"
+    )]
+    session_ttl: u64,
+    /// Marks session cookies `Secure` even without `--tls-cert`, for
+    /// deployments where a proxy in front of the server terminates TLS.
+    #[clap(long = "secure-cookies", env = "PORTABLE_ISSUER_SECURE_COOKIES")]
+    secure_cookies: bool,
+    /// OpenID Connect issuer used for single sign-on.
+    #[clap(
+        long = "oidc-issuer-url",
+        requires_all = [
+            "oidc_client_id",
+            "oidc_client_secret_file",
+            "oidc_redirect_url",
+        ],
+        env = "PORTABLE_ISSUER_OIDC_ISSUER_URL"
+    )]
+    oidc_issuer_url: Option<String>,
+    #[clap(
+        long = "oidc-client-id",
+        requires = "oidc_issuer_url",
+        env = "PORTABLE_ISSUER_OIDC_CLIENT_ID"
+    )]
+    oidc_client_id: Option<String>,
+    /// File with the OpenID Connect client secret.
+    #[clap(
+        long = "oidc-client-secret-file",
+        requires = "oidc_issuer_url",
+        env = "PORTABLE_ISSUER_OIDC_CLIENT_SECRET_FILE"
+    )]
+    oidc_client_secret_file: Option<PathBuf>,
+    /// Public URL of `/api/v1/auth/oidc/callback` registered with the
+    /// identity provider.
+    #[clap(
+        long = "oidc-redirect-url",
+        requires = "oidc_issuer_url",
+        env = "PORTABLE_ISSUER_OIDC_REDIRECT_URL"
+    )]
+    oidc_redirect_url: Option<String>,
 }
 
-fn setup_logger() -> Result<(), LogSetupError> {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::builder()
-                .with_env_var("PORTABLE_ISSUER_LOG")
-                .with_default_directive(LevelFilter::INFO.into())
-                .from_env()?,
-        )
-        .with_writer(io::stderr)
+#[derive(Debug, Args)]
+struct MigrateArgs {
+    #[clap(
+        short = 'd',
+        long = "database",
+        default_value = "database.bin",
+        env = "PORTABLE_ISSUER_DATABASE"
+    )]
+    database: PathBuf,
+    /// Lists every migration as applied or pending.
+    #[clap(
+        long = "status",
+        conflicts_with_all = ["up", "down", "dry_run"],
+        env = "PORTABLE_ISSUER_MIGRATE_STATUS"
+    )]
+    status: bool,
+    /// Applies the pending migrations, which is also done when no other
+    /// action is given.
+    #[clap(
+        long = "up",
+        conflicts_with = "down",
+        env = "PORTABLE_ISSUER_MIGRATE_UP"
+    )]
+    up: bool,
+    /// Reverts the last N applied migrations, backing up the database
+    /// first. Every migration but the initial schema can be reverted.
+    #[clap(
+        long = "down",
+        value_name = "N",
+        env = "PORTABLE_ISSUER_MIGRATE_DOWN"
+    )]
+    down: Option<usize>,
+    /// Prints the migrations that would be applied or reverted without
+    /// touching the database.
+    #[clap(long = "dry-run", env = "PORTABLE_ISSUER_MIGRATE_DRY_RUN")]
+    dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+struct BackupArgs {
+    #[clap(
+        short = 'd',
+        long = "database",
+        default_value = "database.bin",
+        env = "PORTABLE_ISSUER_DATABASE"
+    )]
+    database: PathBuf,
+    /// Defaults to a file next to the database named after the time of the
+    /// backup.
+    #[clap(
+        short = 'o',
+        long = "output",
+        env = "PORTABLE_ISSUER_BACKUP_OUTPUT"
+    )]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct SeedArgs {
+    #[clap(
+        short = 'd',
+        long = "database",
+        default_value = "database.bin",
+        env = "PORTABLE_ISSUER_DATABASE"
+    )]
+    database: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct AdminUserArgs {
+    #[clap(
+        short = 'd',
+        long = "database",
+        default_value = "database.bin",
+        env = "PORTABLE_ISSUER_DATABASE"
+    )]
+    database: PathBuf,
+    /// Name of the user.
+    name: String,
+}
+
+#[derive(Debug, Args)]
+struct FsckArgs {
+    #[clap(
+        short = 'd',
+        long = "database",
+        default_value = "database.bin",
+        env = "PORTABLE_ISSUER_DATABASE"
+    )]
+    database: PathBuf,
+    #[clap(
+        long = "repair",
+        value_name = "REPORT_FILE",
+        env = "PORTABLE_ISSUER_FSCK_REPAIR"
+    )]
+    repair: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct DumpArgs {
+    #[clap(
+        short = 'd',
+        long = "database",
+        default_value = "database.bin",
+        env = "PORTABLE_ISSUER_DATABASE"
+    )]
+    database: PathBuf,
+    #[clap(short = 'o', long = "output", env = "PORTABLE_ISSUER_DUMP_OUTPUT")]
+    output: PathBuf,
+    #[clap(long = "anonymize", env = "PORTABLE_ISSUER_DUMP_ANONYMIZE")]
+    anonymize: bool,
+}
+
+#[derive(Debug, Args)]
+struct ImportCsvArgs {
+    #[clap(
+        short = 'd',
+        long = "database",
+        default_value = "database.bin",
+        env = "PORTABLE_ISSUER_DATABASE"
+    )]
+    database: PathBuf,
+    /// Several mappings can also be given at once, separated by commas.
+    #[clap(
+        long = "map",
+        value_name = "FIELD=COLUMN",
+        value_delimiter = ',',
+        env = "PORTABLE_ISSUER_IMPORT_MAP"
+    )]
+    map: Vec<String>,
+    #[clap(long = "dry-run", env = "PORTABLE_ISSUER_IMPORT_DRY_RUN")]
+    dry_run: bool,
+    file: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct ImportArchiveArgs {
+    #[clap(
+        short = 'd',
+        long = "database",
+        default_value = "database.bin",
+        env = "PORTABLE_ISSUER_DATABASE"
+    )]
+    database: PathBuf,
+    /// Reports what would be imported, then rolls everything back.
+    #[clap(long = "dry-run", env = "PORTABLE_ISSUER_IMPORT_DRY_RUN")]
+    dry_run: bool,
+    file: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+impl From<Synchronous> for SqliteSynchronous {
+    fn from(synchronous: Synchronous) -> Self {
+        match synchronous {
+            Synchronous::Off => Self::Off,
+            Synchronous::Normal => Self::Normal,
+            Synchronous::Full => Self::Full,
+            Synchronous::Extra => Self::Extra,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ChangelogFormat {
+    Md,
+    Json,
+}
+
+#[derive(Debug, Args)]
+#[clap(group(
+    ArgGroup::new("scope").required(true).args(["version", "milestone"])
+))]
+struct ChangelogArgs {
+    #[clap(
+        short = 'd',
+        long = "database",
+        default_value = "database.bin",
+        env = "PORTABLE_ISSUER_DATABASE"
+    )]
+    database: PathBuf,
+    #[clap(long = "version", env = "PORTABLE_ISSUER_CHANGELOG_VERSION")]
+    version: Option<String>,
+    #[clap(long = "milestone", env = "PORTABLE_ISSUER_CHANGELOG_MILESTONE")]
+    milestone: Option<String>,
+    #[clap(
+        long = "format",
+        value_enum,
+        default_value_t = ChangelogFormat::Md,
+        env = "PORTABLE_ISSUER_CHANGELOG_FORMAT"
+    )]
+    format: ChangelogFormat,
+}
+
+#[derive(Debug, Args)]
+struct SyncArgs {
+    #[clap(
+        short = 'd',
+        long = "database",
+        default_value = "database.bin",
+        env = "PORTABLE_ISSUER_DATABASE"
+    )]
+    database: PathBuf,
+    /// Base URL of the other instance, e.g. `https://issues.example.com`.
+    #[clap(
+        long = "remote",
+        required_unless_present = "new_instance_id",
+        requires = "token_file",
+        env = "PORTABLE_ISSUER_SYNC_REMOTE"
+    )]
+    remote: Option<String>,
+    /// File with an API token of an administrator of the other instance.
+    #[clap(long = "token-file", env = "PORTABLE_ISSUER_SYNC_TOKEN_FILE")]
+    token_file: Option<PathBuf>,
+    /// Gives this database a new instance id, needed once after copying a
+    /// database to start another instance from it.
+    #[clap(long = "new-instance-id", conflicts_with = "remote")]
+    new_instance_id: bool,
+}
+
+/// Logs to stderr and, given an OTLP endpoint, exports traces there too.
+/// The returned provider must be shut down to flush pending spans.
+fn setup_telemetry(
+    otlp_endpoint: Option<&str>,
+) -> Result<Option<SdkTracerProvider>, TelemetrySetupError> {
+    let filter = EnvFilter::builder()
+        .with_env_var("PORTABLE_ISSUER_LOG")
+        .with_default_directive(LevelFilter::INFO.into())
+        .from_env()?;
+    let provider = match otlp_endpoint {
+        Some(endpoint) => {
+            let exporter = SpanExporter::builder()
+                .with_http()
+                .with_endpoint(endpoint)
+                .build()
+                .map_err(TelemetrySetupError::Exporter)?;
+            let resource = Resource::builder()
+                .with_service_name(env!("CARGO_PKG_NAME"))
+                .build();
+            let provider = SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(resource)
+                .build();
+            Some(provider)
+        },
+        None => None,
+    };
+    let traces = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+    });
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_writer(io::stderr))
+        .with(traces)
         .try_init()
-        .map_err(LogSetupError::Init)?;
-    Ok(())
+        .map_err(TelemetrySetupError::Init)?;
+    Ok(provider)
 }
 
-async fn run_server_app(cli: &Cli) -> Result<(), AppError> {
-    let pool_options = SqliteConnectOptions::new()
+fn print_migration_plan(plan: &MigrationPlan) {
+    if plan.pending.is_empty() {
+        println!("No pending migrations");
+    }
+    for migration in &plan.pending {
+        println!("{} {}", migration.version, migration.description);
+    }
+}
+
+async fn run_server_app(cli: &ServeArgs) -> Result<(), AppError> {
+    let pool_options = if cli.ephemeral {
+        SqliteConnectOptions::from_str("sqlite::memory:")
+            .map_err(AppError::PoolConnect)?
+    } else {
+        SqliteConnectOptions::new()
+            .filename(&cli.database)
+            .create_if_missing(true)
+    };
+    let mut pool_options = pool_options
         .foreign_keys(true)
-        .filename(&cli.database)
-        .create_if_missing(true);
-    let pool = SqlitePool::connect_with(pool_options)
+        .synchronous(cli.db_synchronous.into())
+        .busy_timeout(Duration::from_secs(cli.db_busy_timeout));
+    if cli.db_wal {
+        pool_options = pool_options.journal_mode(SqliteJournalMode::Wal);
+    }
+    // A shared in-memory database only lives while some connection to it is
+    // open, and pooled connections are closed after each request.
+    let _keeper = if cli.ephemeral {
+        Some(pool_options.connect().await.map_err(AppError::PoolConnect)?)
+    } else {
+        None
+    };
+    let pool = SqlitePoolOptions::new()
+        .max_connections(cli.db_max_connections)
+        .acquire_timeout(Duration::from_secs(cli.db_acquire_timeout))
+        .idle_timeout(Duration::from_secs(cli.db_idle_timeout))
+        .connect_with(pool_options)
         .await
         .map_err(AppError::PoolConnect)?;
-    sqlx::migrate!().run(&pool).await.map_err(AppError::Migrate)?;
-    let app = portable_issuer::router(&cli.static_path, pool);
-    let listener =
-        TcpListener::bind(&cli.bind_addr).await.map_err(AppError::Bind)?;
-    tracing::info!(bind_addr = cli.bind_addr);
-    axum::serve(listener, app)
-        .with_graceful_shutdown(async {
-            if let Err(error) = signal::ctrl_c().await {
+    let plan = migration::plan(&pool).await.map_err(AppError::Migration)?;
+    if cli.migrate_dry_run {
+        print_migration_plan(&plan);
+        return Ok(());
+    }
+    let database = (!cli.ephemeral).then_some(cli.database.as_path());
+    migration::apply(&pool, &plan, database)
+        .await
+        .map_err(AppError::Migration)?;
+    if cli.seed_demo {
+        demo::seed(&pool).await.map_err(AppError::Seed)?;
+    }
+    let upgrade = match &cli.upgrade_check_url {
+        Some(releases_url) => upgrade::spawn_checker(UpgradeCheckConfig {
+            releases_url: releases_url.clone(),
+            interval: Duration::from_secs(cli.upgrade_check_interval),
+        }),
+        None => UpgradeState::disabled(),
+    };
+    let secret = match &cli.session_secret_file {
+        Some(path) => {
+            Some(fs::read(path).await.map_err(AppError::ReadSessionSecret)?)
+        },
+        None => None,
+    };
+    let sessions = SessionConfig::new(
+        secret.as_deref(),
+        Duration::from_secs(cli.session_ttl),
+    )
+    .map_err(AppError::SessionConfig)?
+    .with_secure_cookies(cli.secure_cookies || cli.tls_cert.is_some());
+    let oidc = match (
+        &cli.oidc_issuer_url,
+        &cli.oidc_client_id,
+        &cli.oidc_client_secret_file,
+        &cli.oidc_redirect_url,
+    ) {
+        (
+            Some(issuer_url),
+            Some(client_id),
+            Some(secret_file),
+            Some(redirect_url),
+        ) => {
+            let client_secret = fs::read_to_string(secret_file)
+                .await
+                .map_err(AppError::ReadOidcSecret)?;
+            Some(OidcConfig {
+                issuer_url: issuer_url.clone(),
+                client_id: client_id.clone(),
+                client_secret: client_secret.trim().to_owned(),
+                redirect_url: redirect_url.clone(),
+            })
+        },
+        _ => None,
+    };
+    let cors = if cli.cors_origins.is_empty() {
+        None
+    } else {
+        let cors = CorsConfig::new(
+            &cli.cors_origins,
+            &cli.cors_methods,
+            &cli.cors_headers,
+        )
+        .map_err(AppError::CorsConfig)?;
+        Some(cors)
+    };
+    let network = NetworkAcl::new(
+        &cli.deny_networks,
+        &cli.admin_networks,
+        &cli.auth_networks,
+        cli.admin_bind_addr.is_some(),
+    )
+    .map_err(AppError::NetworkAcl)?;
+    let app = portable_issuer::router(
+        &cli.static_path,
+        pool.clone(),
+        upgrade,
+        sessions,
+        oidc,
+        cli.read_only,
+        HttpConfig {
+            cors,
+            body_limits: BodyLimits {
+                max_bytes: cli.max_body_size,
+                max_json_depth: cli.max_json_depth,
+            },
+            undo_window: Duration::from_secs(cli.undo_window),
+            network,
+            backup_dir: cli.backup_dir.clone(),
+        },
+    );
+    let tls = match (&cli.tls_cert, &cli.tls_key) {
+        (Some(cert), Some(key)) => {
+            // Only the first installed provider counts, and it is always
+            // this one.
+            let _ = rustls::crypto::ring::default_provider().install_default();
+            let config = RustlsConfig::from_pem_file(cert, key)
+                .await
+                .map_err(AppError::TlsConfig)?;
+            Some(config)
+        },
+        _ => None,
+    };
+    let handle = Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        let timeout = Duration::from_secs(cli.shutdown_timeout);
+        async move {
+            shutdown_signal().await;
+            tracing::info!(
+                timeout_secs = timeout.as_secs(),
+                "Shutting down, waiting for in-flight requests"
+            );
+            handle.graceful_shutdown(Some(timeout));
+        }
+    });
+    let mut servers = Vec::new();
+    for (listener, kind) in listen(cli).await? {
+        let local_addr = listener.local_addr().map_err(AppError::Bind)?;
+        tracing::info!(
+            local_addr = local_addr.to_string(),
+            listener = ?kind,
+            tls = tls.is_some(),
+        );
+        let app = match kind {
+            ListenerKind::Admin => app.clone().layer(Extension(AdminListener)),
+            _ => app.clone(),
+        };
+        let app = app.into_make_service_with_connect_info::<SocketAddr>();
+        let handle = handle.clone();
+        let tls = tls.clone();
+        servers.push(async move {
+            match tls {
+                Some(config) => {
+                    axum_server::from_tcp_rustls(listener, config)
+                        .handle(handle)
+                        .serve(app)
+                        .await
+                },
+                None => {
+                    axum_server::from_tcp(listener)
+                        .handle(handle)
+                        .serve(app)
+                        .await
+                },
+            }
+        });
+    }
+    future::try_join_all(servers).await.map_err(AppError::Serve)?;
+    pool.close().await;
+    tracing::info!("Server stopped");
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListenerKind {
+    SocketActivated,
+    Bound,
+    Admin,
+}
+
+/// Takes every socket passed by systemd, so that they stay open across
+/// restarts, then binds `--bind-addr` and `--admin-bind-addr`.
+async fn listen(
+    cli: &ServeArgs,
+) -> Result<Vec<(net::TcpListener, ListenerKind)>, AppError> {
+    let mut listeners = Vec::new();
+    let mut passed = ListenFd::from_env();
+    for index in 0..passed.len() {
+        let listener = passed
+            .take_tcp_listener(index)
+            .map_err(AppError::SocketActivation)?;
+        if let Some(listener) = listener {
+            listener
+                .set_nonblocking(true)
+                .map_err(AppError::SocketActivation)?;
+            listeners.push((listener, ListenerKind::SocketActivated));
+        }
+    }
+    let bind_addrs =
+        cli.bind_addrs.iter().map(|addr| (addr, ListenerKind::Bound)).chain(
+            cli.admin_bind_addr.iter().map(|addr| (addr, ListenerKind::Admin)),
+        );
+    for (bind_addr, kind) in bind_addrs {
+        let listener = TcpListener::bind(bind_addr)
+            .await
+            .and_then(TcpListener::into_std)
+            .map_err(AppError::Bind)?;
+        listeners.push((listener, kind));
+    }
+    if listeners.iter().all(|(_, kind)| *kind == ListenerKind::Admin) {
+        return Err(AppError::NoListener);
+    }
+    Ok(listeners)
+}
+
+/// Waits for Ctrl-C, or on Unix for SIGTERM, which service managers send
+/// to stop the server.
+async fn shutdown_signal() {
+    let interrupt = async {
+        if let Err(error) = signal::ctrl_c().await {
+            tracing::error!(
+                error = error.to_string(),
+                "Failed to control C-C signal"
+            );
+            future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            },
+            Err(error) => {
                 tracing::error!(
                     error = error.to_string(),
-                    "Failed to control C-C signal"
+                    "Failed to listen for SIGTERM"
                 );
-            }
-        })
+                future::pending::<()>().await;
+            },
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = future::pending::<()>();
+    tokio::select! {
+        () = interrupt => (),
+        () = terminate => (),
+    }
+}
+
+async fn connect_existing(database: &Path) -> Result<SqlitePool, CommandError> {
+    let pool_options =
+        SqliteConnectOptions::new().foreign_keys(true).filename(database);
+    SqlitePool::connect_with(pool_options)
         .await
-        .map_err(AppError::Serve)?;
+        .map_err(CommandError::PoolConnect)
+}
+
+async fn run_migrate(args: &MigrateArgs) -> Result<(), CommandError> {
+    let writes = !args.dry_run && !args.status;
+    let pool_options = SqliteConnectOptions::new()
+        .foreign_keys(true)
+        .filename(&args.database)
+        .create_if_missing(writes && args.down.is_none());
+    let pool = SqlitePool::connect_with(pool_options)
+        .await
+        .map_err(CommandError::PoolConnect)?;
+    if args.status {
+        for migration in migration::status(&pool).await? {
+            let state = if migration.applied { "applied" } else { "pending" };
+            println!("{state} {} {}", migration.version, migration.description);
+        }
+    } else if let Some(count) = args.down {
+        let plan = migration::plan_revert(&pool, count).await?;
+        if plan.reverted.is_empty() {
+            println!("No migrations to revert");
+        }
+        for migration in &plan.reverted {
+            println!("{} {}", migration.version, migration.description);
+        }
+        if writes && !plan.reverted.is_empty() {
+            migration::revert(&pool, &plan, &args.database).await?;
+            println!("reverted {} migration(s)", plan.reverted.len());
+        }
+    } else {
+        let plan = migration::plan(&pool).await?;
+        print_migration_plan(&plan);
+        if writes && !plan.pending.is_empty() {
+            migration::apply(&pool, &plan, Some(&args.database)).await?;
+            println!("applied {} migration(s)", plan.pending.len());
+        }
+    }
+    pool.close().await;
+    Ok(())
+}
+
+/// Copies the database with `VACUUM INTO`, as `POST /admin/backup` does. It
+/// reads a single consistent snapshot, like the online backup API, and also
+/// leaves free pages out of the copy, without an unsafe call into the C
+/// backup API, which sqlx does not wrap.
+async fn run_backup(args: &BackupArgs) -> Result<(), CommandError> {
+    let output = match &args.output {
+        Some(output) => output.clone(),
+        None => migration::backup_path(&args.database, "backup"),
+    };
+    let pool = connect_existing(&args.database).await?;
+    dump::dump(&pool, &output, false).await.map_err(CommandError::Backup)?;
+    pool.close().await;
+    println!("{}", output.display());
+    Ok(())
+}
+
+async fn run_seed(args: &SeedArgs) -> Result<(), CommandError> {
+    let pool = connect_existing(&args.database).await?;
+    let plan = migration::plan(&pool).await?;
+    if !plan.pending.is_empty() {
+        return Err(CommandError::PendingMigrations(plan.pending.len()));
+    }
+    demo::seed(&pool).await.map_err(CommandError::Seed)?;
+    pool.close().await;
+    Ok(())
+}
+
+async fn run_admin(command: &AdminCommand) -> Result<(), CommandError> {
+    match command {
+        AdminCommand::Grant(args) => {
+            let pool = connect_existing(&args.database).await?;
+            users::set_admin(&pool, &args.name, true).await?;
+            pool.close().await;
+        },
+        AdminCommand::Revoke(args) => {
+            let pool = connect_existing(&args.database).await?;
+            users::set_admin(&pool, &args.name, false).await?;
+            pool.close().await;
+        },
+        AdminCommand::ResetPassword(args) => {
+            let mut password = String::new();
+            io::stdin()
+                .read_line(&mut password)
+                .map_err(CommandError::ReadPassword)?;
+            let password = password.trim_end_matches(['\r', '\n']);
+            let pool = connect_existing(&args.database).await?;
+            users::reset_password(&pool, &args.name, password.to_owned())
+                .await?;
+            pool.close().await;
+        },
+    }
+    Ok(())
+}
+
+async fn run_fsck(args: &FsckArgs) -> Result<(), CommandError> {
+    let pool = connect_existing(&args.database).await?;
+    let report = fsck::check(&pool).await?;
+    for message in &report.integrity_errors {
+        println!("integrity: {message}");
+    }
+    for violation in &report.foreign_key_violations {
+        println!(
+            "foreign key: {} row {:?} references missing {}",
+            violation.table, violation.rowid, violation.parent
+        );
+    }
+    for orphan in &report.orphans {
+        println!("orphan: {} {} ({})", orphan.table, orphan.id, orphan.kind);
+        for dependent in &orphan.dependents {
+            println!("  along with: {} {}", dependent.table, dependent.data);
+        }
+    }
+    if report.is_clean() {
+        println!("ok");
+        return Ok(());
+    }
+    if let Some(report_path) = &args.repair {
+        fsck::repair(&pool, &report, report_path).await?;
+        println!(
+            "quarantined {} orphan(s) into {}",
+            report.orphans.len(),
+            report_path.display()
+        );
+    }
+    pool.close().await;
+    Err(CommandError::ProblemsFound(
+        report.integrity_errors.len()
+            + report.foreign_key_violations.len()
+            + report.orphans.len(),
+    ))
+}
+
+async fn run_dump(args: &DumpArgs) -> Result<(), CommandError> {
+    let pool = connect_existing(&args.database).await?;
+    dump::dump(&pool, &args.output, args.anonymize).await?;
+    pool.close().await;
+    Ok(())
+}
+
+async fn run_import_csv(args: &ImportCsvArgs) -> Result<(), CommandError> {
+    let mapping = ColumnMapping::parse(args.map.iter().map(String::as_str))?;
+    let pool = connect_existing(&args.database).await?;
+    let report =
+        import::import_csv(&pool, &args.file, &mapping, args.dry_run).await?;
+    pool.close().await;
+    for error in &report.errors {
+        println!("line {}: {}", error.line, error.message);
+    }
+    if !report.errors.is_empty() {
+        return Err(CommandError::InvalidRows(report.errors.len()));
+    }
+    if args.dry_run {
+        println!("dry run: no errors found");
+    } else {
+        println!("imported {} issue(s)", report.imported);
+    }
+    Ok(())
+}
+
+async fn run_import_archive(
+    args: &ImportArchiveArgs,
+) -> Result<(), CommandError> {
+    let bytes =
+        tokio::fs::read(&args.file).await.map_err(CommandError::ReadArchive)?;
+    let archive = Archive::parse(&bytes)?;
+    let pool = connect_existing(&args.database).await?;
+    let mut transaction = pool.begin().await.map_err(ArchiveError::from)?;
+    let report = archive::import(&mut transaction, &archive).await?;
+    if args.dry_run {
+        transaction.rollback().await.map_err(ArchiveError::from)?;
+    } else {
+        transaction.commit().await.map_err(ArchiveError::from)?;
+    }
+    pool.close().await;
+    for (kind, count) in [
+        ("status(es)", report.statuses),
+        ("issue type(s)", report.issue_types),
+        ("label(s)", report.labels),
+        ("milestone(s)", report.milestones),
+        ("version(s)", report.versions),
+    ] {
+        println!(
+            "{kind}: {} created, {} reused by name",
+            count.created, count.reused
+        );
+    }
+    for name in &report.unmatched_users {
+        println!("no user named {name:?}, issues left unassigned");
+    }
+    if args.dry_run {
+        println!("dry run: {} issue(s) would be imported", report.issues);
+    } else {
+        println!("imported {} issue(s)", report.issues);
+    }
+    Ok(())
+}
+
+async fn run_sync(args: &SyncArgs) -> Result<(), CommandError> {
+    let token = match &args.token_file {
+        Some(path) => Some(
+            fs::read_to_string(path).await.map_err(CommandError::ReadToken)?,
+        ),
+        None => None,
+    };
+    let pool = connect_existing(&args.database).await?;
+    let (Some(remote), Some(token)) = (&args.remote, &token) else {
+        let instance_id =
+            sync::new_instance_id(&pool).await.map_err(SyncError::from)?;
+        pool.close().await;
+        println!("new instance id {instance_id}");
+        return Ok(());
+    };
+    let report = sync::sync_with(&pool, remote, token.trim()).await;
+    pool.close().await;
+    let report = report?;
+    for (direction, applied) in
+        [("pulled", &report.pulled), ("pushed", &report.pushed)]
+    {
+        println!("{direction} {} change(s)", applied.applied);
+        for conflict in &applied.conflicts {
+            println!(
+                "  conflict on {} {}: {}",
+                conflict.entity, conflict.id, conflict.reason
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn run_changelog(args: &ChangelogArgs) -> Result<(), CommandError> {
+    let (scope, name) = match (&args.version, &args.milestone) {
+        (Some(version), _) => (Scope::Version, version),
+        (None, Some(milestone)) => (Scope::Milestone, milestone),
+        (None, None) => unreachable!("clap requires a version or milestone"),
+    };
+    let pool = connect_existing(&args.database).await?;
+    let mut conn = pool.acquire().await.map_err(CommandError::Changelog)?;
+    let notes = release_notes::for_name(&mut conn, scope, name).await.map_err(
+        |error| match error {
+            sqlx::Error::RowNotFound => CommandError::UnknownRelease,
+            error => CommandError::Changelog(error),
+        },
+    )?;
+    drop(conn);
+    pool.close().await;
+    match args.format {
+        ChangelogFormat::Md => print!("{}", notes.to_markdown()),
+        ChangelogFormat::Json => {
+            let json = serde_json::to_string_pretty(&notes)
+                .map_err(CommandError::ChangelogEncode)?;
+            println!("{json}");
+        },
+    }
     Ok(())
 }
 
 async fn try_main(cli: Cli) -> Result<(), MainError> {
-    setup_logger()?;
-    run_server_app(&cli).await?;
+    if cli.version_json {
+        println!("{}", json!(BUILD_INFO));
+        return Ok(());
+    }
+    let otlp_endpoint = match &cli.command {
+        Some(Command::Serve(serve)) => serve.otlp_endpoint.as_deref(),
+        _ => None,
+    };
+    let tracer_provider = setup_telemetry(otlp_endpoint)?;
+    let result = run_command(&cli).await;
+    if let Some(provider) = tracer_provider {
+        if let Err(error) = provider.shutdown() {
+            tracing::error!(
+                error = error.to_string(),
+                "Failed to flush traces"
+            );
+        }
+    }
+    result
+}
+
+async fn run_command(cli: &Cli) -> Result<(), MainError> {
+    match &cli.command {
+        Some(Command::Serve(args)) => run_server_app(args).await?,
+        Some(Command::Migrate(args)) => run_migrate(args).await?,
+        Some(Command::Backup(args)) => run_backup(args).await?,
+        Some(Command::Seed(args)) => run_seed(args).await?,
+        Some(Command::Admin(command)) => run_admin(command).await?,
+        Some(Command::Fsck(args)) => run_fsck(args).await?,
+        Some(Command::Dump(args)) => run_dump(args).await?,
+        Some(Command::Import(ImportCommand::Csv(args))) => {
+            run_import_csv(args).await?
+        },
+        Some(Command::Import(ImportCommand::Archive(args))) => {
+            run_import_archive(args).await?
+        },
+        Some(Command::Changelog(args)) => run_changelog(args).await?,
+        Some(Command::Sync(args)) => run_sync(args).await?,
+        None => unreachable!("clap requires a subcommand or --version-json"),
+    }
+    Ok(())
+}
+
+/// Parses the command line, filling in settings from `--config` that the
+/// command line and environment leave unset, as if they had been given as
+/// options.
+fn parse_cli() -> Result<Cli, ConfigError> {
+    let args: Vec<OsString> = env::args_os().collect();
+    let command = Cli::command();
+    // Required options may only be in the file, so errors are left for the
+    // final parse to report.
+    let Ok(matches) =
+        command.clone().ignore_errors(true).try_get_matches_from(&args)
+    else {
+        return Ok(parse_from(command, args));
+    };
+    // Options of `serve` come last, so settings from the file can be
+    // appended to the command line.
+    let (Some(matches), Some(serve)) =
+        (matches.subcommand_matches("serve"), command.find_subcommand("serve"))
+    else {
+        return Ok(parse_from(command, args));
+    };
+    let Some(path) = matches.get_one::<PathBuf>("config") else {
+        return Ok(parse_from(command, args));
+    };
+    let contents = std::fs::read_to_string(path)
+        .map_err(|error| ConfigError::Read(path.clone(), error))?;
+    let table: toml::Table = contents.parse().map_err(ConfigError::Parse)?;
+    let mut file_args = Vec::new();
+    let mut file_ids = Vec::new();
+    for (key, value) in &table {
+        let arg = serve
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(key) && key != "config")
+            .ok_or_else(|| ConfigError::UnknownSetting(key.clone()))?;
+        let source = matches.value_source(arg.get_id().as_str());
+        if matches!(
+            source,
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        ) {
+            continue;
+        }
+        let is_flag = !arg.get_action().takes_values();
+        push_setting(&mut file_args, key, value, is_flag)?;
+        file_ids.push(arg.get_id().clone());
+    }
+    // Lists in the file are passed one item at a time, so commas inside a
+    // value must not split it.
+    let command = command.mut_subcommand("serve", |serve| {
+        file_ids.into_iter().fold(serve, |serve, id| {
+            serve.mut_arg(id, |arg| arg.value_delimiter(None))
+        })
+    });
+    Ok(parse_from(command, args.into_iter().chain(file_args)))
+}
+
+fn parse_from<I>(mut command: clap::Command, args: I) -> Cli
+where
+    I: IntoIterator,
+    I::Item: Into<OsString> + Clone,
+{
+    let mut matches = command.clone().get_matches_from(args);
+    Cli::from_arg_matches_mut(&mut matches)
+        .map_err(|error| error.format(&mut command))
+        .unwrap_or_else(|error| error.exit())
+}
+
+/// Arrays become the option repeated once per element, and flags are only
+/// passed when true.
+fn push_setting(
+    args: &mut Vec<OsString>,
+    key: &str,
+    value: &toml::Value,
+    is_flag: bool,
+) -> Result<(), ConfigError> {
+    let value = match value {
+        toml::Value::Array(values) => {
+            for value in values {
+                if value.is_array() {
+                    return Err(ConfigError::InvalidValue(key.to_owned()));
+                }
+                push_setting(args, key, value, is_flag)?;
+            }
+            return Ok(());
+        },
+        toml::Value::Boolean(enabled) if is_flag => {
+            if *enabled {
+                args.push(format!("--{key}").into());
+            }
+            return Ok(());
+        },
+        toml::Value::String(value) => value.clone(),
+        toml::Value::Integer(value) => value.to_string(),
+        toml::Value::Float(value) => value.to_string(),
+        toml::Value::Boolean(value) => value.to_string(),
+        _ => return Err(ConfigError::InvalidValue(key.to_owned())),
+    };
+    args.push(format!("--{key}={value}").into());
     Ok(())
 }
 
@@ -116,8 +1506,12 @@ fn print_fatal_error(error: MainError) {
 
 #[tokio::main]
 async fn main() {
-    let cli = Cli::parse();
-    if let Err(error) = try_main(cli).await {
+    let result = match parse_cli() {
+        Ok(cli) => try_main(cli).await,
+        Err(error) => Err(error.into()),
+    };
+    if let Err(error) = result {
         print_fatal_error(error);
+        process::exit(1);
     }
 }
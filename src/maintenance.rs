@@ -0,0 +1,93 @@
+use std::sync::RwLock;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, Pool, Row};
+
+use crate::RDBMS;
+
+/// Persisted maintenance flag, shared by the API and the static pages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MaintenanceState {
+    pub enabled: bool,
+    /// Seconds clients are told to wait before retrying.
+    #[serde(default = "default_retry_after")]
+    pub retry_after: u64,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+fn default_retry_after() -> u64 {
+    3600
+}
+
+/// Reads the flag from the database once and serves it from memory
+/// afterwards.
+#[derive(Debug)]
+pub struct Maintenance {
+    pool: Pool<RDBMS>,
+    cached: RwLock<Option<MaintenanceState>>,
+}
+
+impl Maintenance {
+    pub fn new(pool: Pool<RDBMS>) -> Self {
+        Self { pool, cached: RwLock::new(None) }
+    }
+
+    pub async fn state(&self) -> Result<MaintenanceState, sqlx::Error> {
+        let cached = self
+            .cached
+            .read()
+            .unwrap_or_else(|error| error.into_inner())
+            .clone();
+        if let Some(state) = cached {
+            return Ok(state);
+        }
+        let sql = "SELECT enabled, retry_after, message FROM maintenance";
+        let row = query(sql).fetch_one(&self.pool).await?;
+        let state = MaintenanceState {
+            enabled: row.try_get("enabled")?,
+            retry_after: row.try_get::<i64, _>("retry_after")? as u64,
+            message: row.try_get("message")?,
+        };
+        self.store(state.clone());
+        Ok(state)
+    }
+
+    /// Whether requests should be turned away, failing open when the flag
+    /// cannot be read.
+    pub async fn active(&self) -> Option<MaintenanceState> {
+        match self.state().await {
+            Ok(state) => state.enabled.then_some(state),
+            Err(error) => {
+                tracing::error!(
+                    error = error.to_string(),
+                    "Failed to read maintenance flag"
+                );
+                None
+            },
+        }
+    }
+
+    pub async fn set(
+        &self,
+        state: MaintenanceState,
+    ) -> Result<(), sqlx::Error> {
+        query(
+            "UPDATE maintenance \
+             SET enabled = ?, retry_after = ?, message = ?",
+        )
+        .bind(state.enabled)
+        .bind(state.retry_after as i64)
+        .bind(&state.message)
+        .execute(&self.pool)
+        .await?;
+        self.store(state);
+        Ok(())
+    }
+
+    fn store(&self, state: MaintenanceState) {
+        *self.cached.write().unwrap_or_else(|error| error.into_inner()) =
+            Some(state);
+    }
+}
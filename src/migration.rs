@@ -0,0 +1,333 @@
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use sqlx::{
+    migrate::{MigrateError, Migrator},
+    query,
+    query_scalar,
+    Pool,
+};
+use thiserror::Error;
+
+use crate::{
+    dump::{self, DumpError},
+    RDBMS,
+};
+
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// A single-connection in-memory database with every migration applied.
+#[cfg(test)]
+pub(crate) async fn migrated_pool() -> Pool<RDBMS> {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    MIGRATOR.run(&pool).await.unwrap();
+    pool
+}
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("Failed to read applied migrations")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+    #[error("Failed to back up database before migrating")]
+    Backup(#[source] DumpError),
+    #[error("Failed to migrate database updates")]
+    Migrate(#[source] MigrateError),
+    #[error(
+        "Failed to migrate database updates, copy {} over the database to \
+         roll back",
+        .backup.display()
+    )]
+    MigrateWithBackup {
+        backup: PathBuf,
+        #[source]
+        source: MigrateError,
+    },
+    #[error(
+        "Migration {version} ({description}) has no down script, restore a \
+         pre-migrate backup instead"
+    )]
+    Irreversible { version: i64, description: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingMigration {
+    pub version: i64,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationPlan {
+    pub applied: usize,
+    pub pending: Vec<PendingMigration>,
+}
+
+impl MigrationPlan {
+    /// A database that never went through a migration holds nothing worth
+    /// backing up.
+    pub fn needs_backup(&self) -> bool {
+        self.applied > 0 && !self.pending.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevertPlan {
+    /// Version the database is left at, zero when every migration is
+    /// reverted.
+    pub target: i64,
+    /// Newest first, in the order they are reverted.
+    pub reverted: Vec<PendingMigration>,
+}
+
+/// Versions of the migrations recorded in the database, oldest first.
+async fn applied_versions(
+    pool: &Pool<RDBMS>,
+) -> Result<Vec<i64>, MigrationError> {
+    let has_table = query(
+        "SELECT 1 FROM sqlite_master \
+         WHERE type = 'table' AND name = '_sqlx_migrations'",
+    )
+    .fetch_optional(pool)
+    .await?
+    .is_some();
+    if !has_table {
+        return Ok(Vec::new());
+    }
+    let versions = query_scalar(
+        "SELECT version FROM _sqlx_migrations WHERE success \
+         ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(versions)
+}
+
+/// Compares the embedded migrations with the ones recorded in the database,
+/// without writing anything.
+pub async fn plan(pool: &Pool<RDBMS>) -> Result<MigrationPlan, MigrationError> {
+    let applied: HashSet<i64> =
+        applied_versions(pool).await?.into_iter().collect();
+    let pending = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| PendingMigration {
+            version: migration.version,
+            description: migration.description.to_string(),
+        })
+        .collect();
+    Ok(MigrationPlan { applied: applied.len(), pending })
+}
+
+/// Lists every embedded migration and whether the database went through it.
+pub async fn status(
+    pool: &Pool<RDBMS>,
+) -> Result<Vec<MigrationStatus>, MigrationError> {
+    let applied: HashSet<i64> =
+        applied_versions(pool).await?.into_iter().collect();
+    let status = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.contains(&migration.version),
+        })
+        .collect();
+    Ok(status)
+}
+
+/// Picks the last `count` applied migrations, failing if any of them cannot
+/// be reverted.
+pub async fn plan_revert(
+    pool: &Pool<RDBMS>,
+    count: usize,
+) -> Result<RevertPlan, MigrationError> {
+    let mut applied = applied_versions(pool).await?;
+    let kept = applied.len().saturating_sub(count);
+    let mut reverted = Vec::new();
+    for version in applied.drain(kept..).rev() {
+        let down = MIGRATOR.iter().find(|migration| {
+            migration.version == version
+                && migration.migration_type.is_down_migration()
+        });
+        let description = MIGRATOR
+            .iter()
+            .find(|migration| migration.version == version)
+            .map_or_else(String::new, |migration| {
+                migration.description.to_string()
+            });
+        if down.is_none() {
+            return Err(MigrationError::Irreversible { version, description });
+        }
+        reverted.push(PendingMigration { version, description });
+    }
+    let target = applied.last().copied().unwrap_or(0);
+    Ok(RevertPlan { target, reverted })
+}
+
+/// Path next to the database, named after `kind` and the current time.
+pub fn backup_path(database: &Path, kind: &str) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let mut name = OsString::from(database.as_os_str());
+    name.push(format!(".{kind}-{timestamp}.bak"));
+    PathBuf::from(name)
+}
+
+/// Copies the database next to itself, named after the time of the backup,
+/// and returns the path of the copy.
+pub async fn backup(
+    pool: &Pool<RDBMS>,
+    database: &Path,
+) -> Result<PathBuf, MigrationError> {
+    let path = backup_path(database, "pre-migrate");
+    dump::dump(pool, &path, false).await.map_err(MigrationError::Backup)?;
+    Ok(path)
+}
+
+/// Applies the pending migrations of `plan`, backing up `database` first
+/// when it holds anything. In-memory databases have no path and are never
+/// backed up.
+pub async fn apply(
+    pool: &Pool<RDBMS>,
+    plan: &MigrationPlan,
+    database: Option<&Path>,
+) -> Result<(), MigrationError> {
+    let backup = match database {
+        Some(database) if plan.needs_backup() => {
+            let backup = backup(pool, database).await?;
+            tracing::info!(
+                backup = %backup.display(),
+                pending = plan.pending.len(),
+                "Backed up database before migrating, copy the backup over \
+                 the database to roll back"
+            );
+            Some(backup)
+        },
+        _ => None,
+    };
+    if let Err(source) = MIGRATOR.run(pool).await {
+        return Err(match backup {
+            Some(backup) => {
+                MigrationError::MigrateWithBackup { backup, source }
+            },
+            None => MigrationError::Migrate(source),
+        });
+    }
+    Ok(())
+}
+
+/// Reverts the migrations of `plan`, backing up `database` first.
+pub async fn revert(
+    pool: &Pool<RDBMS>,
+    plan: &RevertPlan,
+    database: &Path,
+) -> Result<(), MigrationError> {
+    if plan.reverted.is_empty() {
+        return Ok(());
+    }
+    let backup = backup(pool, database).await?;
+    tracing::info!(
+        backup = %backup.display(),
+        reverted = plan.reverted.len(),
+        "Backed up database before reverting migrations"
+    );
+    MIGRATOR
+        .undo(pool, plan.target)
+        .await
+        .map_err(|source| MigrationError::MigrateWithBackup { backup, source })
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn status_marks_every_migration_applied() {
+        let pool = migrated_pool().await;
+        let status = status(&pool).await.unwrap();
+        assert!(!status.is_empty());
+        assert!(status.iter().all(|migration| migration.applied));
+    }
+
+    #[tokio::test]
+    async fn revert_nothing_keeps_last_version() {
+        let pool = migrated_pool().await;
+        let plan = plan_revert(&pool, 0).await.unwrap();
+        let last = MIGRATOR.iter().map(|migration| migration.version).max();
+        assert_eq!(Some(plan.target), last);
+        assert!(plan.reverted.is_empty());
+    }
+
+    #[tokio::test]
+    async fn revert_without_down_script_fails() {
+        let pool = migrated_pool().await;
+        let result = plan_revert(&pool, MIGRATOR.iter().count()).await;
+        assert!(matches!(
+            result,
+            Err(MigrationError::Irreversible { version: 1, .. })
+        ));
+    }
+
+    /// Tables with their columns, then indexes and triggers, by name.
+    async fn schema(pool: &Pool<RDBMS>) -> Vec<String> {
+        query_scalar(
+            "SELECT format('%s.%s %s %d %s %d', m.name, c.name, c.type, \
+             c.\"notnull\", c.dflt_value, c.pk) \
+             FROM sqlite_master AS m, pragma_table_info(m.name) AS c \
+             WHERE m.type = 'table' AND m.name NOT LIKE '_sqlx%' \
+             UNION ALL \
+             SELECT format('%s %s', type, name) FROM sqlite_master \
+             WHERE type IN ('index', 'trigger') \
+             AND tbl_name NOT LIKE '_sqlx%' \
+             ORDER BY 1",
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn later_migrations_revert_to_the_initial_schema() {
+        let initial = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let first = MIGRATOR.iter().next().unwrap();
+        sqlx::raw_sql(&first.sql).execute(&initial).await.unwrap();
+
+        let pool = migrated_pool().await;
+        let count = status(&pool).await.unwrap().len() - 1;
+        let plan = plan_revert(&pool, count).await.unwrap();
+        assert_eq!(plan.target, first.version);
+        MIGRATOR.undo(&pool, plan.target).await.unwrap();
+        assert_eq!(schema(&pool).await, schema(&initial).await);
+
+        MIGRATOR.run(&pool).await.unwrap();
+        let status = status(&pool).await.unwrap();
+        assert!(status.iter().all(|migration| migration.applied));
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt::Write;
+
+use futures::TryStreamExt;
+use serde::Serialize;
+use sqlx::{query, Row, SqliteConnection};
+
+const UNLABELED_GROUP: &str = "Other";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Version,
+    Milestone,
+}
+
+impl Scope {
+    fn table(self) -> &'static str {
+        match self {
+            Self::Version => "versions",
+            Self::Milestone => "milestones",
+        }
+    }
+
+    fn issue_column(self) -> &'static str {
+        match self {
+            Self::Version => "fixed_in_version",
+            Self::Milestone => "milestone",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReleaseNote {
+    pub issue_id: i64,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReleaseGroup {
+    pub name: String,
+    pub issues: Vec<ReleaseNote>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReleaseNotes {
+    pub title: String,
+    pub groups: Vec<ReleaseGroup>,
+}
+
+impl ReleaseNotes {
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# {}\n", self.title);
+        for group in &self.groups {
+            let _ = write!(markdown, "\n## {}\n\n", group.name);
+            for issue in &group.issues {
+                let _ =
+                    writeln!(markdown, "- #{} {}", issue.issue_id, issue.title);
+            }
+        }
+        markdown
+    }
+}
+
+pub async fn for_version(
+    connection: &mut SqliteConnection,
+    version_id: i64,
+) -> Result<ReleaseNotes, sqlx::Error> {
+    let row = query("SELECT name FROM versions WHERE id = ?")
+        .bind(version_id)
+        .fetch_one(&mut *connection)
+        .await?;
+    let title = row.try_get("name")?;
+    collect(connection, Scope::Version, version_id, title).await
+}
+
+pub async fn for_name(
+    connection: &mut SqliteConnection,
+    scope: Scope,
+    name: &str,
+) -> Result<ReleaseNotes, sqlx::Error> {
+    let sql = format!("SELECT id FROM {} WHERE name = ?", scope.table());
+    let row = query(&sql).bind(name).fetch_one(&mut *connection).await?;
+    let id = row.try_get("id")?;
+    collect(connection, scope, id, name.to_owned()).await
+}
+
+async fn collect(
+    connection: &mut SqliteConnection,
+    scope: Scope,
+    id: i64,
+    title: String,
+) -> Result<ReleaseNotes, sqlx::Error> {
+    let sql = format!(
+        "SELECT issues.id, issues.title, ( \
+             SELECT MIN(labels.name) FROM issue_labels \
+             JOIN labels ON labels.id = issue_labels.label \
+             WHERE issue_labels.issue = issues.id \
+         ) AS label \
+         FROM issues WHERE issues.{} = ? \
+         ORDER BY label IS NULL, label, issues.id",
+        scope.issue_column(),
+    );
+    let mut groups: Vec<ReleaseGroup> = Vec::new();
+    let mut stream = query(&sql).bind(id).fetch(connection);
+    while let Some(row) = stream.try_next().await? {
+        let label: Option<String> = row.try_get("label")?;
+        let group_name = label.as_deref().unwrap_or(UNLABELED_GROUP);
+        let note = ReleaseNote {
+            issue_id: row.try_get("id")?,
+            title: row.try_get("title")?,
+        };
+        match groups.last_mut() {
+            Some(group) if group.name == group_name => group.issues.push(note),
+            _ => groups.push(ReleaseGroup {
+                name: group_name.to_owned(),
+                issues: vec![note],
+            }),
+        }
+    }
+    Ok(ReleaseNotes { title, groups })
+}
@@ -0,0 +1,68 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+pub const REQUEST_ID_HEADER: HeaderName =
+    HeaderName::from_static("x-request-id");
+
+/// Longest client supplied ID that is honored.
+const MAX_LEN: usize = 128;
+
+const GENERATED_BYTES: usize = 16;
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+/// Identifies a request in logs, traces and error responses, so that users
+/// can quote it when reporting a failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(HeaderValue);
+
+impl RequestId {
+    fn from_request(request: &Request) -> Self {
+        request
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .filter(|value| Self::is_acceptable(value))
+            .map_or_else(Self::generate, |value| Self(value.clone()))
+    }
+
+    fn is_acceptable(value: &HeaderValue) -> bool {
+        let bytes = value.as_bytes();
+        !bytes.is_empty()
+            && bytes.len() <= MAX_LEN
+            && bytes.iter().all(u8::is_ascii_graphic)
+    }
+
+    fn generate() -> Self {
+        let mut bytes = [0; GENERATED_BYTES];
+        OsRng.fill_bytes(&mut bytes);
+        let id: String =
+            bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+        Self(HeaderValue::from_str(&id).expect("hex is a valid header value"))
+    }
+
+    /// ID of the request being handled by the current task, if any.
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Self::clone).ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.to_str().expect("request IDs are visible ASCII")
+    }
+}
+
+/// Honors a well-formed `X-Request-Id` from the client, generates one
+/// otherwise, and echoes it back in the response.
+pub async fn propagate(mut request: Request, next: Next) -> Response {
+    let id = RequestId::from_request(&request);
+    request.extensions_mut().insert(id.clone());
+    let mut response = CURRENT.scope(id.clone(), next.run(request)).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER, id.0);
+    response
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use axum_extra::extract::cookie::Key;
+use thiserror::Error;
+
+pub const MIN_SECRET_LEN: usize = 32;
+
+#[derive(Debug, Error)]
+pub enum SessionConfigError {
+    #[error("Session secret must be at least {MIN_SECRET_LEN} bytes long")]
+    SecretTooShort,
+}
+
+#[derive(Clone)]
+pub struct SessionConfig {
+    key: Key,
+    ttl: Duration,
+    secure_cookies: bool,
+}
+
+impl SessionConfig {
+    pub fn new(
+        secret: Option<&[u8]>,
+        ttl: Duration,
+    ) -> Result<Self, SessionConfigError> {
+        let key = match secret {
+            Some(secret) if secret.len() < MIN_SECRET_LEN => {
+                return Err(SessionConfigError::SecretTooShort);
+            },
+            Some(secret) => Key::derive_from(secret),
+            None => Key::generate(),
+        };
+        Ok(Self { key, ttl, secure_cookies: false })
+    }
+
+    /// Marks cookies `Secure`, so that browsers only send them over HTTPS.
+    pub fn with_secure_cookies(mut self, secure_cookies: bool) -> Self {
+        self.secure_cookies = secure_cookies;
+        self
+    }
+
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn secure_cookies(&self) -> bool {
+        self.secure_cookies
+    }
+}
@@ -6,7 +6,10 @@ use std::{
 use axum::{
     body::{Body, Bytes},
     extract,
-    http::StatusCode,
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE, RETRY_AFTER},
+        StatusCode,
+    },
     response::{IntoResponse, Response},
     routing::get,
     Router,
@@ -14,11 +17,13 @@ use axum::{
 use futures::Stream;
 use thiserror::Error;
 use tokio::{
-    fs::File,
+    fs::{self, File},
     io::{self, BufReader},
 };
 use tokio_util::io::ReaderStream;
 
+use crate::maintenance::Maintenance;
+
 #[derive(Debug, Error)]
 enum RequestError {
     #[error("Failed to open file")]
@@ -46,17 +51,25 @@ impl IntoResponse for RequestError {
     }
 }
 
+/// Served with 503 in place of HTML documents during maintenance.
+const MAINTENANCE_PAGE: &str = "maintenance.html";
+
+const FALLBACK_MAINTENANCE_PAGE: &str = "Under maintenance";
+
 #[derive(Debug)]
 struct Resources {
     base_dir: PathBuf,
+    maintenance: Arc<Maintenance>,
 }
 
 impl Resources {
+    /// Also gives the file length, so that compression can tell small
+    /// files apart.
     async fn stream_file(
         &self,
         subpath: String,
     ) -> Result<
-        impl Stream<Item = Result<Bytes, io::Error>> + Send + 'static,
+        (impl Stream<Item = Result<Bytes, io::Error>> + Send + 'static, u64),
         RequestError,
     > {
         let full_path = self.base_dir.join(&subpath);
@@ -68,20 +81,57 @@ impl Resources {
         }
         let file =
             File::open(&full_path).await.map_err(RequestError::FileOpen)?;
+        let length =
+            file.metadata().await.map_err(RequestError::FileOpen)?.len();
         let reader = ReaderStream::new(BufReader::new(file));
-        Ok(reader)
+        Ok((reader, length))
+    }
+
+    async fn maintenance_page(&self, subpath: &str) -> Option<Response> {
+        if !subpath.ends_with(".html") {
+            return None;
+        }
+        let state = self.maintenance.active().await?;
+        let page = fs::read(self.base_dir.join(MAINTENANCE_PAGE)).await;
+        let headers = [(RETRY_AFTER, state.retry_after)];
+        let response = match page {
+            Ok(page) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                headers,
+                [(CONTENT_TYPE, "text/html; charset=utf-8")],
+                page,
+            )
+                .into_response(),
+            Err(_) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                headers,
+                FALLBACK_MAINTENANCE_PAGE,
+            )
+                .into_response(),
+        };
+        Some(response)
     }
 }
 
-pub fn router(base_dir: impl Into<PathBuf>) -> Router {
-    let resources = Arc::new(Resources { base_dir: base_dir.into() });
+pub fn router(
+    base_dir: impl Into<PathBuf>,
+    maintenance: Arc<Maintenance>,
+) -> Router {
+    let resources =
+        Arc::new(Resources { base_dir: base_dir.into(), maintenance });
     Router::new().route(
         "/*path",
-        get(move |extract::Path(subpath)| async move {
+        get(move |extract::Path(subpath): extract::Path<String>| async move {
+            if let Some(page) = resources.maintenance_page(&subpath).await {
+                return page;
+            }
             match resources.stream_file(subpath).await {
-                Ok(stream) => {
-                    (StatusCode::OK, Body::from_stream(stream)).into_response()
-                },
+                Ok((stream, length)) => (
+                    StatusCode::OK,
+                    [(CONTENT_LENGTH, length)],
+                    Body::from_stream(stream),
+                )
+                    .into_response(),
                 Err(error) => error.into_response(),
             }
         }),
@@ -1,3 +1,5 @@
+use std::convert::Infallible;
+
 use axum::http::StatusCode;
 use serde::Serialize;
 
@@ -5,6 +7,12 @@ pub trait ResponseStatusCode {
     fn status_code(&self) -> StatusCode;
 }
 
+impl ResponseStatusCode for Infallible {
+    fn status_code(&self) -> StatusCode {
+        match *self {}
+    }
+}
+
 pub trait WithResultStatus {
     type Ok;
     type Err;
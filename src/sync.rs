@@ -0,0 +1,1076 @@
+use std::collections::HashSet;
+
+use futures::TryStreamExt;
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{
+    query,
+    query::QueryScalar,
+    query_scalar,
+    sqlite::SqliteArguments,
+    Connection,
+    Pool,
+    Row,
+    Sqlite,
+    SqliteConnection,
+};
+use thiserror::Error;
+
+use crate::{archive, RDBMS};
+
+/// Largest number of changes sent in one request.
+pub const MAX_BATCH: i64 = 500;
+
+struct SyncedEntity {
+    table: &'static str,
+    columns: &'static [&'static str],
+    /// Bumps the row version so that `If-Match` holders notice the change.
+    versioned: bool,
+    /// Unique column under which a row created on both instances is
+    /// recognized as the same row, as archive imports do.
+    name: Option<&'static str>,
+    /// Columns holding ids of other synced rows, with their entity. They
+    /// are sent as global ids.
+    references: &'static [(&'static str, &'static str)],
+    links: Option<SyncedLinks>,
+}
+
+/// Link table replaced as a whole with the row.
+struct SyncedLinks {
+    table: &'static str,
+    own: &'static str,
+    linked: &'static str,
+    /// Entity of the linked rows.
+    entity: &'static str,
+    /// Key of the linked global ids in the row data.
+    key: &'static str,
+}
+
+/// Users and their assignments stay local to each instance, as do
+/// blockings and checks. References only point to entities listed earlier,
+/// which is the order changes are applied in.
+const SYNCED_ENTITIES: &[SyncedEntity] = &[
+    SyncedEntity {
+        table: "issue_statuses",
+        columns: &["name", "closed"],
+        versioned: true,
+        name: Some("name"),
+        references: &[],
+        links: None,
+    },
+    SyncedEntity {
+        table: "issue_types",
+        columns: &["name", "template"],
+        versioned: false,
+        name: Some("name"),
+        references: &[],
+        links: Some(SyncedLinks {
+            table: "issue_type_statuses",
+            own: "type",
+            linked: "status",
+            entity: "issue_statuses",
+            key: "statuses",
+        }),
+    },
+    SyncedEntity {
+        table: "labels",
+        columns: &["name"],
+        versioned: false,
+        name: Some("name"),
+        references: &[],
+        links: None,
+    },
+    SyncedEntity {
+        table: "milestones",
+        columns: &["name", "description", "due_date"],
+        versioned: false,
+        name: Some("name"),
+        references: &[],
+        links: None,
+    },
+    SyncedEntity {
+        table: "versions",
+        columns: &["name", "release_date"],
+        versioned: false,
+        name: Some("name"),
+        references: &[],
+        links: None,
+    },
+    SyncedEntity {
+        table: "issues",
+        columns: &[
+            "title",
+            "description",
+            "status",
+            "parent",
+            "milestone",
+            "affects_version",
+            "fixed_in_version",
+            "type",
+            "created_at",
+            "updated_at",
+        ],
+        versioned: true,
+        name: None,
+        references: &[
+            ("status", "issue_statuses"),
+            ("parent", "issues"),
+            ("milestone", "milestones"),
+            ("affects_version", "versions"),
+            ("fixed_in_version", "versions"),
+            ("type", "issue_types"),
+        ],
+        links: Some(SyncedLinks {
+            table: "issue_labels",
+            own: "issue",
+            linked: "label",
+            entity: "labels",
+            key: "labels",
+        }),
+    },
+];
+
+fn synced_entity(table: &str) -> Option<&'static SyncedEntity> {
+    SYNCED_ENTITIES.iter().find(|entity| entity.table == table)
+}
+
+fn apply_order(table: &str) -> usize {
+    SYNCED_ENTITIES
+        .iter()
+        .position(|entity| entity.table == table)
+        .unwrap_or(SYNCED_ENTITIES.len())
+}
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error(
+        "Both instances have the same instance id, run `sync \
+         --new-instance-id` on one of them"
+    )]
+    SameInstance,
+    #[error("Remote instance failed to sync: {}", .0.join(", "))]
+    Remote(Vec<String>),
+    #[error("Failed to reach remote instance")]
+    Http(
+        #[source]
+        #[from]
+        reqwest::Error,
+    ),
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct InstanceInfo {
+    pub instance_id: String,
+    /// Sequence number of the latest change.
+    pub checkpoint: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Change {
+    pub seq: i64,
+    pub entity: String,
+    /// Global id of the row, the same on every instance that has it.
+    pub id: String,
+    pub deleted: bool,
+    /// Columns of the row as it is now, missing for deletions. References
+    /// to other rows hold their global ids.
+    #[serde(default)]
+    pub data: Option<Map<String, Value>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChangeFeed {
+    pub instance_id: String,
+    /// Where the next page, or the next sync, starts from.
+    pub checkpoint: i64,
+    pub more: bool,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChangeBatch {
+    /// Instance the changes come from.
+    pub instance_id: String,
+    /// Latest change of the receiving instance the sender has seen. Rows
+    /// the receiver changed after it are reported as conflicts.
+    pub seen: i64,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Conflict {
+    pub entity: String,
+    pub id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ApplyReport {
+    pub applied: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SyncReport {
+    pub pulled: ApplyReport,
+    pub pushed: ApplyReport,
+}
+
+pub async fn instance(
+    conn: &mut SqliteConnection,
+) -> Result<InstanceInfo, sqlx::Error> {
+    let instance_id =
+        query_scalar("SELECT instance_id FROM sync_instance WHERE id = 1")
+            .fetch_one(&mut *conn)
+            .await?;
+    let checkpoint = checkpoint(conn).await?;
+    Ok(InstanceInfo { instance_id, checkpoint })
+}
+
+async fn checkpoint(conn: &mut SqliteConnection) -> Result<i64, sqlx::Error> {
+    query_scalar("SELECT COALESCE(MAX(seq), 0) FROM sync_changes")
+        .fetch_one(conn)
+        .await
+}
+
+/// Gives the database a new identity, needed after copying it to start
+/// another instance. Peers that synced with the old identity are
+/// forgotten.
+pub async fn new_instance_id(
+    pool: &Pool<RDBMS>,
+) -> Result<String, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let instance_id = query_scalar(
+        "UPDATE sync_instance SET instance_id = lower(hex(randomblob(16))) \
+         WHERE id = 1 RETURNING instance_id",
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    query("DELETE FROM sync_peers").execute(&mut *transaction).await?;
+    transaction.commit().await?;
+    Ok(instance_id)
+}
+
+/// Lists the rows changed after `since`, each once with its latest state.
+/// Rows whose latest change came from `peer` are left out, since the peer
+/// already has them.
+pub async fn changes_since(
+    conn: &mut SqliteConnection,
+    since: i64,
+    peer: Option<&str>,
+    limit: i64,
+) -> Result<ChangeFeed, sqlx::Error> {
+    let InstanceInfo { instance_id, checkpoint } = instance(conn).await?;
+    let limit = limit.clamp(1, MAX_BATCH);
+    let mut pending = Vec::new();
+    let mut stream = query(
+        "SELECT seq, entity, row_id, global_id, deleted \
+         FROM sync_changes AS change \
+         WHERE seq > ? AND global_id IS NOT NULL \
+         AND seq = (SELECT MAX(seq) FROM sync_changes AS latest \
+         WHERE latest.entity = change.entity \
+         AND latest.global_id = change.global_id) \
+         AND (origin IS NULL OR origin IS NOT ?) \
+         ORDER BY seq LIMIT ?",
+    )
+    .bind(since)
+    .bind(peer)
+    .bind(limit)
+    .fetch(&mut *conn);
+    while let Some(row) = stream.try_next().await? {
+        let seq: i64 = row.try_get("seq")?;
+        let entity: String = row.try_get("entity")?;
+        let row_id: i64 = row.try_get("row_id")?;
+        let id: String = row.try_get("global_id")?;
+        let deleted: bool = row.try_get("deleted")?;
+        pending.push((seq, entity, row_id, id, deleted));
+    }
+    drop(stream);
+    let more = pending.len() as i64 == limit;
+    let checkpoint = match pending.last() {
+        Some((seq, ..)) if more => *seq,
+        _ => checkpoint,
+    };
+    let mut changes = Vec::new();
+    for (seq, entity, row_id, id, deleted) in pending {
+        let Some(synced) = synced_entity(&entity) else {
+            continue;
+        };
+        let data = match deleted {
+            true => None,
+            false => match row_data(conn, synced, row_id).await? {
+                Some(data) => Some(to_global(conn, synced, data).await?),
+                None => None,
+            },
+        };
+        let deleted = data.is_none();
+        changes.push(Change { seq, entity, id, deleted, data });
+    }
+    Ok(ChangeFeed { instance_id, checkpoint, more, changes })
+}
+
+/// Global id a row is sent under.
+async fn global_id(
+    conn: &mut SqliteConnection,
+    entity: &str,
+    row_id: i64,
+) -> Result<Option<String>, sqlx::Error> {
+    query_scalar(
+        "SELECT global_id FROM sync_ids WHERE entity = ? AND row_id = ? \
+         ORDER BY rowid LIMIT 1",
+    )
+    .bind(entity)
+    .bind(row_id)
+    .fetch_optional(conn)
+    .await
+}
+
+async fn local_id(
+    conn: &mut SqliteConnection,
+    entity: &str,
+    global_id: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    query_scalar(
+        "SELECT row_id FROM sync_ids WHERE entity = ? AND global_id = ?",
+    )
+    .bind(entity)
+    .bind(global_id)
+    .fetch_optional(conn)
+    .await
+}
+
+/// Columns of a row with local ids, and the ids of its links in order.
+async fn row_data(
+    conn: &mut SqliteConnection,
+    synced: &SyncedEntity,
+    id: i64,
+) -> Result<Option<Map<String, Value>>, sqlx::Error> {
+    let select = format!(
+        "SELECT {columns} FROM {table} WHERE id = ?",
+        columns = synced.columns.join(", "),
+        table = synced.table,
+    );
+    let Some(row) = query(&select).bind(id).fetch_optional(&mut *conn).await?
+    else {
+        return Ok(None);
+    };
+    let mut data = archive::row_object(&row)?;
+    if let Some(links) = &synced.links {
+        let select = format!(
+            "SELECT {linked} FROM {table} WHERE {own} = ? ORDER BY {linked}",
+            linked = links.linked,
+            table = links.table,
+            own = links.own,
+        );
+        let ids: Vec<i64> =
+            query_scalar(&select).bind(id).fetch_all(&mut *conn).await?;
+        data.insert(links.key.to_owned(), Value::from(ids));
+    }
+    Ok(Some(data))
+}
+
+async fn to_global(
+    conn: &mut SqliteConnection,
+    synced: &SyncedEntity,
+    mut data: Map<String, Value>,
+) -> Result<Map<String, Value>, sqlx::Error> {
+    for (column, entity) in synced.references {
+        let Some(id) = data.get(*column).and_then(Value::as_i64) else {
+            continue;
+        };
+        let global = global_id(conn, entity, id).await?;
+        data.insert((*column).to_owned(), Value::from(global));
+    }
+    if let Some(links) = &synced.links {
+        let ids: Vec<i64> = data
+            .get(links.key)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_i64)
+            .collect();
+        let mut globals = Vec::with_capacity(ids.len());
+        for id in ids {
+            globals.extend(global_id(conn, links.entity, id).await?);
+        }
+        globals.sort_unstable();
+        data.insert(links.key.to_owned(), Value::from(globals));
+    }
+    Ok(data)
+}
+
+#[derive(Debug, Error)]
+enum ResolveError {
+    #[error("References a row of {0} this instance does not have")]
+    Missing(&'static str),
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+/// Incoming row data translated to local ids.
+#[derive(Debug)]
+struct LocalRow {
+    data: Map<String, Value>,
+    /// Global id of a parent issue not received yet.
+    pending_parent: Option<String>,
+}
+
+async fn to_local(
+    conn: &mut SqliteConnection,
+    synced: &SyncedEntity,
+    data: &Map<String, Value>,
+) -> Result<LocalRow, ResolveError> {
+    let mut local = data.clone();
+    let mut pending_parent = None;
+    for (column, entity) in synced.references {
+        let Some(global) = data.get(*column).and_then(Value::as_str) else {
+            continue;
+        };
+        let id = match local_id(conn, entity, global).await? {
+            Some(id) => Value::from(id),
+            // Issues may arrive before their parent.
+            None if *column == "parent" => {
+                pending_parent = Some(global.to_owned());
+                Value::Null
+            },
+            None => return Err(ResolveError::Missing(entity)),
+        };
+        local.insert((*column).to_owned(), id);
+    }
+    if let Some(links) = &synced.links {
+        let globals = data
+            .get(links.key)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str);
+        let mut ids = Vec::new();
+        for global in globals {
+            let id = local_id(conn, links.entity, global).await?;
+            ids.push(id.ok_or(ResolveError::Missing(links.entity))?);
+        }
+        ids.sort_unstable();
+        ids.dedup();
+        local.insert(links.key.to_owned(), Value::from(ids));
+    }
+    Ok(LocalRow { data: local, pending_parent })
+}
+
+async fn row_by_name(
+    conn: &mut SqliteConnection,
+    synced: &SyncedEntity,
+    row: &LocalRow,
+) -> Result<Option<i64>, sqlx::Error> {
+    let Some(name) = synced.name else {
+        return Ok(None);
+    };
+    let select = format!("SELECT id FROM {} WHERE {name} = ?", synced.table);
+    bind_value(query_scalar(&select), row.data.get(name))
+        .fetch_optional(conn)
+        .await
+}
+
+fn bind_value<'q, O>(
+    query: QueryScalar<'q, Sqlite, O, SqliteArguments<'q>>,
+    value: Option<&Value>,
+) -> QueryScalar<'q, Sqlite, O, SqliteArguments<'q>> {
+    match value {
+        None | Some(Value::Null) => query.bind(None::<i64>),
+        Some(Value::Bool(value)) => query.bind(*value),
+        Some(Value::Number(number)) => match number.as_i64() {
+            Some(number) => query.bind(number),
+            None => query.bind(number.as_f64()),
+        },
+        Some(Value::String(value)) => query.bind(value.clone()),
+        Some(value) => query.bind(value.to_string()),
+    }
+}
+
+/// Writes an incoming row under `target`, or as a new row known by
+/// `global_id`, and deletes it when `row` is missing.
+async fn write_change(
+    conn: &mut SqliteConnection,
+    synced: &SyncedEntity,
+    global_id: &str,
+    target: Option<i64>,
+    row: Option<&LocalRow>,
+) -> Result<(), sqlx::Error> {
+    let Some(row) = row else {
+        let delete = format!("DELETE FROM {} WHERE id = ?", synced.table);
+        query(&delete).bind(target).execute(&mut *conn).await?;
+        return Ok(());
+    };
+    let id = match target {
+        Some(id) => {
+            let mut updates: Vec<String> = synced
+                .columns
+                .iter()
+                .map(|column| format!("{column} = ?"))
+                .collect();
+            if synced.versioned {
+                updates.push("version = version + 1".to_owned());
+            }
+            let update = format!(
+                "UPDATE {table} SET {updates} WHERE id = ? RETURNING id",
+                table = synced.table,
+                updates = updates.join(", "),
+            );
+            let mut update_query = query_scalar(&update);
+            for column in synced.columns {
+                update_query = bind_value(update_query, row.data.get(*column));
+            }
+            let id: i64 = update_query.bind(id).fetch_one(&mut *conn).await?;
+            query(
+                "INSERT OR IGNORE INTO sync_ids (global_id, entity, row_id) \
+                 VALUES (?, ?, ?)",
+            )
+            .bind(global_id)
+            .bind(synced.table)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+            id
+        },
+        None => {
+            let insert = format!(
+                "INSERT INTO {table} ({columns}) VALUES ({placeholders}) \
+                 RETURNING id",
+                table = synced.table,
+                columns = synced.columns.join(", "),
+                placeholders = vec!["?"; synced.columns.len()].join(", "),
+            );
+            let mut insert_query = query_scalar(&insert);
+            for column in synced.columns {
+                insert_query = bind_value(insert_query, row.data.get(*column));
+            }
+            let id: i64 = insert_query.fetch_one(&mut *conn).await?;
+            // The row was given a fresh global id when it was inserted; it
+            // keeps the one of the instance that created it instead.
+            for table in ["sync_ids", "sync_changes"] {
+                let update = format!(
+                    "UPDATE {table} SET global_id = ? \
+                     WHERE entity = ? AND row_id = ?"
+                );
+                query(&update)
+                    .bind(global_id)
+                    .bind(synced.table)
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            id
+        },
+    };
+    if let Some(links) = &synced.links {
+        let delete =
+            format!("DELETE FROM {} WHERE {} = ?", links.table, links.own);
+        query(&delete).bind(id).execute(&mut *conn).await?;
+        let insert = format!(
+            "INSERT INTO {} ({}, {}) VALUES (?, ?)",
+            links.table, links.own, links.linked,
+        );
+        let linked_ids = row
+            .data
+            .get(links.key)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_i64);
+        for linked_id in linked_ids {
+            query(&insert).bind(id).bind(linked_id).execute(&mut *conn).await?;
+        }
+    }
+    if synced.table == "issues" {
+        link_parents(conn, id, global_id, row).await?;
+    }
+    Ok(())
+}
+
+/// Remembers a parent that has not arrived yet, and points issues that
+/// were waiting for this one to it.
+async fn link_parents(
+    conn: &mut SqliteConnection,
+    id: i64,
+    global_id: &str,
+    row: &LocalRow,
+) -> Result<(), sqlx::Error> {
+    match &row.pending_parent {
+        Some(parent) => {
+            query(
+                "INSERT OR REPLACE INTO sync_pending_parents (issue, parent) \
+                 VALUES (?, ?)",
+            )
+            .bind(id)
+            .bind(parent)
+            .execute(&mut *conn)
+            .await?;
+        },
+        None => {
+            query("DELETE FROM sync_pending_parents WHERE issue = ?")
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+        },
+    }
+    query(
+        "UPDATE issues SET parent = ?, version = version + 1 \
+         WHERE id IN \
+         (SELECT issue FROM sync_pending_parents WHERE parent = ?)",
+    )
+    .bind(id)
+    .bind(global_id)
+    .execute(&mut *conn)
+    .await?;
+    query("DELETE FROM sync_pending_parents WHERE parent = ?")
+        .bind(global_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Applies changes from another instance, skipping rows that are already
+/// identical. Rows are matched by global id, or by name for rows the other
+/// instance created on its own; unmatched rows are created with new local
+/// ids. A row changed here after `batch.seen` is not overwritten but
+/// reported as a conflict, as is a change the database refuses or one that
+/// references a row this instance does not have. Meant to run inside a
+/// transaction.
+pub async fn apply(
+    conn: &mut SqliteConnection,
+    batch: &ChangeBatch,
+) -> Result<ApplyReport, SyncError> {
+    if instance(conn).await?.instance_id == batch.instance_id {
+        return Err(SyncError::SameInstance);
+    }
+    let mut changes: Vec<&Change> = batch.changes.iter().collect();
+    changes.sort_by_key(|change| apply_order(&change.entity));
+    let mut report = ApplyReport::default();
+    for change in changes {
+        let conflict = |reason: String| Conflict {
+            entity: change.entity.clone(),
+            id: change.id.clone(),
+            reason,
+        };
+        let Some(synced) = synced_entity(&change.entity) else {
+            report.conflicts.push(conflict("unknown entity".to_owned()));
+            continue;
+        };
+        let incoming = match change.data.as_ref().filter(|_| !change.deleted) {
+            Some(data) => match to_local(conn, synced, data).await {
+                Ok(row) => Some(row),
+                Err(ResolveError::Sqlx(error)) => return Err(error.into()),
+                Err(error) => {
+                    report.conflicts.push(conflict(error.to_string()));
+                    continue;
+                },
+            },
+            None => None,
+        };
+        let mut target = local_id(conn, &change.entity, &change.id).await?;
+        if let (None, Some(row)) = (target, &incoming) {
+            target = row_by_name(conn, synced, row).await?;
+        }
+        let current = match target {
+            Some(id) => row_data(conn, synced, id).await?,
+            None => None,
+        };
+        // Both sides may hold the same row, as after copying a database.
+        if current.as_ref() == incoming.as_ref().map(|row| &row.data) {
+            if let Some(id) = target {
+                query(
+                    "INSERT OR IGNORE INTO sync_ids \
+                     (global_id, entity, row_id) VALUES (?, ?, ?)",
+                )
+                .bind(&change.id)
+                .bind(&change.entity)
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+            }
+            continue;
+        }
+        let changed_here = query(
+            "SELECT 1 FROM sync_changes \
+             WHERE entity = ? AND row_id = ? AND seq > ? \
+             AND (origin IS NULL OR origin IS NOT ?) LIMIT 1",
+        )
+        .bind(&change.entity)
+        .bind(target)
+        .bind(batch.seen)
+        .bind(&batch.instance_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .is_some();
+        if changed_here {
+            report
+                .conflicts
+                .push(conflict("changed on both instances".to_owned()));
+            continue;
+        }
+        let before = checkpoint(conn).await?;
+        let mut savepoint = conn.begin().await?;
+        let written = write_change(
+            &mut savepoint,
+            synced,
+            &change.id,
+            target,
+            incoming.as_ref(),
+        )
+        .await;
+        match written {
+            Ok(()) => savepoint.commit().await?,
+            Err(sqlx::Error::Database(error)) => {
+                savepoint.rollback().await?;
+                report.conflicts.push(conflict(error.message().to_owned()));
+                continue;
+            },
+            Err(error) => return Err(error.into()),
+        }
+        // Changes written on behalf of the sender are not sent back to it.
+        query(
+            "UPDATE sync_changes SET origin = ? \
+             WHERE seq > ? AND origin IS NULL",
+        )
+        .bind(&batch.instance_id)
+        .bind(before)
+        .execute(&mut *conn)
+        .await?;
+        report.applied += 1;
+    }
+    Ok(report)
+}
+
+#[derive(Debug, Deserialize)]
+struct Envelope<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<String>,
+}
+
+async fn remote_data<T: DeserializeOwned>(
+    request: reqwest::RequestBuilder,
+) -> Result<T, SyncError> {
+    let envelope: Envelope<T> = request.send().await?.json().await?;
+    envelope.data.ok_or(SyncError::Remote(envelope.errors))
+}
+
+/// Pulls the changes of the instance at `remote` (its base URL), then
+/// pushes the local ones to it. Rows that conflicted on the way in are not
+/// pushed, so both instances keep their version until someone edits it
+/// again.
+pub async fn sync_with(
+    pool: &Pool<RDBMS>,
+    remote: &str,
+    token: &str,
+) -> Result<SyncReport, SyncError> {
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1/sync", remote.trim_end_matches('/'));
+    let mut conn = pool.acquire().await?;
+    let local = instance(&mut conn).await?;
+    let remote_instance: InstanceInfo =
+        remote_data(client.get(format!("{api}/instance")).bearer_auth(token))
+            .await?;
+    let remote_id = remote_instance.instance_id;
+    if remote_id == local.instance_id {
+        return Err(SyncError::SameInstance);
+    }
+    let peer = query(
+        "SELECT pulled_seq, pushed_seq FROM sync_peers WHERE instance_id = ?",
+    )
+    .bind(&remote_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let (mut pulled_seq, pushed_seq): (i64, i64) = match peer {
+        Some(row) => (row.try_get("pulled_seq")?, row.try_get("pushed_seq")?),
+        None => (0, 0),
+    };
+
+    let mut incoming = Vec::new();
+    loop {
+        let since = pulled_seq.to_string();
+        let feed: ChangeFeed = remote_data(
+            client
+                .get(format!("{api}/changes"))
+                .query(&[
+                    ("since", since.as_str()),
+                    ("peer", local.instance_id.as_str()),
+                ])
+                .bearer_auth(token),
+        )
+        .await?;
+        incoming.extend(feed.changes);
+        pulled_seq = feed.checkpoint;
+        if !feed.more {
+            break;
+        }
+    }
+    let batch = ChangeBatch {
+        instance_id: remote_id.clone(),
+        seen: pushed_seq,
+        changes: incoming,
+    };
+    let mut transaction = conn.begin().await?;
+    let pulled = apply(&mut transaction, &batch).await?;
+    transaction.commit().await?;
+    let conflicted: HashSet<(String, String)> = pulled
+        .conflicts
+        .iter()
+        .map(|conflict| (conflict.entity.clone(), conflict.id.clone()))
+        .collect();
+
+    // Gathered before sending, so that every batch can be applied in
+    // reference order.
+    let mut outgoing = Vec::new();
+    let mut since = pushed_seq;
+    loop {
+        let feed = changes_since(&mut conn, since, Some(&remote_id), MAX_BATCH)
+            .await?;
+        since = feed.checkpoint;
+        outgoing.extend(feed.changes.into_iter().filter(|change| {
+            !conflicted.contains(&(change.entity.clone(), change.id.clone()))
+        }));
+        if !feed.more {
+            break;
+        }
+    }
+    outgoing.sort_by_key(|change| apply_order(&change.entity));
+    let mut pushed = ApplyReport::default();
+    for changes in outgoing.chunks(MAX_BATCH as usize) {
+        let batch = ChangeBatch {
+            instance_id: local.instance_id.clone(),
+            seen: pulled_seq,
+            changes: changes.to_vec(),
+        };
+        let report: ApplyReport = remote_data(
+            client.post(format!("{api}/apply")).json(&batch).bearer_auth(token),
+        )
+        .await?;
+        pushed.applied += report.applied;
+        pushed.conflicts.extend(report.conflicts);
+    }
+
+    query(
+        "INSERT INTO sync_peers (instance_id, pulled_seq, pushed_seq, \
+         synced_at) VALUES (?, ?, ?, unixepoch()) \
+         ON CONFLICT (instance_id) DO UPDATE SET \
+         pulled_seq = excluded.pulled_seq, \
+         pushed_seq = excluded.pushed_seq, \
+         synced_at = excluded.synced_at",
+    )
+    .bind(&remote_id)
+    .bind(pulled_seq)
+    .bind(since)
+    .execute(&mut *conn)
+    .await?;
+    Ok(SyncReport { pulled, pushed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migration::migrated_pool;
+
+    async fn batch_from(
+        conn: &mut SqliteConnection,
+        since: i64,
+        seen: i64,
+    ) -> ChangeBatch {
+        let feed = changes_since(conn, since, None, MAX_BATCH).await.unwrap();
+        ChangeBatch {
+            instance_id: feed.instance_id,
+            seen,
+            changes: feed.changes,
+        }
+    }
+
+    #[tokio::test]
+    async fn changes_are_applied_once_per_row() {
+        let source = migrated_pool().await;
+        let target = migrated_pool().await;
+        let mut source = source.acquire().await.unwrap();
+        let mut target = target.acquire().await.unwrap();
+        query("INSERT INTO labels (name) VALUES ('bug')")
+            .execute(&mut *source)
+            .await
+            .unwrap();
+        query("UPDATE labels SET name = 'defect' WHERE id = 1")
+            .execute(&mut *source)
+            .await
+            .unwrap();
+        let batch = batch_from(&mut source, 0, 0).await;
+        assert_eq!(batch.changes.len(), 1);
+        let report = apply(&mut target, &batch).await.unwrap();
+        assert_eq!(report.applied, 1);
+        assert!(report.conflicts.is_empty());
+        let name: String = query_scalar("SELECT name FROM labels WHERE id = 1")
+            .fetch_one(&mut *target)
+            .await
+            .unwrap();
+        assert_eq!(name, "defect");
+        let echo =
+            changes_since(&mut target, 0, Some(&batch.instance_id), MAX_BATCH)
+                .await
+                .unwrap();
+        assert!(echo.changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_the_latest_change_per_row_is_kept() {
+        let pool = migrated_pool().await;
+        for sql in [
+            "INSERT INTO labels (name) VALUES ('bug')",
+            "UPDATE labels SET name = 'defect' WHERE id = 1",
+            "UPDATE labels SET name = 'flaw' WHERE id = 1",
+            "INSERT INTO labels (name) VALUES ('docs')",
+        ] {
+            query(sql).execute(&pool).await.unwrap();
+        }
+        let seqs: Vec<i64> = query_scalar(
+            "SELECT seq FROM sync_changes WHERE entity = 'labels' ORDER BY seq",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(seqs, [3, 4]);
+    }
+
+    #[tokio::test]
+    async fn row_changed_on_both_sides_conflicts() {
+        let source = migrated_pool().await;
+        let target = migrated_pool().await;
+        let mut source = source.acquire().await.unwrap();
+        let mut target = target.acquire().await.unwrap();
+        query("INSERT INTO labels (name) VALUES ('bug')")
+            .execute(&mut *source)
+            .await
+            .unwrap();
+        let batch = batch_from(&mut source, 0, 0).await;
+        apply(&mut target, &batch).await.unwrap();
+        let since = checkpoint(&mut source).await.unwrap();
+        let seen = checkpoint(&mut target).await.unwrap();
+        query("UPDATE labels SET name = 'defect'")
+            .execute(&mut *source)
+            .await
+            .unwrap();
+        query("UPDATE labels SET name = 'ui'")
+            .execute(&mut *target)
+            .await
+            .unwrap();
+        let batch = batch_from(&mut source, since, seen).await;
+        let report = apply(&mut target, &batch).await.unwrap();
+        assert_eq!(report.applied, 0);
+        assert_eq!(report.conflicts.len(), 1);
+        let name: String = query_scalar("SELECT name FROM labels")
+            .fetch_one(&mut *target)
+            .await
+            .unwrap();
+        assert_eq!(name, "ui");
+    }
+
+    async fn titles(conn: &mut SqliteConnection) -> Vec<String> {
+        query_scalar("SELECT title FROM issues ORDER BY title")
+            .fetch_all(conn)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn rows_created_on_both_sides_are_kept() {
+        let source = migrated_pool().await;
+        let target = migrated_pool().await;
+        let mut source = source.acquire().await.unwrap();
+        let mut target = target.acquire().await.unwrap();
+        for (conn, title) in [(&mut source, "a"), (&mut target, "b")] {
+            query("INSERT INTO issue_statuses (name) VALUES ('open')")
+                .execute(&mut **conn)
+                .await
+                .unwrap();
+            query(
+                "INSERT INTO issues (title, description, status) \
+                 VALUES (?, '', 1)",
+            )
+            .bind(title)
+            .execute(&mut **conn)
+            .await
+            .unwrap();
+        }
+        // The child comes before its parent in the feed.
+        query(
+            "INSERT INTO issues (title, description, status, parent) \
+             VALUES ('c', '', 1, 1)",
+        )
+        .execute(&mut *source)
+        .await
+        .unwrap();
+        query("UPDATE issues SET description = 'parent' WHERE id = 1")
+            .execute(&mut *source)
+            .await
+            .unwrap();
+
+        let pulled = batch_from(&mut source, 0, 0).await;
+        let report = apply(&mut target, &pulled).await.unwrap();
+        assert_eq!(report.applied, 2);
+        assert!(report.conflicts.is_empty());
+        let seen = checkpoint(&mut source).await.unwrap();
+        let feed =
+            changes_since(&mut target, 0, Some(&pulled.instance_id), MAX_BATCH)
+                .await
+                .unwrap();
+        let pushed = ChangeBatch {
+            instance_id: feed.instance_id,
+            seen,
+            changes: feed.changes,
+        };
+        let report = apply(&mut source, &pushed).await.unwrap();
+        assert_eq!(report.applied, 1);
+        assert!(report.conflicts.is_empty());
+
+        for conn in [&mut source, &mut target] {
+            assert_eq!(titles(conn).await, ["a", "b", "c"]);
+            let statuses: i64 =
+                query_scalar("SELECT COUNT(*) FROM issue_statuses")
+                    .fetch_one(&mut **conn)
+                    .await
+                    .unwrap();
+            assert_eq!(statuses, 1);
+            let parent: String = query_scalar(
+                "SELECT parent.title FROM issues AS child \
+                 JOIN issues AS parent ON parent.id = child.parent \
+                 WHERE child.title = 'c'",
+            )
+            .fetch_one(&mut **conn)
+            .await
+            .unwrap();
+            assert_eq!(parent, "a");
+        }
+    }
+
+    #[tokio::test]
+    async fn identical_rows_do_not_conflict() {
+        let source = migrated_pool().await;
+        let target = migrated_pool().await;
+        let mut source = source.acquire().await.unwrap();
+        let mut target = target.acquire().await.unwrap();
+        for conn in [&mut source, &mut target] {
+            query("INSERT INTO labels (name) VALUES ('bug')")
+                .execute(&mut **conn)
+                .await
+                .unwrap();
+        }
+        let batch = batch_from(&mut source, 0, 0).await;
+        let report = apply(&mut target, &batch).await.unwrap();
+        assert_eq!(report.applied, 0);
+        assert!(report.conflicts.is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+use std::{net::SocketAddr, sync::OnceLock, time::Instant};
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request},
+    http::{header::CONTENT_TYPE, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use sqlx::Pool;
+use tracing::Instrument;
+
+use crate::{request_id::RequestId, RDBMS};
+
+/// Route label of requests that matched no route, so that arbitrary paths
+/// do not each create a time series.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Access log lines can be silenced with
+/// `PORTABLE_ISSUER_LOG=portable_issuer::access=warn`.
+const ACCESS_LOG_TARGET: &str = "portable_issuer::access";
+
+static HANDLE: OnceLock<Option<PrometheusHandle>> = OnceLock::new();
+
+/// Installs the Prometheus recorder on first use. Later routers in the same
+/// process share it.
+pub fn handle() -> Option<&'static PrometheusHandle> {
+    HANDLE
+        .get_or_init(|| match PrometheusBuilder::new().install_recorder() {
+            Ok(handle) => Some(handle),
+            Err(error) => {
+                tracing::error!(
+                    error = error.to_string(),
+                    "Failed to install metrics recorder"
+                );
+                None
+            },
+        })
+        .as_ref()
+}
+
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let path = request.uri().path().to_owned();
+    let remote_addr = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.to_string());
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(UNMATCHED_ROUTE, MatchedPath::as_str)
+        .to_owned();
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.as_str().to_owned());
+    let span = tracing::info_span!(
+        "request",
+        id = request_id,
+        method = method,
+        route = route,
+        status = tracing::field::Empty,
+    );
+    let start = Instant::now();
+    let response = next.run(request).instrument(span.clone()).await;
+    span.record("status", response.status().as_u16());
+    let elapsed = start.elapsed();
+    span.in_scope(|| {
+        tracing::info!(
+            target: ACCESS_LOG_TARGET,
+            path,
+            latency_us = elapsed.as_micros() as u64,
+            remote_addr,
+        )
+    });
+    let status = format!("{}xx", response.status().as_u16() / 100);
+    counter!(
+        "http_requests_total",
+        "method" => method.clone(),
+        "route" => route.clone(),
+        "status" => status,
+    )
+    .increment(1);
+    histogram!(
+        "http_request_duration_seconds",
+        "method" => method,
+        "route" => route,
+    )
+    .record(elapsed.as_secs_f64());
+    response
+}
+
+pub async fn get_metrics(pool: Pool<RDBMS>) -> Response {
+    let Some(handle) = handle() else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+    gauge!("db_pool_connections").set(pool.size() as f64);
+    gauge!("db_pool_idle_connections").set(pool.num_idle() as f64);
+    ([(CONTENT_TYPE, "text/plain; version=0.0.4")], handle.render())
+        .into_response()
+}
@@ -0,0 +1,135 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::time;
+
+use crate::version::BUILD_INFO;
+
+#[derive(Debug, Error)]
+enum CheckError {
+    #[error("Failed to fetch releases")]
+    Fetch(#[source] reqwest::Error),
+    #[error("Release response has no version")]
+    MissingVersion,
+    #[error("Failed to parse release version")]
+    Parse(#[source] semver::Error),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct LatestRelease {
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    tag_name: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UpgradeCheckConfig {
+    pub releases_url: String,
+    pub interval: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct UpgradeStatus {
+    pub latest_version: String,
+    pub upgrade_available: bool,
+    pub checked_at: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UpgradeState {
+    enabled: bool,
+    latest: Arc<RwLock<Option<UpgradeStatus>>>,
+}
+
+impl UpgradeState {
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn latest(&self) -> Option<UpgradeStatus> {
+        self.latest.read().unwrap_or_else(|error| error.into_inner()).clone()
+    }
+
+    fn set_latest(&self, status: UpgradeStatus) {
+        *self.latest.write().unwrap_or_else(|error| error.into_inner()) =
+            Some(status);
+    }
+}
+
+pub fn spawn_checker(config: UpgradeCheckConfig) -> UpgradeState {
+    let state = UpgradeState { enabled: true, ..UpgradeState::default() };
+    let task_state = state.clone();
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let mut interval = time::interval(config.interval);
+        loop {
+            interval.tick().await;
+            match check(&client, &config.releases_url).await {
+                Ok(status) => {
+                    if status.upgrade_available {
+                        tracing::warn!(
+                            current_version = BUILD_INFO.version,
+                            latest_version = status.latest_version,
+                            "A newer version is available"
+                        );
+                    }
+                    task_state.set_latest(status);
+                },
+                Err(error) => {
+                    tracing::error!(
+                        error = error.to_string(),
+                        "Failed to check for upgrades"
+                    );
+                },
+            }
+        }
+    });
+    state
+}
+
+async fn check(
+    client: &reqwest::Client,
+    releases_url: &str,
+) -> Result<UpgradeStatus, CheckError> {
+    let release: LatestRelease = client
+        .get(releases_url)
+        .header(
+            "User-Agent",
+            concat!("portable-issuer/", env!("CARGO_PKG_VERSION")),
+        )
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(CheckError::Fetch)?
+        .json()
+        .await
+        .map_err(CheckError::Fetch)?;
+    let raw_version = release
+        .version
+        .or(release.tag_name)
+        .ok_or(CheckError::MissingVersion)?;
+    let latest = Version::parse(raw_version.trim_start_matches('v'))
+        .map_err(CheckError::Parse)?;
+    let current =
+        Version::parse(BUILD_INFO.version).map_err(CheckError::Parse)?;
+    let checked_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    Ok(UpgradeStatus {
+        upgrade_available: latest > current,
+        latest_version: latest.to_string(),
+        checked_at,
+    })
+}
@@ -0,0 +1,71 @@
+use sqlx::{query, Pool};
+use thiserror::Error;
+
+use crate::{
+    api::{hash_password, PasswordError},
+    RDBMS,
+};
+
+#[derive(Debug, Error)]
+pub enum UserAdminError {
+    #[error("No user named {0:?}")]
+    NotFound(String),
+    #[error("Password must not be empty")]
+    EmptyPassword,
+    #[error("Failed to process password")]
+    Password(
+        #[source]
+        #[from]
+        PasswordError,
+    ),
+    #[error("Failed to manipulate database resources")]
+    Sqlx(
+        #[source]
+        #[from]
+        sqlx::Error,
+    ),
+}
+
+/// Grants or revokes administration rights.
+pub async fn set_admin(
+    pool: &Pool<RDBMS>,
+    name: &str,
+    is_admin: bool,
+) -> Result<(), UserAdminError> {
+    query("UPDATE users SET is_admin = ? WHERE name = ? RETURNING id")
+        .bind(is_admin)
+        .bind(name)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| UserAdminError::NotFound(name.to_owned()))?;
+    Ok(())
+}
+
+/// Replaces the password and ends every session of the user, so that
+/// whoever knew the old password is logged out.
+pub async fn reset_password(
+    pool: &Pool<RDBMS>,
+    name: &str,
+    password: String,
+) -> Result<(), UserAdminError> {
+    if password.is_empty() {
+        return Err(UserAdminError::EmptyPassword);
+    }
+    let password_hash = hash_password(password).await?;
+    let mut transaction = pool.begin().await?;
+    query("UPDATE users SET password_hash = ? WHERE name = ? RETURNING id")
+        .bind(password_hash)
+        .bind(name)
+        .fetch_optional(&mut *transaction)
+        .await?
+        .ok_or_else(|| UserAdminError::NotFound(name.to_owned()))?;
+    query(
+        "DELETE FROM sessions \
+         WHERE user = (SELECT id FROM users WHERE name = ?)",
+    )
+    .bind(name)
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
@@ -0,0 +1,15 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Serialize, JsonSchema)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    pub build_timestamp: &'static str,
+}
+
+pub const BUILD_INFO: BuildInfo = BuildInfo {
+    version: env!("CARGO_PKG_VERSION"),
+    git_commit: env!("PORTABLE_ISSUER_GIT_COMMIT"),
+    build_timestamp: env!("PORTABLE_ISSUER_BUILD_TIMESTAMP"),
+};
@@ -0,0 +1,894 @@
+//! Golden response fixtures for the public API.
+//!
+//! Every case is compared against `tests/contract/<api version>/<case>.json`.
+//! A shape change must either keep those files intact or land under a new
+//! API version. After an intended change within a version, rerun with
+//! `UPDATE_GOLDEN=1` to rewrite the fixtures and review the diff.
+//!
+//! Binary downloads, the SQLite export and database backups, have no JSON
+//! shape to compare and are left out.
+
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    body::{self, Body},
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use portable_issuer::{
+    session::SessionConfig,
+    upgrade::UpgradeState,
+    users,
+    HttpConfig,
+};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::{sqlite::SqliteConnectOptions, SqlitePool};
+use tower::ServiceExt;
+
+const API_VERSION: &str = "v1";
+
+/// Values that change from run to run and are replaced before comparison.
+const VOLATILE_KEYS: &[&str] = &[
+    "build_timestamp",
+    "checkpoint",
+    "created_at",
+    "expires_at",
+    "expires_in",
+    "exported_at",
+    "git_commit",
+    "instance_id",
+    "key_id",
+    "last_used_at",
+    "rank",
+    "secret",
+    "submitted_at",
+    "token",
+    "updated_at",
+];
+
+/// Fields added to the version after its fixtures were frozen. Clients
+/// ignore fields they do not know, so these are left out of the comparison
+/// instead of rewriting the fixtures, and checked by `added_fields_are_sent`.
+const ADDED_KEYS: &[&str] = &["issue_type", "request_id", "type_id", "types"];
+
+/// `ann:correct horse`, the credentials of the administrator.
+const BASIC_AUTH: &str = "Basic YW5uOmNvcnJlY3QgaG9yc2U=";
+
+struct Contract {
+    app: Router,
+    pool: SqlitePool,
+    fixtures: PathBuf,
+    update: bool,
+    failures: Vec<String>,
+}
+
+impl Contract {
+    async fn new(database: &Path) -> Self {
+        let options = SqliteConnectOptions::new()
+            .foreign_keys(true)
+            .filename(database)
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+        let sessions =
+            SessionConfig::new(None, Duration::from_secs(3600)).unwrap();
+        let app = portable_issuer::router(
+            "static",
+            pool.clone(),
+            UpgradeState::disabled(),
+            sessions,
+            None,
+            false,
+            HttpConfig::default(),
+        );
+        Self {
+            app,
+            pool,
+            fixtures: PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/contract")
+                .join(API_VERSION),
+            update: env::var_os("UPDATE_GOLDEN").is_some(),
+            failures: Vec::new(),
+        }
+    }
+
+    async fn call(
+        &self,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(format!("/api/{API_VERSION}{path}"));
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let body = match body {
+            Some(body) => {
+                builder =
+                    builder.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            },
+            None => Body::empty(),
+        };
+        let response = self
+            .app
+            .clone()
+            .oneshot(builder.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes =
+            body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+        (status, body)
+    }
+
+    async fn case(
+        &mut self,
+        name: &str,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<Value>,
+    ) {
+        let (status, body) = self.call(method, path, headers, body).await;
+        let actual = json!({
+            "status": status.as_u16(),
+            "body": normalize(body),
+        });
+        let path = self.fixtures.join(format!("{name}.json"));
+        if self.update {
+            fs::create_dir_all(&self.fixtures).unwrap();
+            let mut rendered = serde_json::to_string_pretty(&actual).unwrap();
+            rendered.push('\n');
+            fs::write(&path, rendered).unwrap();
+            return;
+        }
+        match fs::read(&path) {
+            Ok(bytes) => {
+                let expected: Value = serde_json::from_slice(&bytes).unwrap();
+                if expected != actual {
+                    self.failures.push(format!(
+                        "{name}: expected {expected}, found {actual}"
+                    ));
+                }
+            },
+            Err(_) => {
+                self.failures.push(format!("{name}: fixture is missing"));
+            },
+        }
+    }
+}
+
+fn normalize(value: Value) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .filter(|(key, _)| !ADDED_KEYS.contains(&key.as_str()))
+                .map(|(key, value)| {
+                    let value = if VOLATILE_KEYS.contains(&key.as_str())
+                        && !value.is_null()
+                    {
+                        Value::String(String::from("<volatile>"))
+                    } else {
+                        normalize(value)
+                    };
+                    (key, value)
+                })
+                .collect(),
+        ),
+        Value::Array(array) => {
+            Value::Array(array.into_iter().map(normalize).collect())
+        },
+        other => other,
+    }
+}
+
+#[tokio::test]
+async fn responses_match_golden_fixtures() {
+    let database = env::temp_dir()
+        .join(format!("portable-issuer-contract-{}.db", std::process::id()));
+    let _ = fs::remove_file(&database);
+    let mut contract = Contract::new(&database).await;
+    let auth = [("authorization", BASIC_AUTH)];
+
+    contract.case("version", Method::GET, "/version", &[], None).await;
+
+    let credentials = json!({ "name": "ann", "password": "correct horse" });
+    contract
+        .case(
+            "auth_register",
+            Method::POST,
+            "/auth/register",
+            &[],
+            Some(credentials),
+        )
+        .await;
+    users::set_admin(&contract.pool, "ann", true).await.unwrap();
+    contract
+        .case(
+            "auth_login_invalid",
+            Method::POST,
+            "/auth/login",
+            &[],
+            Some(json!({ "name": "ann", "password": "wrong" })),
+        )
+        .await;
+    contract.case("auth_me", Method::GET, "/auth/me", &auth, None).await;
+    contract
+        .case("auth_me_anonymous", Method::GET, "/auth/me", &[], None)
+        .await;
+    contract
+        .case(
+            "token_new",
+            Method::POST,
+            "/tokens/new",
+            &auth,
+            Some(json!({ "name": "ci" })),
+        )
+        .await;
+
+    contract
+        .case(
+            "status_new",
+            Method::POST,
+            "/status/new",
+            &[],
+            Some(json!({ "name": "open" })),
+        )
+        .await;
+    contract.case("status_get", Method::GET, "/status/id/1", &[], None).await;
+    contract
+        .case("status_not_found", Method::GET, "/status/id/99", &[], None)
+        .await;
+    contract
+        .case(
+            "status_patch_unconditional",
+            Method::PATCH,
+            "/status/id/1",
+            &[],
+            Some(json!({ "name": "opened" })),
+        )
+        .await;
+    contract
+        .case(
+            "status_patch_stale",
+            Method::PATCH,
+            "/status/id/1",
+            &[("if-match", "7")],
+            Some(json!({ "name": "opened" })),
+        )
+        .await;
+    contract
+        .case(
+            "status_bulk",
+            Method::POST,
+            "/status/bulk",
+            &[],
+            Some(json!([
+                { "op": "create", "name": "closed", "closed": true },
+                { "op": "patch", "id": 1, "version": 1, "name": "opened" },
+            ])),
+        )
+        .await;
+    contract.case("status_list", Method::GET, "/status/list/", &[], None).await;
+    contract
+        .case(
+            "status_list_bad_sort",
+            Method::GET,
+            "/status/list/?sort=color",
+            &[],
+            None,
+        )
+        .await;
+
+    contract
+        .case(
+            "label_new",
+            Method::POST,
+            "/label/new",
+            &[],
+            Some(json!({ "name": "bug" })),
+        )
+        .await;
+    contract.case("label_list", Method::GET, "/label/list/", &[], None).await;
+
+    contract
+        .case(
+            "issue_type_new",
+            Method::POST,
+            "/issue-type/new",
+            &[],
+            Some(json!({
+                "name": "bug",
+                "template": "Steps to reproduce:",
+                "status_ids": [1],
+            })),
+        )
+        .await;
+    contract
+        .case("issue_type_get", Method::GET, "/issue-type/id/1", &[], None)
+        .await;
+
+    contract
+        .case(
+            "issue_new",
+            Method::POST,
+            "/issue/new",
+            &[],
+            Some(json!({
+                "title": "Crash on startup",
+                "description": "The server crashes when started.",
+                "status_id": 1,
+            })),
+        )
+        .await;
+    contract
+        .case(
+            "issue_new_bad_reference",
+            Method::POST,
+            "/issue/new",
+            &[],
+            Some(json!({ "title": "Orphan", "status_id": 99 })),
+        )
+        .await;
+    contract
+        .case(
+            "issue_attach_label",
+            Method::PUT,
+            "/issue/id/1/labels/1",
+            &[],
+            None,
+        )
+        .await;
+    contract
+        .case(
+            "label_merge_preview_missing_target",
+            Method::POST,
+            "/label/id/1/merge-into/99?preview=true",
+            &[],
+            None,
+        )
+        .await;
+    contract.case("issue_get", Method::GET, "/issue/id/1", &[], None).await;
+    contract
+        .case(
+            "issue_get_jsonapi",
+            Method::GET,
+            "/issue/id/1?include=status,labels",
+            &[("accept", "application/vnd.api+json")],
+            None,
+        )
+        .await;
+    contract
+        .case(
+            "issue_patch",
+            Method::PATCH,
+            "/issue/id/1",
+            &[("if-match", "1")],
+            Some(json!({ "title": "Crash on first startup" })),
+        )
+        .await;
+    contract
+        .case("issue_list", Method::GET, "/issue/list/?limit=1", &[], None)
+        .await;
+    contract
+        .case(
+            "issue_list_bad_cursor",
+            Method::GET,
+            "/issue/list/?after=zz",
+            &[],
+            None,
+        )
+        .await;
+    contract
+        .case("issue_not_found", Method::GET, "/issue/id/99", &[], None)
+        .await;
+    contract.case("issue_form", Method::GET, "/issue/form", &auth, None).await;
+    contract
+        .case("issue_form_anonymous", Method::GET, "/issue/form", &[], None)
+        .await;
+
+    contract.case("search", Method::GET, "/search?q=crash", &[], None).await;
+    contract.case("search_empty", Method::GET, "/search?q=", &[], None).await;
+    contract.case("triage_queue", Method::GET, "/triage", &auth, None).await;
+    contract
+        .case("triage_queue_anonymous", Method::GET, "/triage", &[], None)
+        .await;
+    contract
+        .case(
+            "shape_camel_bare",
+            Method::GET,
+            "/status/id/1",
+            &[
+                ("x-response-casing", "camelCase"),
+                ("x-response-envelope", "bare"),
+            ],
+            None,
+        )
+        .await;
+
+    contract
+        .call(
+            Method::POST,
+            "/issue/new",
+            &[],
+            Some(json!({ "title": "Config is not read", "status_id": 1 })),
+        )
+        .await;
+    sqlx::query("INSERT INTO issue_blockings (blocker, blocked) VALUES (2, 1)")
+        .execute(&contract.pool)
+        .await
+        .unwrap();
+    contract
+        .case(
+            "issue_close_blocked",
+            Method::PATCH,
+            "/issue/id/1",
+            &[("if-match", "2")],
+            Some(json!({ "status_id": 2 })),
+        )
+        .await;
+    contract
+        .call(
+            Method::POST,
+            "/status/new",
+            &[],
+            Some(json!({ "name": "review" })),
+        )
+        .await;
+    contract
+        .call(
+            Method::PATCH,
+            "/issue/id/2",
+            &[("if-match", "1")],
+            Some(json!({ "status_id": 3 })),
+        )
+        .await;
+    contract
+        .case(
+            "status_close_blocked",
+            Method::PATCH,
+            "/status/id/1",
+            &[("if-match", "2")],
+            Some(json!({ "closed": true })),
+        )
+        .await;
+
+    // A bearer token skips the password hash that makes each Basic call slow.
+    let (_, issued) = contract
+        .call(
+            Method::POST,
+            "/tokens/new",
+            &auth,
+            Some(json!({ "name": "contract" })),
+        )
+        .await;
+    let bearer =
+        format!("Bearer {}", issued["data"]["token"].as_str().unwrap());
+    let auth = [("authorization", bearer.as_str())];
+
+    contract
+        .case(
+            "milestone_new",
+            Method::POST,
+            "/milestone/new",
+            &[],
+            Some(json!({
+                "name": "1.0",
+                "description": "First release",
+                "due_date": "2026-12-01",
+            })),
+        )
+        .await;
+    contract
+        .case(
+            "milestone_assign",
+            Method::PUT,
+            "/milestone/id/1/issues/1",
+            &[],
+            None,
+        )
+        .await;
+    contract
+        .case(
+            "milestone_progress",
+            Method::GET,
+            "/milestone/id/1/progress",
+            &[],
+            None,
+        )
+        .await;
+    contract
+        .case("milestone_list", Method::GET, "/milestone/list/", &[], None)
+        .await;
+
+    contract
+        .case(
+            "version_new",
+            Method::POST,
+            "/versions/new",
+            &[],
+            Some(json!({ "name": "1.0.0", "release_date": "2026-12-01" })),
+        )
+        .await;
+    contract
+        .case(
+            "version_release_notes",
+            Method::GET,
+            "/versions/id/1/release-notes",
+            &[],
+            None,
+        )
+        .await;
+    contract
+        .case("version_list", Method::GET, "/versions/list/", &[], None)
+        .await;
+
+    contract
+        .case(
+            "check_new",
+            Method::POST,
+            "/issue/id/1/checks",
+            &[],
+            Some(json!({
+                "name": "ci",
+                "state": "failure",
+                "url": "https://ci.example.com/1",
+                "summary": "2 tests failed",
+            })),
+        )
+        .await;
+    contract
+        .case("check_latest", Method::GET, "/issue/id/1/checks", &[], None)
+        .await;
+
+    contract
+        .case(
+            "canned_new",
+            Method::POST,
+            "/canned/new",
+            &auth,
+            Some(json!({
+                "name": "duplicate",
+                "body": "Duplicate of #{{issue}} ({{title}}), thanks {{user}}.",
+            })),
+        )
+        .await;
+    contract
+        .case(
+            "canned_render",
+            Method::GET,
+            "/canned/id/1/render/1",
+            &auth,
+            None,
+        )
+        .await;
+    contract
+        .case("canned_list", Method::GET, "/canned/list/", &auth, None)
+        .await;
+    contract
+        .case("canned_list_anonymous", Method::GET, "/canned/list/", &[], None)
+        .await;
+
+    contract
+        .case(
+            "presence_heartbeat",
+            Method::PUT,
+            "/presence/issue/1",
+            &auth,
+            Some(json!({ "typing": true })),
+        )
+        .await;
+    contract
+        .case("presence_viewers", Method::GET, "/presence/issue/1", &auth, None)
+        .await;
+
+    contract
+        .case("edit_lock_new", Method::POST, "/issue/id/1/lock", &auth, None)
+        .await;
+    contract
+        .case(
+            "edit_lock_release",
+            Method::DELETE,
+            "/issue/id/1/lock",
+            &auth,
+            None,
+        )
+        .await;
+
+    contract
+        .case(
+            "i18n_put",
+            Method::PUT,
+            "/i18n/pt-BR.json",
+            &auth,
+            Some(json!({ "issue.title": "Título" })),
+        )
+        .await;
+    contract
+        .case("i18n_bad_lang", Method::GET, "/i18n/pt_BR.json", &[], None)
+        .await;
+
+    contract.case("schema_list", Method::GET, "/schema/", &[], None).await;
+    contract
+        .case("schema_get", Method::GET, "/schema/NewCheckPayload", &[], None)
+        .await;
+
+    let (_, detached) =
+        contract.call(Method::DELETE, "/issue/id/1/labels/1", &[], None).await;
+    let token = detached["undo"]["token"].as_str().unwrap().to_owned();
+    contract
+        .case("undo", Method::POST, &format!("/undo/{token}"), &[], None)
+        .await;
+    contract
+        .case("undo_used", Method::POST, &format!("/undo/{token}"), &[], None)
+        .await;
+
+    contract
+        .case(
+            "signing_key_new",
+            Method::POST,
+            "/signing-keys/new",
+            &auth,
+            Some(json!({ "name": "ci" })),
+        )
+        .await;
+    contract
+        .case(
+            "signing_key_list",
+            Method::GET,
+            "/signing-keys/list/",
+            &auth,
+            None,
+        )
+        .await;
+
+    contract
+        .case(
+            "triage_session_new",
+            Method::POST,
+            "/triage/session",
+            &auth,
+            Some(json!({ "size": 5 })),
+        )
+        .await;
+    contract
+        .case(
+            "triage_session_submit",
+            Method::POST,
+            "/triage/session/1/submit",
+            &auth,
+            Some(json!({ "decisions": [{ "issue_id": 2, "label_ids": [1] }] })),
+        )
+        .await;
+
+    contract
+        .case("sync_instance", Method::GET, "/sync/instance", &auth, None)
+        .await;
+    let (_, instance) =
+        contract.call(Method::GET, "/sync/instance", &auth, None).await;
+    let checkpoint = instance["data"]["checkpoint"].as_i64().unwrap();
+    contract
+        .case(
+            "sync_apply",
+            Method::POST,
+            "/sync/apply",
+            &auth,
+            Some(json!({
+                "instance_id": "0123456789abcdef0123456789abcdef",
+                "seen": 0,
+                "changes": [{
+                    "seq": 1,
+                    "entity": "labels",
+                    "id": "fedcba9876543210fedcba9876543210",
+                    "deleted": false,
+                    "data": { "name": "regression" },
+                }],
+            })),
+        )
+        .await;
+    // Rows get random global ids, so only the feed after the last change
+    // is stable.
+    contract
+        .case(
+            "sync_changes",
+            Method::GET,
+            &format!("/sync/changes?since={}", checkpoint + 1),
+            &auth,
+            None,
+        )
+        .await;
+    contract
+        .case("sync_changes_anonymous", Method::GET, "/sync/changes", &[], None)
+        .await;
+
+    contract
+        .case("admin_upgrade", Method::GET, "/admin/upgrade", &auth, None)
+        .await;
+    contract
+        .case(
+            "admin_read_only",
+            Method::PUT,
+            "/admin/read-only",
+            &auth,
+            Some(json!({ "enabled": false })),
+        )
+        .await;
+    contract
+        .case(
+            "admin_maintenance",
+            Method::GET,
+            "/admin/maintenance",
+            &auth,
+            None,
+        )
+        .await;
+    contract
+        .case(
+            "admin_maintenance_anonymous",
+            Method::GET,
+            "/admin/maintenance",
+            &[],
+            None,
+        )
+        .await;
+
+    contract
+        .case(
+            "import",
+            Method::POST,
+            "/import",
+            &auth,
+            Some(json!({
+                "format": "portable-issuer-export",
+                "format_version": 1,
+                "tables": {
+                    "issue_statuses": [
+                        { "id": 1, "name": "opened", "closed": 0 },
+                    ],
+                    "labels": [{ "id": 1, "name": "docs" }],
+                    "issues": [{ "id": 1, "title": "Imported", "status": 1 }],
+                    "issue_labels": [{ "issue": 1, "label": 1 }],
+                },
+            })),
+        )
+        .await;
+    contract.case("export", Method::GET, "/export", &auth, None).await;
+
+    contract
+        .case(
+            "batch",
+            Method::POST,
+            "/batch",
+            &auth,
+            Some(json!([
+                { "method": "GET", "path": "/status/id/1" },
+                { "method": "GET", "path": "/label/id/99" },
+            ])),
+        )
+        .await;
+
+    let _ = fs::remove_file(&database);
+    assert!(
+        contract.failures.is_empty(),
+        "response contract changed:\n{}",
+        contract.failures.join("\n"),
+    );
+}
+
+#[tokio::test]
+async fn added_fields_are_sent() {
+    let database = env::temp_dir().join(format!(
+        "portable-issuer-contract-added-{}.db",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&database);
+    let contract = Contract::new(&database).await;
+
+    let (status, body) = contract
+        .call(
+            Method::GET,
+            "/status/id/99",
+            &[("x-request-id", "report-me")],
+            None,
+        )
+        .await;
+    contract
+        .call(Method::POST, "/status/new", &[], Some(json!({ "name": "open" })))
+        .await;
+    let (_, issue) = contract
+        .call(
+            Method::POST,
+            "/issue/new",
+            &[],
+            Some(json!({ "title": "Crash", "status_id": 1 })),
+        )
+        .await;
+    let (_, document) = contract
+        .call(
+            Method::GET,
+            "/issue/id/1",
+            &[("accept", "application/vnd.api+json")],
+            None,
+        )
+        .await;
+    let (_, form) = contract.call(Method::GET, "/issue/form", &[], None).await;
+    let _ = fs::remove_file(&database);
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["request_id"], "report-me");
+    assert!(form["data"]["types"].is_array());
+    assert!(issue["data"].get("type_id").is_some());
+    let relationships = &document["data"]["relationships"];
+    assert!(relationships.get("issue_type").is_some());
+}
+
+#[tokio::test]
+async fn signed_batch_runs_as_the_signer() {
+    let database = env::temp_dir().join(format!(
+        "portable-issuer-contract-batch-{}.db",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&database);
+    let contract = Contract::new(&database).await;
+    let auth = [("authorization", BASIC_AUTH)];
+    let credentials = json!({ "name": "ann", "password": "correct horse" });
+    contract.call(Method::POST, "/auth/register", &[], Some(credentials)).await;
+    let (_, key) = contract
+        .call(
+            Method::POST,
+            "/signing-keys/new",
+            &auth,
+            Some(json!({ "name": "ci" })),
+        )
+        .await;
+
+    let batch = json!([
+        { "method": "GET", "path": "/auth/me" },
+        { "method": "GET", "path": "/signing-keys/list/" },
+    ]);
+    let date = httpdate::fmt_http_date(SystemTime::now());
+    let digest = format!(
+        "SHA-256={}",
+        STANDARD.encode(Sha256::digest(batch.to_string().as_bytes()))
+    );
+    let message = format!("POST\n/api/{API_VERSION}/batch\n{date}\n{digest}\n");
+    let secret = key["data"]["secret"].as_str().unwrap();
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(message.as_bytes());
+    let authorization = format!(
+        "HMAC-SHA256 {}:{}",
+        key["data"]["key_id"].as_str().unwrap(),
+        STANDARD.encode(mac.finalize().into_bytes()),
+    );
+    let headers = [
+        ("authorization", authorization.as_str()),
+        ("date", date.as_str()),
+        ("digest", digest.as_str()),
+    ];
+    let (status, body) = contract
+        .call(Method::POST, "/batch", &headers, Some(batch.clone()))
+        .await;
+    let (replayed, _) =
+        contract.call(Method::POST, "/batch", &headers, Some(batch)).await;
+    let _ = fs::remove_file(&database);
+    assert_eq!(status, StatusCode::OK);
+    let list = &body["data"]["list"];
+    assert_eq!(list[0]["status"], 200);
+    assert_eq!(list[0]["body"]["data"]["name"], "ann");
+    assert_eq!(list[1]["status"], 200);
+    assert_eq!(list[1]["body"]["data"]["list"][0]["name"], "ci");
+    assert_eq!(replayed, StatusCode::UNAUTHORIZED);
+}
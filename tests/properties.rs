@@ -0,0 +1,214 @@
+//! Property tests for request inputs that reach a parser unchecked: search
+//! text, pagination cursors and static file paths.
+//!
+//! Each property drives the real router with generated input and only
+//! accepts the documented outcomes, so a parser that panics, leaks a file or
+//! hands malformed input to SQLite shows up as a failure with a shrunk
+//! counterexample.
+
+use std::{env, fs, path::PathBuf, time::Duration};
+
+use axum::{
+    body::{self, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use portable_issuer::{
+    demo,
+    session::SessionConfig,
+    upgrade::UpgradeState,
+    HttpConfig,
+};
+use proptest::{
+    prelude::*,
+    test_runner::{Config, TestRunner},
+};
+use sqlx::{sqlite::SqliteConnectOptions, SqlitePool};
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+
+const CASES: u32 = 128;
+
+const SECRET: &str = "outside of the static directory";
+
+struct Fixture {
+    runtime: Runtime,
+    app: Router,
+    root: PathBuf,
+}
+
+impl Fixture {
+    fn new(name: &str) -> Self {
+        let root = env::temp_dir().join(format!(
+            "portable-issuer-properties-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("static")).unwrap();
+        fs::write(root.join("static/index.html"), "<html></html>").unwrap();
+        fs::write(root.join("secret.txt"), SECRET).unwrap();
+        let runtime = Runtime::new().unwrap();
+        let app = runtime.block_on(async {
+            let options = SqliteConnectOptions::new()
+                .foreign_keys(true)
+                .filename(root.join("database.bin"))
+                .create_if_missing(true);
+            let pool = SqlitePool::connect_with(options).await.unwrap();
+            sqlx::migrate!().run(&pool).await.unwrap();
+            demo::seed(&pool).await.unwrap();
+            let sessions =
+                SessionConfig::new(None, Duration::from_secs(3600)).unwrap();
+            portable_issuer::router(
+                root.join("static"),
+                pool,
+                UpgradeState::disabled(),
+                sessions,
+                None,
+                false,
+                HttpConfig::default(),
+            )
+        });
+        Self { runtime, app, root }
+    }
+
+    fn get(&self, uri: &str) -> (StatusCode, String) {
+        self.runtime.block_on(async {
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            let response = self.app.clone().oneshot(request).await.unwrap();
+            let status = response.status();
+            let bytes =
+                body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, String::from_utf8_lossy(&bytes).into_owned())
+        })
+    }
+
+    fn check<S>(&self, strategy: S, test: impl Fn(&Self, S::Value))
+    where
+        S: Strategy,
+    {
+        let mut runner = TestRunner::new(Config::with_cases(CASES));
+        runner
+            .run(&strategy, |value| {
+                test(self, value);
+                Ok(())
+            })
+            .unwrap();
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Encodes every byte outside the unreserved set, so that generated input
+/// reaches the handlers exactly as written.
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'~' => {
+                char::from(byte).to_string()
+            },
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Free text mixed with FTS5 operators and quoting.
+fn search_text() -> impl Strategy<Value = String> {
+    let token = prop_oneof![
+        "[a-z]{1,8}",
+        Just(String::from("\"")),
+        Just(String::from("*")),
+        Just(String::from("-")),
+        Just(String::from("^")),
+        Just(String::from("AND")),
+        Just(String::from("OR")),
+        Just(String::from("NOT")),
+        Just(String::from("NEAR(")),
+        Just(String::from(")")),
+        Just(String::from("title:")),
+        Just(String::from("{title description}:")),
+    ];
+    prop_oneof![
+        any::<String>(),
+        prop::collection::vec(token, 0..8).prop_map(|tokens| tokens.join(" ")),
+    ]
+}
+
+/// Arbitrary text as well as hex strings, which get past the first checks
+/// of the cursor decoder.
+fn cursor_text() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<String>(),
+        "([0-9a-f]{2}){0,24}",
+        "-?[0-9]{1,20}:-?[0-9]{1,20}".prop_map(|plain| plain
+            .bytes()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()),
+    ]
+}
+
+/// Paths built from traversal components, separators and names of files
+/// inside and outside the static directory.
+fn static_path() -> impl Strategy<Value = String> {
+    let component = prop_oneof![
+        Just(String::from("..")),
+        Just(String::from(".")),
+        Just(String::new()),
+        Just(String::from("index.html")),
+        Just(String::from("secret.txt")),
+        Just(String::from("static")),
+        Just(String::from("\\..")),
+        Just(String::from("..\\secret.txt")),
+        "[a-z.]{1,6}",
+    ];
+    prop_oneof![
+        any::<String>(),
+        prop::collection::vec(component, 1..6)
+            .prop_map(|parts| parts.join("/")),
+    ]
+}
+
+#[test]
+fn search_text_is_never_parsed_as_query_syntax() {
+    let fixture = Fixture::new("search");
+    fixture.check(search_text(), |fixture, text| {
+        let uri = format!("/api/v1/search?q={}", percent_encode(&text));
+        let (status, body) = fixture.get(&uri);
+        let expected = match text.split_whitespace().next() {
+            Some(_) => StatusCode::OK,
+            None => StatusCode::BAD_REQUEST,
+        };
+        assert_eq!(status, expected, "query {text:?} answered {body}");
+    });
+}
+
+#[test]
+fn malformed_cursors_are_rejected() {
+    let fixture = Fixture::new("cursor");
+    fixture.check(cursor_text(), |fixture, cursor| {
+        let uri =
+            format!("/api/v1/issue/list/?after={}", percent_encode(&cursor));
+        let (status, body) = fixture.get(&uri);
+        assert!(
+            status == StatusCode::OK || status == StatusCode::BAD_REQUEST,
+            "cursor {cursor:?} answered {status} {body}",
+        );
+    });
+}
+
+#[test]
+fn static_paths_stay_inside_the_static_directory() {
+    let fixture = Fixture::new("static");
+    fixture.check(static_path(), |fixture, path| {
+        let uri = format!("/static/{}", percent_encode(&path));
+        let (status, body) = fixture.get(&uri);
+        assert!(!body.contains(SECRET), "path {path:?} leaked a file");
+        assert!(
+            !status.is_server_error(),
+            "path {path:?} answered {status} {body}",
+        );
+    });
+}